@@ -0,0 +1,366 @@
+//! Portable archive of the whole library content and play history
+//!
+//! An archive is a JSON Lines file, every line holds one JSON object.
+//! The first line is the `Manifest`, identifying the format and version and counting the records.
+//! Every following line is a `Record`, tagged by `type` with the entity in `data`:
+//!
+//! ```text
+//! {"format":"rustic-archive","version":1,"schema_version":3,"created_at":1700000000,...}
+//! {"type":"artist","data":{"id":3,"name":"Artist","uri":"local:artist:1",...}}
+//! {"type":"track","data":{"id":7,"title":"Track","artist_id":3,...}}
+//! {"type":"annotation","data":{"uri":"local:track:1","annotation":{"favourite":true,"rating":4}}}
+//! ```
+//!
+//! The entities are written with the current schema version of the library models,
+//! archives of older versions are migrated on import, see `library::migration`.
+//!
+//! Relations are stored by the ids of the exporting library. Importing syncs all entities
+//! by uri, maps the old ids to the ids assigned by the target library and relinks all
+//! relations, so archives can be imported into any `Library`, even a non-empty one.
+//! Plays already in the history with the same track uri and time are skipped, so importing
+//! an archive again doesn't count its plays twice.
+use log::debug;
+use failure::{Error, Fail};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::history::{History, Play};
+use crate::library::migration::{self, SCHEMA_VERSION};
+use crate::library::{Album, Annotation, Artist, EntityKind, Library, Playlist, Track};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, Write};
+use std::mem;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const ARCHIVE_FORMAT: &str = "rustic-archive";
+/// Version written by `export`
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// First line of an archive
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: String,
+    /// Version of the archive layout
+    pub version: u32,
+    /// Version of the entities in the records, see `library::migration`
+    #[serde(default)]
+    pub schema_version: u32,
+    /// Unix timestamp of the export
+    pub created_at: u64,
+    pub artists: usize,
+    pub albums: usize,
+    pub tracks: usize,
+    pub playlists: usize,
+    pub annotations: usize,
+    pub plays: usize,
+}
+
+impl Manifest {
+    fn new() -> Manifest {
+        Manifest {
+            format: ARCHIVE_FORMAT.to_owned(),
+            version: ARCHIVE_VERSION,
+            schema_version: SCHEMA_VERSION,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|timestamp| timestamp.as_secs())
+                .unwrap_or_default(),
+            artists: 0,
+            albums: 0,
+            tracks: 0,
+            playlists: 0,
+            annotations: 0,
+            plays: 0,
+        }
+    }
+
+    fn count(&mut self, record: &Record) {
+        match record {
+            Record::Artist(_) => self.artists += 1,
+            Record::Album(_) => self.albums += 1,
+            Record::Track(_) => self.tracks += 1,
+            Record::Playlist(_) => self.playlists += 1,
+            Record::Annotation { .. } => self.annotations += 1,
+            Record::Play(_) => self.plays += 1,
+        }
+    }
+
+    fn counts(&self) -> [usize; 6] {
+        [
+            self.artists,
+            self.albums,
+            self.tracks,
+            self.playlists,
+            self.annotations,
+            self.plays,
+        ]
+    }
+}
+
+/// Single line of an archive after the manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "lowercase")]
+pub enum Record {
+    Artist(Artist),
+    Album(Album),
+    Track(Track),
+    Playlist(Playlist),
+    Annotation { uri: String, annotation: Annotation },
+    Play(Play),
+}
+
+#[derive(Debug, Fail)]
+pub enum ArchiveError {
+    /// The archive is empty
+    #[fail(display = "Archive has no manifest")]
+    MissingManifest,
+    /// The archive doesn't start with a manifest
+    #[fail(display = "Invalid archive manifest")]
+    InvalidManifest(#[cause] serde_json::Error),
+    #[fail(display = "Unsupported archive format {}", _0)]
+    UnsupportedFormat(String),
+    /// The archive was written by a newer version
+    #[fail(display = "Unsupported archive version {}", _0)]
+    UnsupportedVersion(u32),
+    #[fail(display = "Invalid archive record")]
+    InvalidRecord(#[cause] serde_json::Error),
+    /// The records don't match the counts of the manifest, e.g. because the file was truncated
+    #[fail(display = "Archive doesn't match its manifest")]
+    Incomplete,
+    #[fail(display = "Can't access archive")]
+    Io(#[cause] io::Error),
+}
+
+/// Writes the value as a single line
+fn write_line<W: Write, T: serde::Serialize>(writer: &mut W, value: &T) -> Result<(), ArchiveError> {
+    serde_json::to_writer(&mut *writer, value).map_err(|e| ArchiveError::Io(e.into()))?;
+    writer.write_all(b"\n").map_err(ArchiveError::Io)
+}
+
+/// Writes the library and history to `writer` and returns the written manifest
+pub fn export<W: Write>(
+    library: &dyn Library,
+    history: &History,
+    mut writer: W,
+) -> Result<Manifest, Error> {
+    let mut records: Vec<Record> = vec![];
+    records.extend(library.get_artists()?.into_iter().map(Record::Artist));
+    records.extend(library.get_albums()?.into_iter().map(Record::Album));
+    records.extend(library.get_tracks()?.into_iter().map(Record::Track));
+    records.extend(library.get_playlists()?.into_iter().map(Record::Playlist));
+    let mut annotations: Vec<(String, Annotation)> =
+        library.get_annotations()?.into_iter().collect();
+    annotations.sort_by(|a, b| a.0.cmp(&b.0));
+    records.extend(
+        annotations
+            .into_iter()
+            .map(|(uri, annotation)| Record::Annotation { uri, annotation }),
+    );
+    records.extend(history.plays().into_iter().map(Record::Play));
+
+    let mut manifest = Manifest::new();
+    for record in &records {
+        manifest.count(record);
+    }
+    write_line(&mut writer, &manifest)?;
+    for record in &records {
+        write_line(&mut writer, record)?;
+    }
+    writer.flush().map_err(ArchiveError::Io)?;
+    Ok(manifest)
+}
+
+/// Reads and validates the whole archive, then imports it into the library and history
+///
+/// Nothing is imported when the archive is invalid. Returns the manifest of the archive.
+pub fn import<R: BufRead>(
+    library: &dyn Library,
+    history: &History,
+    reader: R,
+) -> Result<Manifest, Error> {
+    let mut lines = reader.lines().filter(|line| {
+        line.as_ref().map(|line| !line.trim().is_empty()).unwrap_or(true)
+    });
+    let manifest: Manifest = match lines.next() {
+        Some(line) => {
+            let line = line.map_err(ArchiveError::Io)?;
+            serde_json::from_str(&line).map_err(ArchiveError::InvalidManifest)?
+        }
+        None => return Err(ArchiveError::MissingManifest.into()),
+    };
+    if manifest.format != ARCHIVE_FORMAT {
+        return Err(ArchiveError::UnsupportedFormat(manifest.format).into());
+    }
+    if manifest.version > ARCHIVE_VERSION {
+        return Err(ArchiveError::UnsupportedVersion(manifest.version).into());
+    }
+    migration::check_version(manifest.schema_version)?;
+    let mut counted = Manifest::new();
+    let mut records = vec![];
+    for line in lines {
+        let line = line.map_err(ArchiveError::Io)?;
+        let mut record: Value = serde_json::from_str(&line).map_err(ArchiveError::InvalidRecord)?;
+        migrate_record(&mut record, manifest.schema_version)?;
+        let record: Record = serde_json::from_value(record).map_err(ArchiveError::InvalidRecord)?;
+        counted.count(&record);
+        records.push(record);
+    }
+    if counted.counts() != manifest.counts() {
+        return Err(ArchiveError::Incomplete.into());
+    }
+    debug!("Importing archive created at {}", manifest.created_at);
+    Importer::default().import(library, history, records)?;
+    Ok(manifest)
+}
+
+/// Upgrades the entities of a record written with an older schema version
+fn migrate_record(record: &mut Value, version: u32) -> Result<(), Error> {
+    if version == SCHEMA_VERSION {
+        return Ok(());
+    }
+    let kind = match record.get("type").and_then(Value::as_str) {
+        Some("artist") => EntityKind::Artist,
+        Some("album") => EntityKind::Album,
+        Some("track") => EntityKind::Track,
+        Some("playlist") => EntityKind::Playlist,
+        Some("play") => {
+            return match record.pointer_mut("/data/track") {
+                Some(track) => migration::migrate_entity(EntityKind::Track, track, version),
+                None => Ok(()),
+            };
+        }
+        _ => return Ok(()),
+    };
+    match record.get_mut("data") {
+        Some(data) => migration::migrate_entity(kind, data, version),
+        None => Ok(()),
+    }
+}
+
+/// Entities imported so far by their id in the archive
+#[derive(Default)]
+struct Importer {
+    artists: HashMap<usize, Artist>,
+    albums: HashMap<usize, Album>,
+    tracks: HashMap<usize, Track>,
+}
+
+/// Points the reference at the imported entity
+///
+/// References to entities missing in the archive are cleared, an embedded copy is kept
+/// so syncing links it by uri.
+fn relink<T: Clone>(
+    id: &mut Option<usize>,
+    copy: &mut Option<T>,
+    imported: &HashMap<usize, T>,
+    new_id: fn(&T) -> Option<usize>,
+) {
+    if let Some(old_id) = *id {
+        match imported.get(&old_id) {
+            Some(entity) => {
+                *id = new_id(entity);
+                if copy.is_some() {
+                    *copy = Some(entity.clone());
+                }
+            }
+            None => *id = None,
+        }
+    }
+}
+
+impl Importer {
+    fn import(
+        mut self,
+        library: &dyn Library,
+        history: &History,
+        records: Vec<Record>,
+    ) -> Result<(), Error> {
+        let mut artists = vec![];
+        let mut albums = vec![];
+        let mut tracks = vec![];
+        let mut playlists = vec![];
+        let mut annotations = vec![];
+        let mut plays = vec![];
+        for record in records {
+            match record {
+                Record::Artist(artist) => artists.push(artist),
+                Record::Album(album) => albums.push(album),
+                Record::Track(track) => tracks.push(track),
+                Record::Playlist(playlist) => playlists.push(playlist),
+                Record::Annotation { uri, annotation } => annotations.push((uri, annotation)),
+                Record::Play(play) => plays.push(play),
+            }
+        }
+
+        // the entities are written at once, the plays refer to them and follow afterwards
+        library.batch(&mut |library| {
+            for mut artist in mem::take(&mut artists) {
+                let old_id = artist.id.take();
+                artist.albums.clear();
+                artist.top_tracks.clear();
+                library.sync_artist(&mut artist)?;
+                if let Some(old_id) = old_id {
+                    self.artists.insert(old_id, artist);
+                }
+            }
+            for mut album in mem::take(&mut albums) {
+                let old_id = album.id.take();
+                album.tracks.clear();
+                relink(&mut album.artist_id, &mut album.artist, &self.artists, |artist| artist.id);
+                library.sync_album(&mut album)?;
+                if let Some(old_id) = old_id {
+                    self.albums.insert(old_id, album);
+                }
+            }
+            for mut track in mem::take(&mut tracks) {
+                let old_id = track.id.take();
+                self.relink_track(&mut track);
+                library.sync_track(&mut track)?;
+                if let Some(old_id) = old_id {
+                    self.tracks.insert(old_id, track);
+                }
+            }
+            for mut playlist in mem::take(&mut playlists) {
+                playlist.id = None;
+                for entry in &mut playlist.tracks {
+                    match entry.id.and_then(|id| self.tracks.get(&id)) {
+                        Some(track) => *entry = track.clone(),
+                        None => {
+                            entry.id = None;
+                            self.relink_track(entry);
+                        }
+                    }
+                }
+                library.sync_playlist(&mut playlist)?;
+            }
+            for (uri, annotation) in mem::take(&mut annotations) {
+                library.set_annotation(&uri, annotation)?;
+            }
+            Ok(())
+        })?;
+        let recorded: HashSet<(String, SystemTime)> = history
+            .plays()
+            .into_iter()
+            .map(|play| (play.track.uri, play.played_at))
+            .collect();
+        let plays = plays
+            .into_iter()
+            .filter(|play| !recorded.contains(&(play.track.uri.clone(), play.played_at)))
+            .map(|mut play| {
+                match play.track.id.and_then(|id| self.tracks.get(&id)) {
+                    Some(track) => play.track = track.clone(),
+                    None => {
+                        play.track.id = None;
+                        self.relink_track(&mut play.track);
+                    }
+                }
+                play
+            })
+            .collect();
+        history.record_all(plays)
+    }
+
+    fn relink_track(&self, track: &mut Track) {
+        relink(&mut track.album_id, &mut track.album, &self.albums, |album| album.id);
+        relink(&mut track.artist_id, &mut track.artist, &self.artists, |artist| artist.id);
+    }
+}
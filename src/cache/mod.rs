@@ -1,11 +1,12 @@
 use log::{info, error, trace, debug};
+use serde_derive::{Deserialize, Serialize};
 use failure::Error;
 use image;
 use image::FilterType;
 use md5;
 use reqwest::get;
 use std::collections::HashMap;
-use std::fs::{create_dir_all, OpenOptions};
+use std::fs::{self, create_dir_all, OpenOptions};
 use std::io::prelude::*;
 use std::path::Path;
 use std::sync::{Arc, Condvar, Mutex, RwLock};
@@ -13,6 +14,7 @@ use std::thread;
 use std::time::Duration;
 use crate::Rustic;
 
+const COVERART_PATH: &str = ".cache/coverart";
 const THUMBNAIL_SIZE: u32 = 512;
 const SERVICE_INTERVAL: u64 = 30;
 
@@ -29,11 +31,19 @@ pub struct Cache {
 
 pub type SharedCache = Arc<Cache>;
 
+/// Storage used by the cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStatistics {
+    pub coverart: usize,
+    /// Size of the cached coverart files in bytes
+    pub coverart_bytes: u64,
+}
+
 pub fn start(
     app: Arc<Rustic>,
     running: Arc<(Mutex<bool>, Condvar)>,
 ) -> Result<thread::JoinHandle<()>, Error> {
-    create_dir_all(".cache/coverart")?;
+    create_dir_all(COVERART_PATH)?;
 
     thread::Builder::new()
         .name("Coverart Cache".into())
@@ -78,10 +88,9 @@ pub fn start(
 
 fn cache_coverart(uri: String) -> Result<CachedEntry, Error> {
     trace!("cache_coverart (uri: {})", &uri);
-    let base = ".cache/coverart";
     let hash = md5::compute(&uri);
     let filename = format!("{:x}.png", hash);
-    let path = format!("{}/{}", base, filename);
+    let path = format!("{}/{}", COVERART_PATH, filename);
     if Path::new(&path).exists() {
         trace!("file already exists");
         return Ok(CachedEntry { filename, uri });
@@ -110,6 +119,20 @@ impl Cache {
         Cache::default()
     }
 
+    /// Sums up the cached entries, files which went missing count as empty
+    pub fn statistics(&self) -> CacheStatistics {
+        let map = self.coverart.read().unwrap();
+        let coverart_bytes = map
+            .values()
+            .filter_map(|filename| fs::metadata(Path::new(COVERART_PATH).join(filename)).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        CacheStatistics {
+            coverart: map.len(),
+            coverart_bytes,
+        }
+    }
+
     pub fn fetch_coverart(&self, uri: String) -> Result<String, Error> {
         trace!("fetch_coverart (uri: {})", &uri);
         {
@@ -0,0 +1,354 @@
+use log::{debug, error, trace};
+use failure::Error;
+use serde_derive::{Deserialize, Serialize};
+use crate::channel;
+use crate::library::{SharedLibrary, Track};
+use crate::player::{PlayerBackend, PlayerEvent, PlayerState};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+pub type SharedHistory = Arc<History>;
+
+/// Listening time after which a track counts as played
+///
+/// A track is played once it was listened to for `fraction` of its duration or for `duration`,
+/// whichever comes first. Tracks without a duration need the full `duration`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PlayThreshold {
+    pub fraction: f64,
+    pub duration: Duration,
+}
+
+impl Default for PlayThreshold {
+    fn default() -> PlayThreshold {
+        PlayThreshold {
+            fraction: 0.5,
+            duration: Duration::from_secs(4 * 60),
+        }
+    }
+}
+
+impl PlayThreshold {
+    /// Listening time required for the given track, `Track::duration` is in seconds
+    pub fn for_track(&self, track: &Track) -> Duration {
+        match track.duration {
+            Some(duration) => {
+                let fraction = Duration::from_secs(duration).mul_f64(self.fraction);
+                fraction.min(self.duration)
+            }
+            None => self.duration,
+        }
+    }
+}
+
+/// A single recorded play
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Play {
+    pub track: Track,
+    pub played_at: SystemTime,
+}
+
+/// Plays of a single track
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayStats {
+    pub track: Track,
+    pub play_count: u64,
+    pub last_played: SystemTime,
+}
+
+/// Records plays reported by `PlayTracker`s, tracks are identified by uri
+///
+/// A history created with `with_library` stores every play in the library,
+/// so it survives restarts of persistent libraries.
+#[derive(Default)]
+pub struct History {
+    threshold: Mutex<PlayThreshold>,
+    store: Mutex<Store>,
+    library: Option<SharedLibrary>,
+}
+
+#[derive(Debug, Default)]
+struct Store {
+    plays: Vec<Play>,
+    stats: HashMap<String, PlayStats>,
+}
+
+impl Store {
+    fn add(&mut self, play: Play) {
+        let stats = self
+            .stats
+            .entry(play.track.uri.clone())
+            .or_insert_with(|| PlayStats {
+                track: play.track.clone(),
+                play_count: 0,
+                last_played: play.played_at,
+            });
+        stats.track = play.track.clone();
+        stats.play_count += 1;
+        stats.last_played = stats.last_played.max(play.played_at);
+        self.plays.push(play);
+    }
+}
+
+impl History {
+    pub fn new(threshold: PlayThreshold) -> History {
+        History {
+            threshold: Mutex::new(threshold),
+            store: Mutex::default(),
+            library: None,
+        }
+    }
+
+    /// Creates a history stored in `library`, starting with the plays already stored there
+    pub fn with_library(threshold: PlayThreshold, library: SharedLibrary) -> Result<History, Error> {
+        let mut store = Store::default();
+        for play in library.get_plays()? {
+            store.add(play);
+        }
+        Ok(History {
+            threshold: Mutex::new(threshold),
+            store: Mutex::new(store),
+            library: Some(library),
+        })
+    }
+
+    pub fn threshold(&self) -> PlayThreshold {
+        *self.threshold.lock().unwrap()
+    }
+
+    /// Changes the threshold for tracks started afterwards
+    pub fn set_threshold(&self, threshold: PlayThreshold) {
+        *self.threshold.lock().unwrap() = threshold;
+    }
+
+    /// Records a play, it is only kept in memory when the library can't store it
+    pub fn record(&self, track: Track, played_at: SystemTime) -> Result<(), Error> {
+        debug!("Recording play of {}", track.uri);
+        let play = Play { track, played_at };
+        if let Some(library) = &self.library {
+            library.add_play(play.clone())?;
+        }
+        self.store.lock().unwrap().add(play);
+        Ok(())
+    }
+
+    /// Records multiple plays, the library stores them in one batch
+    pub fn record_all(&self, plays: Vec<Play>) -> Result<(), Error> {
+        debug!("Recording {} plays", plays.len());
+        if let Some(library) = &self.library {
+            library.batch(&mut |library| {
+                plays.iter().try_for_each(|play| library.add_play(play.clone()))
+            })?;
+        }
+        let mut store = self.store.lock().unwrap();
+        for play in plays {
+            store.add(play);
+        }
+        Ok(())
+    }
+
+    /// Returns the stats of the track with the given uri or None when it was never played
+    pub fn stats(&self, uri: &str) -> Option<PlayStats> {
+        self.store.lock().unwrap().stats.get(uri).cloned()
+    }
+
+    /// Returns all plays in the order they were recorded
+    pub fn plays(&self) -> Vec<Play> {
+        self.store.lock().unwrap().plays.clone()
+    }
+
+    /// Returns up to `limit` plays, latest first
+    pub fn recently_played(&self, limit: usize) -> Vec<Play> {
+        let store = self.store.lock().unwrap();
+        let mut plays = store.plays.clone();
+        plays.sort_by_key(|play| Reverse(play.played_at));
+        plays.truncate(limit);
+        plays
+    }
+
+    /// Returns up to `limit` tracks with the most plays, ties are ordered by last play
+    pub fn most_played(&self, limit: usize) -> Vec<PlayStats> {
+        let store = self.store.lock().unwrap();
+        let mut stats: Vec<PlayStats> = store.stats.values().cloned().collect();
+        stats.sort_by(|a, b| {
+            b.play_count
+                .cmp(&a.play_count)
+                .then(b.last_played.cmp(&a.last_played))
+        });
+        stats.truncate(limit);
+        stats
+    }
+
+    /// Spawns a thread recording the plays of the given player until it stops sending events
+    pub fn observe_player(history: &SharedHistory, id: String, player: &Arc<Box<dyn PlayerBackend>>) {
+        let history = Arc::clone(history);
+        let events = player.observe();
+        let mut tracker = PlayTracker::new(player.state());
+        if let Some(track) = player.current() {
+            tracker.handle(&PlayerEvent::TrackChanged(track), Instant::now(), history.threshold());
+        }
+        let spawned = thread::Builder::new()
+            .name(format!("History {}", id))
+            .spawn(move || {
+                tracker.run(&events, || history.threshold(), |_| {}, |track, played_at| {
+                    let uri = track.uri.clone();
+                    if let Err(e) = history.record(track, played_at) {
+                        error!("Can't store play of {}: {}", uri, e);
+                    }
+                });
+                trace!("Player {} stopped sending events", id);
+            });
+        if let Err(e) = spawned {
+            debug!("Can't observe player: {}", e);
+        }
+    }
+}
+
+/// Measures the listening time of the current track of a single player
+///
+/// Only time spent in `PlayerState::Play` counts, seeking doesn't change the listening time.
+/// A play is reported with the instant it crossed the threshold, either by `poll` once
+/// `remaining` has elapsed or by `handle` when an event arrives after the threshold.
+#[derive(Debug)]
+pub struct PlayTracker {
+    state: PlayerState,
+    current: Option<Current>,
+}
+
+#[derive(Debug)]
+struct Current {
+    track: Track,
+    threshold: Duration,
+    listened: Duration,
+    resumed: Option<Instant>,
+    recorded: bool,
+}
+
+impl Current {
+    /// Returns the instant the threshold was crossed if it was crossed before `now`
+    fn crossed(&self, now: Instant) -> Option<Instant> {
+        if self.recorded {
+            return None;
+        }
+        let remaining = self.threshold.saturating_sub(self.listened);
+        let resumed = self.resumed?;
+        if now.duration_since(resumed) >= remaining {
+            Some(resumed + remaining)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the listening time left at `now` while playing and not yet recorded
+    fn remaining(&self, now: Instant) -> Option<Duration> {
+        if self.recorded {
+            return None;
+        }
+        let resumed = self.resumed?;
+        let listened = self.listened + now.saturating_duration_since(resumed);
+        Some(self.threshold.saturating_sub(listened))
+    }
+}
+
+impl PlayTracker {
+    pub fn new(state: PlayerState) -> PlayTracker {
+        PlayTracker {
+            state,
+            current: None,
+        }
+    }
+
+    /// Returns how long the current track has to keep playing until it counts as played
+    ///
+    /// None when nothing is playing or the current track was already reported.
+    pub fn remaining(&self, now: Instant) -> Option<Duration> {
+        self.current.as_ref()?.remaining(now)
+    }
+
+    /// Returns the current track once it counts as played, each track is reported only once
+    pub fn poll(&mut self, now: Instant) -> Option<(Track, Instant)> {
+        let current = self.current.as_mut()?;
+        let at = current.crossed(now)?;
+        current.recorded = true;
+        Some((current.track.clone(), at))
+    }
+
+    /// Handles the events of a player until it stops sending them
+    ///
+    /// `played` is called with each track counting as played and the time it crossed the
+    /// threshold, without waiting for the next event, `observed` is called with every event.
+    pub fn run<T, O, P>(
+        &mut self,
+        events: &channel::Receiver<PlayerEvent>,
+        threshold: T,
+        mut observed: O,
+        mut played: P,
+    ) where
+        T: Fn() -> PlayThreshold,
+        O: FnMut(&PlayerEvent),
+        P: FnMut(Track, SystemTime),
+    {
+        loop {
+            // None when the current track crossed the threshold without an event
+            let received = match self.remaining(Instant::now()) {
+                Some(remaining) => {
+                    let timeout = channel::after(remaining);
+                    channel::Select::new()
+                        .recv(events, Some)
+                        .recv(&timeout, |_| None)
+                        .wait()
+                }
+                None => Some(events.recv()),
+            };
+            let now = Instant::now();
+            let (play, event) = match received {
+                Some(Some(event)) => (self.handle(&event, now, threshold()), Some(event)),
+                Some(None) => break,
+                None => (self.poll(now), None),
+            };
+            if let Some((track, at)) = play {
+                played(track, SystemTime::now() - now.duration_since(at));
+            }
+            if let Some(event) = event {
+                observed(&event);
+            }
+        }
+    }
+
+    /// Updates the listening time and returns the current track once it counts as played
+    pub fn handle(
+        &mut self,
+        event: &PlayerEvent,
+        now: Instant,
+        threshold: PlayThreshold,
+    ) -> Option<(Track, Instant)> {
+        let play = self.poll(now);
+        match event {
+            PlayerEvent::TrackChanged(track) => {
+                self.current = Some(Current {
+                    track: track.clone(),
+                    threshold: threshold.for_track(track),
+                    listened: Duration::from_secs(0),
+                    resumed: Some(now).filter(|_| self.state == PlayerState::Play),
+                    recorded: false,
+                });
+            }
+            PlayerEvent::StateChanged(state) => {
+                self.state = *state;
+                if let Some(current) = &mut self.current {
+                    if let Some(resumed) = current.resumed.take() {
+                        current.listened += now.duration_since(resumed);
+                    }
+                    if *state == PlayerState::Play {
+                        current.resumed = Some(now);
+                    }
+                }
+            }
+            _ => {}
+        }
+        play
+    }
+}
@@ -1,26 +1,51 @@
+use log::debug;
+use serde_derive::{Deserialize, Serialize};
 use failure::format_err;
+use crate::library::PlaylistError;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use crossbeam_channel as channel;
 use url::Url;
 
-pub use crate::library::{Album, Artist, Library, Playlist, SearchResults, SharedLibrary, Track};
+pub use crate::library::{
+    Album, Annotation, Artist, CanonicalTrack, FileLibrary, Genre, Library, MemoryLibrary, Playlist, PlaylistEdit, SearchResults,
+    SharedLibrary, Track,
+};
 pub use crate::player::{PlayerBackend, PlayerEvent, PlayerState};
+pub use crate::playlist_file::{ImportResult, PlaylistFile, PlaylistFormat};
 pub use crate::provider::{Explorer, Provider};
+pub use crate::search::{FederatedSearchResults, ProviderSearchFailure, SearchFailureReason};
 
+pub mod archive;
 pub mod cache;
+pub mod history;
 pub mod library;
 pub mod player;
+pub mod playlist_file;
 pub mod provider;
+pub mod scrobble;
+pub mod search;
 pub mod sync;
 
+/// Aggregates for a dashboard, see `Rustic::statistics`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Statistics {
+    pub library: library::LibraryStatistics,
+    pub cache: cache::CacheStatistics,
+}
+
 pub struct Rustic {
     player: Arc<Mutex<HashMap<String, Arc<Box<dyn PlayerBackend>>>>>,
     pub library: library::SharedLibrary,
     pub providers: provider::SharedProviders,
     pub cache: cache::SharedCache,
+    pub history: history::SharedHistory,
+    scrobblers: Mutex<Vec<scrobble::SharedScrobbler>>,
+    source_preference: Mutex<Vec<Provider>>,
     default_player: Arc<Mutex<Option<String>>>,
+    searches: search::SearchPool,
 }
 
 impl Rustic {
@@ -29,20 +54,42 @@ impl Rustic {
         providers: provider::SharedProviders,
     ) -> Result<Arc<Rustic>, failure::Error> {
         let library = Arc::new(library);
+        let history = history::History::with_library(
+            history::PlayThreshold::default(),
+            Arc::clone(&library),
+        )?;
         Ok(Arc::new(Rustic {
             player: Arc::new(Mutex::new(HashMap::new())),
             library,
             providers,
             cache: Arc::new(cache::Cache::new()),
+            history: Arc::new(history),
+            scrobblers: Mutex::new(vec![]),
+            source_preference: Mutex::new(vec![]),
             default_player: Arc::new(Mutex::new(None)),
+            searches: search::SearchPool::default(),
         }))
     }
 
+    /// Registers a player, its plays are recorded in `history`
     pub fn add_player(&self, id: String, backend: Arc<Box<dyn PlayerBackend>>) {
+        history::History::observe_player(&self.history, id.clone(), &backend);
+        for scrobbler in self.scrobblers.lock().unwrap().iter() {
+            scrobble::Scrobbler::observe_player(scrobbler, id.clone(), &backend);
+        }
         let mut player = self.player.lock().unwrap();
         player.insert(id, backend);
     }
 
+    /// Scrobbles the tracks of all current and future players and retries queued listens
+    pub fn add_scrobbler(&self, scrobbler: scrobble::SharedScrobbler) {
+        for (id, player) in self.player.lock().unwrap().iter() {
+            scrobble::Scrobbler::observe_player(&scrobbler, id.clone(), player);
+        }
+        scrobble::Scrobbler::retry_queued(&scrobbler);
+        self.scrobblers.lock().unwrap().push(scrobbler);
+    }
+
     pub fn get_player(&self, id: String) -> Option<Arc<Box<dyn PlayerBackend>>> {
         let player = self.player.lock().unwrap();
         player.get(&id).map(Arc::clone)
@@ -63,11 +110,7 @@ impl Rustic {
     }
 
     pub fn resolve_track(&self, uri: &str) -> Result<Option<Track>, failure::Error> {
-        let track = self
-            .library
-            .get_tracks()?
-            .into_iter()
-            .find(|track| track.uri == uri);
+        let track = self.library.get_track_by_uri(uri)?;
 
         match track {
             Some(track) => Ok(Some(track)),
@@ -86,10 +129,219 @@ impl Rustic {
         }
     }
 
+    /// Returns the stream url of the preferred available source of the track
+    ///
+    /// The sources are the duplicates of the track in the library, see `CanonicalTrack`.
+    /// A source is skipped when its provider isn't registered or can't stream it.
     pub fn stream_url(&self, track: &Track) -> Result<String, failure::Error> {
+        let canonical = self.library.get_canonical_track(&track.uri)?;
+        let sources = match &canonical {
+            Some(canonical) => canonical.preferred_sources(&self.source_preference()),
+            None => vec![track],
+        };
+        let mut error = None;
+        for source in sources {
+            match self.source_stream_url(source) {
+                Ok(url) => return Ok(url),
+                Err(e) => {
+                    debug!("Can't stream {}: {}", source.uri, e);
+                    error = Some(e);
+                }
+            }
+        }
+        Err(error.unwrap_or_else(|| format_err!("no source for track {:?} found", track)))
+    }
+
+    fn source_stream_url(&self, track: &Track) -> Result<String, failure::Error> {
         self.providers.iter()
             .find(|provider| provider.read().unwrap().provider() == track.provider)
             .ok_or_else(|| format_err!("provider for track {:?} not found", track))
             .and_then(|provider| provider.read().unwrap().stream_url(track))
     }
+
+    /// Providers in the order their sources are preferred by `stream_url`
+    ///
+    /// Starts with the providers passed to `set_source_preference`,
+    /// followed by the remaining providers in registration order.
+    pub fn source_preference(&self) -> Vec<Provider> {
+        let mut preference = self.source_preference.lock().unwrap().clone();
+        for provider in &self.providers {
+            let provider = provider.read().unwrap().provider();
+            if !preference.contains(&provider) {
+                preference.push(provider);
+            }
+        }
+        preference
+    }
+
+    pub fn set_source_preference(&self, preference: Vec<Provider>) {
+        *self.source_preference.lock().unwrap() = preference;
+    }
+
+    /// Returns library and cache statistics with up to `top_artists` artists
+    pub fn statistics(&self, top_artists: usize) -> Result<Statistics, failure::Error> {
+        Ok(Statistics {
+            library: self.library.statistics(top_artists)?,
+            cache: self.cache.statistics(),
+        })
+    }
+
+    /// Writes the library and play history as archive, see `archive`
+    pub fn export_archive<W: std::io::Write>(&self, writer: W) -> Result<archive::Manifest, failure::Error> {
+        archive::export(&**self.library, &self.history, writer)
+    }
+
+    /// Imports an archive written by `export_archive` into the library and play history
+    pub fn import_archive<R: std::io::BufRead>(&self, reader: R) -> Result<archive::Manifest, failure::Error> {
+        archive::import(&**self.library, &self.history, reader)
+    }
+
+    /// Scans the library for inconsistencies, see `library::integrity`
+    ///
+    /// With `repair` set the issues are fixed as far as possible.
+    pub fn check_library(&self, repair: bool) -> Result<library::integrity::IntegrityReport, failure::Error> {
+        if repair {
+            library::integrity::repair(&**self.library)
+        } else {
+            library::integrity::check(&**self.library)
+        }
+    }
+
+    /// Creates a playlist only stored in the library
+    pub fn create_playlist(&self, title: String) -> Result<Playlist, failure::Error> {
+        self.library.create_playlist(title)
+    }
+
+    /// Applies the edit to a playlist and pushes it to the provider of the playlist
+    ///
+    /// Playlists of `Provider::Internal` are only edited in the library.
+    /// The library is left untouched when the provider rejects the edit.
+    pub fn edit_playlist(&self, id: usize, edit: &PlaylistEdit) -> Result<Playlist, failure::Error> {
+        let playlist = self
+            .library
+            .get_playlist(id)?
+            .ok_or(PlaylistError::NotFound(id))?;
+        if playlist.provider != Provider::Internal {
+            playlist.clone().apply(edit)?;
+            let provider = self
+                .providers
+                .iter()
+                .find(|provider| provider.read().unwrap().provider() == playlist.provider)
+                .ok_or(PlaylistError::ReadOnly(playlist.provider))?;
+            provider.read().unwrap().edit_playlist(&playlist, edit)?;
+        }
+        self.library.edit_playlist(id, edit)
+    }
+
+    /// Exports a stored playlist in the given format
+    pub fn export_playlist(&self, id: usize, format: PlaylistFormat) -> Result<String, failure::Error> {
+        let playlist = self
+            .library
+            .get_playlist(id)?
+            .ok_or(PlaylistError::NotFound(id))?;
+        Ok(PlaylistFile::from(&playlist).write(format))
+    }
+
+    /// Exports the queue of a player in the given format
+    pub fn export_queue(&self, player_id: String, format: PlaylistFormat) -> Result<String, failure::Error> {
+        let player = self
+            .get_player(player_id.clone())
+            .ok_or_else(|| format_err!("player {} not found", player_id))?;
+        Ok(PlaylistFile::new(None, &player.get_queue()).write(format))
+    }
+
+    /// Imports a playlist file as new playlist only stored in the library
+    ///
+    /// Every entry is resolved with `resolve_track`, entries which can't be resolved
+    /// are skipped and reported in `ImportResult::unmatched`.
+    pub fn import_playlist(
+        &self,
+        title: String,
+        content: &str,
+        format: PlaylistFormat,
+    ) -> Result<ImportResult, failure::Error> {
+        let file = PlaylistFile::parse(content, format)?;
+        let mut tracks = vec![];
+        let mut unmatched = vec![];
+        for entry in file.entries {
+            match self.resolve_track(&entry.location) {
+                Ok(Some(track)) => tracks.push(track),
+                Ok(None) => unmatched.push(entry),
+                Err(e) => {
+                    debug!("Can't resolve {}: {}", entry.location, e);
+                    unmatched.push(entry);
+                }
+            }
+        }
+        let edit = PlaylistEdit::AddTracks {
+            tracks,
+            position: None,
+        };
+        // a failed edit mustn't leave an empty playlist behind
+        let mut imported = None;
+        self.library.batch(&mut |library| {
+            let playlist = library.create_playlist(title.clone())?;
+            imported = Some(library.edit_playlist(playlist.id.unwrap(), &edit)?);
+            Ok(())
+        })?;
+        let playlist = imported.expect("batch succeeded without importing the playlist");
+        Ok(ImportResult { playlist, unmatched })
+    }
+
+    /// Marks the track, album or artist with the given uri as favourite and tells its provider
+    pub fn set_favourite(&self, uri: &str, favourite: bool) -> Result<(), failure::Error> {
+        let annotation = self.library.get_annotation(uri)?;
+        if annotation.favourite == favourite {
+            return Ok(());
+        }
+        if let Some(provider) = self.provider_for_uri(uri) {
+            provider.read().unwrap().set_favourite(uri, favourite)?;
+        }
+        self.library.set_annotation(uri, Annotation {
+            favourite,
+            ..annotation
+        })
+    }
+
+    /// Rates the track, album or artist with the given uri, None clears the rating
+    pub fn set_rating(&self, uri: &str, rating: Option<u8>) -> Result<(), failure::Error> {
+        if let Some(rating) = rating {
+            if rating == 0 || rating > Annotation::MAX_RATING {
+                return Err(format_err!("rating {} out of range", rating));
+            }
+        }
+        let annotation = self.library.get_annotation(uri)?;
+        self.library.set_annotation(uri, Annotation {
+            rating,
+            ..annotation
+        })
+    }
+
+    /// Returns the provider handling the scheme of the given uri
+    fn provider_for_uri(&self, uri: &str) -> Option<&provider::SharedProvider> {
+        let url = Url::parse(uri).ok()?;
+        self.providers
+            .iter()
+            .find(|provider| provider.read().unwrap().uri_scheme() == url.scheme())
+    }
+
+    /// Returns up to `limit` plays of all players, latest first
+    pub fn recently_played(&self, limit: usize) -> Vec<history::Play> {
+        self.history.recently_played(limit)
+    }
+
+    /// Returns up to `limit` tracks with the most plays
+    pub fn most_played(&self, limit: usize) -> Vec<history::PlayStats> {
+        self.history.most_played(limit)
+    }
+
+    /// Searches the library and all providers in parallel
+    ///
+    /// Providers which don't respond within `timeout` or return an error are reported
+    /// in `FederatedSearchResults::failures`, only library errors fail the whole search.
+    /// The library has to respond within `timeout` as well. Concurrent searches are allowed,
+    /// every source searches on a small pool of threads shared by all searches.
+    pub fn search(&self, query: &str, timeout: Duration) -> Result<FederatedSearchResults, failure::Error> {
+        search::search(self, query, timeout)
+    }
 }
@@ -1,20 +1,31 @@
-use serde_derive::Serialize;
-use crate::library::{Artist, MetaValue};
+use serde_derive::{Deserialize, Serialize};
+use crate::library::{normalize, Artist, Genre, MetaKey, MetaValue, Track};
 use crate::provider::Provider;
 use std::sync::Arc;
 use std::collections::HashMap;
 use crate::Rustic;
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Album {
     pub id: Option<usize>,
     pub title: String,
     pub artist_id: Option<usize>,
+    /// Album artist, independent of the artists of the tracks
+    ///
+    /// Compilations usually have no album artist or "Various Artists".
     pub artist: Option<Artist>,
+    /// Whether the album collects tracks of different artists, e.g. a soundtrack or sampler
+    #[serde(default)]
+    pub compilation: bool,
+    /// Tracks of this album, only filled when the album is passed around with its tracks
+    #[serde(default)]
+    pub tracks: Vec<Track>,
     pub provider: Provider,
     pub image_url: Option<String>,
     pub uri: String,
-    pub meta: HashMap<&'static str, MetaValue>
+    #[serde(default)]
+    pub genres: Vec<Genre>,
+    pub meta: HashMap<MetaKey, MetaValue>
 }
 
 impl Album {
@@ -23,6 +34,26 @@ impl Album {
             .clone()
             .and_then(|uri| app.cache.fetch_coverart(uri).ok())
     }
+
+    pub fn year(&self) -> Option<u64> {
+        self.meta.get(&MetaKey::Year).and_then(MetaValue::as_int)
+    }
+
+    /// Whether both albums are the same compilation, split up by the provider
+    ///
+    /// Providers often derive the album of a track from the track artist, so the tracks of a
+    /// compilation end up on one album per artist. Compilations of the same provider match
+    /// by their normalized title, the release year has to match when both have one.
+    pub fn is_same_compilation(&self, other: &Album) -> bool {
+        self.compilation
+            && other.compilation
+            && self.provider == other.provider
+            && normalize(&self.title) == normalize(&other.title)
+            && match (self.year(), other.year()) {
+                (Some(a), Some(b)) => a == b,
+                _ => true,
+            }
+    }
 }
 
 impl PartialEq for Album {
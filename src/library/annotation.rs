@@ -0,0 +1,20 @@
+use serde_derive::{Deserialize, Serialize};
+
+/// User-owned data about a track, album or artist
+///
+/// Annotations are stored by uri separately from the entities, so syncing or even removing
+/// and re-adding an entity keeps them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub favourite: bool,
+    /// Rating from 1 to 5 stars
+    pub rating: Option<u8>,
+}
+
+impl Annotation {
+    pub const MAX_RATING: u8 = 5;
+
+    pub fn is_empty(&self) -> bool {
+        *self == Annotation::default()
+    }
+}
@@ -1,16 +1,22 @@
-use serde_derive::Serialize;
-use crate::library::MetaValue;
+use serde_derive::{Deserialize, Serialize};
+use crate::library::{Album, MetaKey, MetaValue, Track};
 use std::sync::Arc;
 use std::collections::HashMap;
 use crate::Rustic;
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Artist {
     pub id: Option<usize>,
     pub name: String,
     pub uri: String,
     pub image_url: Option<String>,
-    pub meta: HashMap<&'static str, MetaValue>
+    /// Albums of this artist, only filled when the artist is passed around with its albums
+    #[serde(default)]
+    pub albums: Vec<Album>,
+    /// Most popular tracks of this artist, only filled when provided alongside the artist
+    #[serde(default)]
+    pub top_tracks: Vec<Track>,
+    pub meta: HashMap<MetaKey, MetaValue>
 }
 
 impl Artist {
@@ -0,0 +1,202 @@
+use serde_derive::{Deserialize, Serialize};
+use crate::library::{normalize, Track};
+use crate::provider::Provider;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// A recording merged from all its sources in the library
+///
+/// The same song often exists with different uris, e.g. as local file and on Spotify.
+/// See `Track::is_duplicate` for how sources are matched.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanonicalTrack {
+    /// The first source in the library with missing fields taken from the other sources
+    pub track: Track,
+    /// All sources of the recording in library order, including the first one
+    pub sources: Vec<Track>,
+}
+
+impl CanonicalTrack {
+    fn new(track: Track) -> CanonicalTrack {
+        CanonicalTrack {
+            track: track.clone(),
+            sources: vec![track],
+        }
+    }
+
+    fn add_source(&mut self, source: Track) {
+        let track = &mut self.track;
+        if track.artist.is_none() {
+            track.artist_id = source.artist_id;
+            track.artist = source.artist.clone();
+        }
+        if track.album.is_none() {
+            track.album_id = source.album_id;
+            track.album = source.album.clone();
+        }
+        if track.image_url.is_none() {
+            track.image_url = source.image_url.clone();
+        }
+        if track.duration.is_none() {
+            track.duration = source.duration;
+        }
+        for genre in &source.genres {
+            if !track.genres.contains(genre) {
+                track.genres.push(genre.clone());
+            }
+        }
+        for (key, value) in &source.meta {
+            track
+                .meta
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+        self.sources.push(source);
+    }
+
+    pub fn providers(&self) -> Vec<Provider> {
+        let mut providers: Vec<Provider> = vec![];
+        for source in &self.sources {
+            if !providers.contains(&source.provider) {
+                providers.push(source.provider);
+            }
+        }
+        providers
+    }
+
+    /// Returns the sources ordered by the given provider preference
+    ///
+    /// Sources of providers missing in `preference` come last, in library order.
+    pub fn preferred_sources(&self, preference: &[Provider]) -> Vec<&Track> {
+        let mut sources: Vec<&Track> = self.sources.iter().collect();
+        sources.sort_by_key(|source| {
+            preference
+                .iter()
+                .position(|provider| *provider == source.provider)
+                .unwrap_or(preference.len())
+        });
+        sources
+    }
+
+    /// Whether one of the sources has the same ISRC or recording id as the track
+    fn shares_identifier(&self, track: &Track) -> bool {
+        self.sources
+            .iter()
+            .any(|source| source.matches_identifier(track) == Some(true))
+    }
+
+    /// Whether the track duplicates a source without contradicting the identifier of another
+    fn accepts(&self, track: &Track) -> bool {
+        self.sources.iter().any(|source| source.is_duplicate(track))
+            && self
+                .sources
+                .iter()
+                .all(|source| source.matches_identifier(track) != Some(false))
+    }
+
+    /// Groups duplicate tracks, keeping the order of their first occurrence
+    ///
+    /// A track joins the first group sharing its identifier, otherwise the first group
+    /// accepting it by metadata.
+    pub fn group<'a, I>(tracks: I) -> Vec<CanonicalTrack>
+    where
+        I: IntoIterator<Item = &'a Track>,
+    {
+        let mut groups: Vec<CanonicalTrack> = vec![];
+        for track in tracks {
+            let position = groups
+                .iter()
+                .position(|group| group.shares_identifier(track))
+                .or_else(|| groups.iter().position(|group| group.accepts(track)));
+            match position {
+                Some(position) => groups[position].add_source(track.clone()),
+                None => groups.push(CanonicalTrack::new(track.clone())),
+            }
+        }
+        groups
+    }
+}
+
+/// Index of the tracks which can end up in the same `CanonicalTrack`
+///
+/// Duplicates share their normalized title or an identifier, see `Track::is_duplicate`,
+/// so grouping only the tracks connected through those keys yields the same group
+/// as grouping the whole library.
+#[derive(Debug, Default)]
+pub(crate) struct CanonicalIndex {
+    ids: HashMap<String, BTreeSet<usize>>,
+}
+
+impl CanonicalIndex {
+    pub fn new(tracks: &BTreeMap<usize, Track>) -> CanonicalIndex {
+        let mut index = CanonicalIndex::default();
+        for (id, track) in tracks {
+            index.insert(*id, track);
+        }
+        index
+    }
+
+    fn keys(track: &Track) -> Vec<String> {
+        let mut keys = vec![format!("title:{}", normalize(&track.title))];
+        if let Some(isrc) = track.isrc() {
+            keys.push(format!("isrc:{}", isrc.trim().to_ascii_lowercase()));
+        }
+        if let Some(recording_id) = track.recording_id() {
+            keys.push(format!("recording:{}", recording_id.trim().to_ascii_lowercase()));
+        }
+        keys
+    }
+
+    pub fn insert(&mut self, id: usize, track: &Track) {
+        for key in CanonicalIndex::keys(track) {
+            self.ids.entry(key).or_default().insert(id);
+        }
+    }
+
+    pub fn remove(&mut self, id: usize, track: &Track) {
+        for key in CanonicalIndex::keys(track) {
+            if let Some(ids) = self.ids.get_mut(&key) {
+                ids.remove(&id);
+                if ids.is_empty() {
+                    self.ids.remove(&key);
+                }
+            }
+        }
+    }
+
+    /// Returns the ids of all tracks connected to the track, in library order
+    pub fn candidates(&self, track: &Track, tracks: &BTreeMap<usize, Track>) -> BTreeSet<usize> {
+        let mut candidates = BTreeSet::new();
+        let mut visited = BTreeSet::new();
+        let mut pending = CanonicalIndex::keys(track);
+        while let Some(key) = pending.pop() {
+            if !visited.insert(key.clone()) {
+                continue;
+            }
+            for id in self.ids.get(&key).into_iter().flatten() {
+                if candidates.insert(*id) {
+                    if let Some(candidate) = tracks.get(id) {
+                        pending.extend(CanonicalIndex::keys(candidate));
+                    }
+                }
+            }
+        }
+        candidates
+    }
+
+    /// Returns the ids of all tracks split into sets of connected tracks, see `candidates`
+    ///
+    /// The sets are ordered by their first track, each set in library order.
+    pub fn components(&self, tracks: &BTreeMap<usize, Track>) -> Vec<BTreeSet<usize>> {
+        let mut components: Vec<BTreeSet<usize>> = vec![];
+        let mut grouped = HashSet::new();
+        for (id, track) in tracks {
+            if grouped.contains(id) {
+                continue;
+            }
+            let component = self.candidates(track, tracks);
+            grouped.extend(component.iter().cloned());
+            components.push(component);
+        }
+        components
+    }
+}
@@ -0,0 +1,1279 @@
+//! Checks for the contract of the `Library` trait
+//!
+//! Every check borrows a fresh, empty library, returns the errors of the library and fails an assertion
+//! when the library violates the contract.
+//! Use `library_conformance_tests!` to generate a test for each check:
+//!
+//! ```ignore
+//! mod memory {
+//!     rustic_core::library_conformance_tests!(Box::new(rustic_core::MemoryLibrary::new()));
+//! }
+//! ```
+//!
+//! The fixtures `track`, `album`, `artist` and `playlist` help writing further tests of a backend.
+//! Only available with the `conformance` feature.
+use failure::Error;
+use crate::history::Play;
+use crate::library::{
+    Album, Annotation, Artist, Filter, Genre, Library, LibraryEvent, LibraryStatistics, MetaKey, MetaValue, Playlist, PlaylistEdit,
+    Query, Range, Rule, SmartPlaylist, SortKey, SortOrder, TextRule, Track,
+};
+use crate::provider::{Provider, SyncedUris};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+pub type Check = fn(&dyn Library) -> Result<(), Error>;
+
+/// All checks with their names
+pub const CHECKS: &[(&str, Check)] = crate::library_conformance_tests!(@checks list);
+
+/// Runs every check against a library created by `factory`
+pub fn run_all<F>(factory: F) -> Result<(), Error>
+where
+    F: Fn() -> Box<dyn Library>,
+{
+    for (_, check) in CHECKS {
+        check(&*factory())?;
+    }
+    Ok(())
+}
+
+/// Generates a `#[test]` for every check in `library::conformance`
+///
+/// Takes an expression creating a fresh library, it is evaluated once per test.
+/// The expression can return anything dereferencing to a `Library`, e.g. a `Box<dyn Library>`
+/// or a guard cleaning up the storage of the library when dropped.
+///
+/// The list of checks below is the only one, `CHECKS` is generated from it as well.
+#[macro_export]
+macro_rules! library_conformance_tests {
+    ($factory:expr) => {
+        $crate::library_conformance_tests!(@checks tests $factory);
+    };
+    (@checks $output:ident $($args:expr)?) => {
+        $crate::library_conformance_tests! { @$output ($($args)?);
+            add_assigns_ids,
+            add_does_not_dedup,
+            sync_assigns_ids,
+            sync_dedups_by_uri,
+            sync_updates_existing,
+            batch_assigns_ids,
+            links_artist,
+            links_album,
+            links_shared_relations,
+            links_nested_relations,
+            missing_ids,
+            lookup_by_uri,
+            query_pagination,
+            query_filters,
+            query_sorting,
+            album_tracks,
+            artist_albums,
+            artist_top_tracks,
+            remove_entities,
+            remove_clears_references,
+            prune_missing,
+            prune_keeps_other_providers,
+            observe_changes,
+            search_by_title,
+            search_is_case_insensitive,
+            search_ranking,
+            search_field_prefixes,
+            search_tolerates_typos,
+            search_ignores_diacritics,
+            search_short_terms_match_word_starts,
+            create_playlist,
+            edit_playlist,
+            edit_playlist_rejects_invalid,
+            smart_playlist_rules,
+            smart_playlist_sort_limit,
+            annotations_survive_sync,
+            plays_survive_removal,
+            favourites,
+            canonical_tracks,
+            statistics,
+            genres,
+            compilations
+        }
+    };
+    (@list (); $($check:ident),*) => {
+        &[$((stringify!($check), $check as Check)),*]
+    };
+    (@tests ($factory:expr); $($check:ident),*) => {
+        $(
+            #[test]
+            fn $check() {
+                let library = $factory;
+                $crate::library::conformance::$check(&*library).unwrap();
+            }
+        )*
+    };
+}
+
+pub fn track(uri: &str, title: &str) -> Track {
+    Track {
+        id: None,
+        title: title.to_owned(),
+        artist_id: None,
+        artist: None,
+        album_id: None,
+        album: None,
+        provider: Provider::LocalMedia,
+        uri: uri.to_owned(),
+        image_url: None,
+        duration: None,
+        genres: vec![],
+        meta: HashMap::new(),
+    }
+}
+
+pub fn album(uri: &str, title: &str) -> Album {
+    Album {
+        id: None,
+        title: title.to_owned(),
+        artist_id: None,
+        artist: None,
+        compilation: false,
+        tracks: vec![],
+        provider: Provider::LocalMedia,
+        image_url: None,
+        uri: uri.to_owned(),
+        genres: vec![],
+        meta: HashMap::new(),
+    }
+}
+
+pub fn artist(uri: &str, name: &str) -> Artist {
+    Artist {
+        id: None,
+        name: name.to_owned(),
+        uri: uri.to_owned(),
+        image_url: None,
+        albums: vec![],
+        top_tracks: vec![],
+        meta: HashMap::new(),
+    }
+}
+
+pub fn playlist(uri: &str, title: &str) -> Playlist {
+    Playlist {
+        id: None,
+        title: title.to_owned(),
+        tracks: vec![],
+        provider: Provider::LocalMedia,
+        uri: uri.to_owned(),
+    }
+}
+
+pub fn track_uris(tracks: Vec<Track>) -> Vec<String> {
+    tracks.into_iter().map(|track| track.uri).collect()
+}
+
+/// `add_*` assigns an id under which the entity can be fetched again
+pub fn add_assigns_ids(library: &dyn Library) -> Result<(), Error> {
+    let mut track = track("test:track:1", "Track");
+    let mut album = album("test:album:1", "Album");
+    let mut artist = artist("test:artist:1", "Artist");
+    let mut playlist = playlist("test:playlist:1", "Playlist");
+    library.add_track(&mut track)?;
+    library.add_album(&mut album)?;
+    library.add_artist(&mut artist)?;
+    library.add_playlist(&mut playlist)?;
+
+    let track_id = track.id.expect("add_track didn't assign an id");
+    let album_id = album.id.expect("add_album didn't assign an id");
+    let artist_id = artist.id.expect("add_artist didn't assign an id");
+    let playlist_id = playlist.id.expect("add_playlist didn't assign an id");
+    assert_eq!(library.get_track(track_id)?, Some(track));
+    assert_eq!(library.get_album(album_id)?, Some(album));
+    assert_eq!(library.get_artist(artist_id)?, Some(artist));
+    assert_eq!(library.get_playlist(playlist_id)?, Some(playlist));
+    Ok(())
+}
+
+/// `add_*` always inserts a new entity, even when the uri is already known
+pub fn add_does_not_dedup(library: &dyn Library) -> Result<(), Error> {
+    let mut first = track("test:track:1", "Track");
+    let mut second = first.clone();
+    library.add_track(&mut first)?;
+    library.add_track(&mut second)?;
+
+    assert_ne!(first.id, second.id);
+    assert_eq!(library.get_tracks()?.len(), 2);
+    Ok(())
+}
+
+/// `sync_*` assigns an id to entities which are not in the library yet
+pub fn sync_assigns_ids(library: &dyn Library) -> Result<(), Error> {
+    let mut track = track("test:track:1", "Track");
+    let mut album = album("test:album:1", "Album");
+    let mut artist = artist("test:artist:1", "Artist");
+    let mut playlist = playlist("test:playlist:1", "Playlist");
+    library.sync_track(&mut track)?;
+    library.sync_album(&mut album)?;
+    library.sync_artist(&mut artist)?;
+    library.sync_playlist(&mut playlist)?;
+
+    let track_id = track.id.expect("sync_track didn't assign an id");
+    let album_id = album.id.expect("sync_album didn't assign an id");
+    let artist_id = artist.id.expect("sync_artist didn't assign an id");
+    let playlist_id = playlist.id.expect("sync_playlist didn't assign an id");
+    assert_eq!(library.get_track(track_id)?, Some(track));
+    assert_eq!(library.get_album(album_id)?, Some(album));
+    assert_eq!(library.get_artist(artist_id)?, Some(artist));
+    assert_eq!(library.get_playlist(playlist_id)?, Some(playlist));
+    Ok(())
+}
+
+/// `sync_*` reuses the entity with the same uri instead of inserting a new one
+pub fn sync_dedups_by_uri(library: &dyn Library) -> Result<(), Error> {
+    let mut first = track("test:track:1", "Track");
+    let mut second = first.clone();
+    library.sync_track(&mut first)?;
+    library.sync_track(&mut second)?;
+    assert_eq!(first.id, second.id);
+    assert_eq!(library.get_tracks()?.len(), 1);
+
+    let mut first = artist("test:artist:1", "Artist");
+    let mut second = first.clone();
+    library.sync_artist(&mut first)?;
+    library.sync_artist(&mut second)?;
+    assert_eq!(first.id, second.id);
+    assert_eq!(library.get_artists()?.len(), 1);
+
+    let mut first = album("test:album:1", "Album");
+    let mut second = first.clone();
+    library.sync_album(&mut first)?;
+    library.sync_album(&mut second)?;
+    assert_eq!(first.id, second.id);
+    assert_eq!(library.get_albums()?.len(), 1);
+
+    let mut first = playlist("test:playlist:1", "Playlist");
+    let mut second = first.clone();
+    library.sync_playlist(&mut first)?;
+    library.sync_playlist(&mut second)?;
+    assert_eq!(first.id, second.id);
+    assert_eq!(library.get_playlists()?.len(), 1);
+    Ok(())
+}
+
+/// `sync_*` stores the new data of an entity which is already in the library
+pub fn sync_updates_existing(library: &dyn Library) -> Result<(), Error> {
+    let mut track = track("test:track:1", "Old Title");
+    library.sync_track(&mut track)?;
+    let mut updated = self::track("test:track:1", "New Title");
+    updated.duration = Some(180);
+    library.sync_track(&mut updated)?;
+
+    let stored = library
+        .get_track(track.id.unwrap())?
+        .expect("synced track is missing");
+    assert_eq!(stored.title, "New Title");
+    assert_eq!(stored.duration, Some(180));
+    Ok(())
+}
+
+/// The batch methods behave like the single entity ones
+pub fn batch_assigns_ids(library: &dyn Library) -> Result<(), Error> {
+    let mut tracks = vec![track("test:track:1", "One"), track("test:track:2", "Two")];
+    library.add_tracks(&mut tracks)?;
+    assert!(tracks.iter().all(|track| track.id.is_some()));
+    assert_ne!(tracks[0].id, tracks[1].id);
+
+    let mut synced = vec![track("test:track:2", "Two"), track("test:track:3", "Three")];
+    library.sync_tracks(&mut synced)?;
+    assert_eq!(synced[0].id, tracks[1].id);
+    assert!(synced[1].id.is_some());
+    assert_eq!(library.get_tracks()?.len(), 3);
+    Ok(())
+}
+
+/// An embedded artist is stored and linked through `artist_id`
+pub fn links_artist(library: &dyn Library) -> Result<(), Error> {
+    let mut track = track("test:track:1", "Track");
+    track.artist = Some(artist("test:artist:1", "Artist"));
+    library.sync_track(&mut track)?;
+
+    let artist_id = track.artist_id.expect("sync_track didn't link the artist");
+    let artist = library
+        .get_artist(artist_id)?
+        .expect("linked artist is missing");
+    assert_eq!(artist.uri, "test:artist:1");
+    let stored = library.get_track(track.id.unwrap())?.unwrap();
+    assert_eq!(stored.artist_id, Some(artist_id));
+    Ok(())
+}
+
+/// An embedded album and its artist are stored and linked through `album_id` and `artist_id`
+pub fn links_album(library: &dyn Library) -> Result<(), Error> {
+    let mut album = album("test:album:1", "Album");
+    album.artist = Some(artist("test:artist:1", "Artist"));
+    let mut track = track("test:track:1", "Track");
+    track.album = Some(album);
+    library.sync_track(&mut track)?;
+
+    let album_id = track.album_id.expect("sync_track didn't link the album");
+    let album = library.get_album(album_id)?.expect("linked album is missing");
+    assert_eq!(album.uri, "test:album:1");
+    let artist_id = album.artist_id.expect("album artist isn't linked");
+    let artist = library
+        .get_artist(artist_id)?
+        .expect("linked album artist is missing");
+    assert_eq!(artist.uri, "test:artist:1");
+    Ok(())
+}
+
+/// Tracks with the same embedded artist or album share one entity
+pub fn links_shared_relations(library: &dyn Library) -> Result<(), Error> {
+    let mut tracks: Vec<Track> = (1..=3)
+        .map(|i| {
+            let mut track = track(&format!("test:track:{}", i), "Track");
+            track.artist = Some(artist("test:artist:1", "Artist"));
+            track.album = Some(album("test:album:1", "Album"));
+            track
+        }).collect();
+    library.sync_tracks(&mut tracks)?;
+
+    assert_eq!(library.get_artists()?.len(), 1);
+    assert_eq!(library.get_albums()?.len(), 1);
+    assert!(tracks.iter().all(|track| track.artist_id == tracks[0].artist_id));
+    assert!(tracks.iter().all(|track| track.album_id == tracks[0].album_id));
+    Ok(())
+}
+
+/// The tracks of an album and the albums and top tracks of an artist are stored and linked
+pub fn links_nested_relations(library: &dyn Library) -> Result<(), Error> {
+    let mut album = album("test:album:1", "Album");
+    album.tracks = vec![track("test:track:1", "One"), track("test:track:2", "Two")];
+    let mut artist = artist("test:artist:1", "Artist");
+    artist.albums = vec![album];
+    artist.top_tracks = vec![track("test:track:3", "Three")];
+    library.sync_artist(&mut artist)?;
+
+    let artist_id = artist.id.unwrap();
+    let album = &artist.albums[0];
+    assert_eq!(album.artist_id, Some(artist_id));
+    assert!(album.tracks.iter().all(|track| track.album_id == album.id));
+    assert_eq!(library.get_tracks()?.len(), 3);
+    assert_eq!(library.get_album_tracks(album.id.unwrap())?.len(), 2);
+    assert_eq!(library.get_artist_albums(artist_id)?, vec![album.clone()]);
+    assert_eq!(
+        library.get_artist_top_tracks(artist_id, 10)?,
+        artist.top_tracks
+    );
+    Ok(())
+}
+
+/// Unknown ids result in `None` instead of an error
+pub fn missing_ids(library: &dyn Library) -> Result<(), Error> {
+    assert_eq!(library.get_track(42)?, None);
+    assert_eq!(library.get_album(42)?, None);
+    assert_eq!(library.get_artist(42)?, None);
+    assert_eq!(library.get_playlist(42)?, None);
+    Ok(())
+}
+
+/// `get_*_by_uri` returns the entity stored under that uri
+pub fn lookup_by_uri(library: &dyn Library) -> Result<(), Error> {
+    let mut track = track("test:track:1", "Track");
+    let mut album = album("test:album:1", "Album");
+    let mut artist = artist("test:artist:1", "Artist");
+    let mut playlist = playlist("test:playlist:1", "Playlist");
+    library.sync_track(&mut track)?;
+    library.sync_album(&mut album)?;
+    library.sync_artist(&mut artist)?;
+    library.sync_playlist(&mut playlist)?;
+    library.sync_track(&mut self::track("test:track:2", "Other"))?;
+
+    let stored = library
+        .get_track_by_uri("test:track:1")?
+        .expect("track not found by uri");
+    assert_eq!(stored.id, track.id);
+    let stored = library
+        .get_album_by_uri("test:album:1")?
+        .expect("album not found by uri");
+    assert_eq!(stored.id, album.id);
+    let stored = library
+        .get_artist_by_uri("test:artist:1")?
+        .expect("artist not found by uri");
+    assert_eq!(stored.id, artist.id);
+    let stored = library
+        .get_playlist_by_uri("test:playlist:1")?
+        .expect("playlist not found by uri");
+    assert_eq!(stored.id, playlist.id);
+    assert_eq!(library.get_track_by_uri("test:track:42")?, None);
+    assert_eq!(library.get_album_by_uri("test:album:42")?, None);
+    assert_eq!(library.get_artist_by_uri("test:artist:42")?, None);
+    assert_eq!(library.get_playlist_by_uri("test:playlist:42")?, None);
+    Ok(())
+}
+
+/// `query_*` returns the requested page and the total count of matching entities
+pub fn query_pagination(library: &dyn Library) -> Result<(), Error> {
+    let mut tracks: Vec<Track> = (1..=5)
+        .map(|i| track(&format!("test:track:{}", i), &format!("Track {}", i)))
+        .collect();
+    library.sync_tracks(&mut tracks)?;
+
+    let query = Query {
+        offset: 1,
+        limit: Some(2),
+        sort: Some(SortKey::Id),
+        ..Query::default()
+    };
+    let page = library.query_tracks(&query)?;
+    assert_eq!(page.total, 5);
+    assert_eq!(page.items, tracks[1..3].to_vec());
+
+    let query = Query {
+        offset: 4,
+        limit: Some(10),
+        ..Query::default()
+    };
+    let page = library.query_tracks(&query)?;
+    assert_eq!(page.total, 5);
+    assert_eq!(page.items.len(), 1);
+
+    let page = library.query_tracks(&Query::default())?;
+    assert_eq!(page.items.len(), 5);
+    Ok(())
+}
+
+/// `query_*` only returns entities matching all filters
+pub fn query_filters(library: &dyn Library) -> Result<(), Error> {
+    let mut jazz = track("test:track:1", "Jazz");
+    jazz.meta.insert(MetaKey::Year, MetaValue::from(1959u64));
+    jazz.artist = Some(artist("test:artist:1", "Artist"));
+    let mut rock = track("test:track:2", "Rock");
+    rock.meta.insert(MetaKey::Year, MetaValue::from(1971u64));
+    rock.artist = Some(artist("test:artist:1", "Artist"));
+    rock.album = Some(album("test:album:1", "Album"));
+    let mut other = track("test:track:3", "Other");
+    other.provider = Provider::Soundcloud;
+    library.sync_tracks(&mut vec![jazz.clone(), rock.clone(), other])?;
+    let rock = library.get_track_by_uri(&rock.uri)?.unwrap();
+
+    let query = Query {
+        filters: vec![Filter::Provider(Provider::LocalMedia)],
+        ..Query::default()
+    };
+    assert_eq!(library.query_tracks(&query)?.total, 2);
+
+    let query = Query {
+        filters: vec![Filter::ArtistId(rock.artist_id.unwrap())],
+        ..Query::default()
+    };
+    assert_eq!(library.query_tracks(&query)?.total, 2);
+
+    let query = Query {
+        filters: vec![Filter::AlbumId(rock.album_id.unwrap())],
+        ..Query::default()
+    };
+    assert_eq!(library.query_tracks(&query)?.items, vec![rock.clone()]);
+
+    let query = Query {
+        filters: vec![
+            Filter::ArtistId(rock.artist_id.unwrap()),
+            Filter::Meta(MetaKey::Year, MetaValue::from(1959u64)),
+        ],
+        ..Query::default()
+    };
+    let page = library.query_tracks(&query)?;
+    assert_eq!(page.total, 1);
+    assert_eq!(page.items, vec![jazz]);
+
+    let query = Query {
+        filters: vec![Filter::ArtistId(rock.artist_id.unwrap())],
+        ..Query::default()
+    };
+    assert_eq!(library.query_albums(&query)?.total, 0);
+    assert_eq!(library.query_artists(&query)?.total, 1);
+    Ok(())
+}
+
+/// `query_*` sorts by the given key and order
+pub fn query_sorting(library: &dyn Library) -> Result<(), Error> {
+    let mut short = track("test:track:1", "b");
+    short.duration = Some(60);
+    let mut long = track("test:track:2", "C");
+    long.duration = Some(600);
+    let mut medium = track("test:track:3", "a");
+    medium.duration = Some(180);
+    library.sync_tracks(&mut vec![short, long, medium])?;
+
+    let titles = |query: Query| -> Result<Vec<String>, Error> {
+        let page = library.query_tracks(&query)?;
+        Ok(page.items.into_iter().map(|track| track.title).collect())
+    };
+    let query = Query {
+        sort: Some(SortKey::Title),
+        ..Query::default()
+    };
+    assert_eq!(titles(query)?, vec!["a", "b", "C"]);
+    let query = Query {
+        sort: Some(SortKey::Duration),
+        order: SortOrder::Descending,
+        ..Query::default()
+    };
+    assert_eq!(titles(query)?, vec!["C", "a", "b"]);
+    Ok(())
+}
+
+/// `get_album_tracks` orders the tracks by disc and track number
+pub fn album_tracks(library: &dyn Library) -> Result<(), Error> {
+    let mut album = album("test:album:1", "Album");
+    library.sync_album(&mut album)?;
+    let positioned = |uri: &str, disc: u64, number: u64| {
+        let mut track = track(uri, uri);
+        track.album_id = album.id;
+        track.meta.insert(MetaKey::DiscNumber, MetaValue::from(disc));
+        track.meta.insert(MetaKey::TrackNumber, MetaValue::from(number));
+        track
+    };
+    let mut unrelated = track("test:track:5", "Other");
+    unrelated.album = Some(self::album("test:album:2", "Other"));
+    library.sync_tracks(&mut vec![
+        positioned("test:track:1", 2, 1),
+        positioned("test:track:2", 1, 2),
+        positioned("test:track:3", 1, 1),
+        unrelated,
+    ])?;
+
+    let tracks: Vec<String> = library
+        .get_album_tracks(album.id.unwrap())?
+        .into_iter()
+        .map(|track| track.uri)
+        .collect();
+    assert_eq!(tracks, vec!["test:track:3", "test:track:2", "test:track:1"]);
+    Ok(())
+}
+
+/// `get_artist_albums` returns all albums linked to the artist
+pub fn artist_albums(library: &dyn Library) -> Result<(), Error> {
+    let mut artist = artist("test:artist:1", "Artist");
+    library.sync_artist(&mut artist)?;
+    let mut albums = vec![
+        album("test:album:1", "B"),
+        album("test:album:2", "A"),
+        album("test:album:3", "Other"),
+    ];
+    albums[0].artist_id = artist.id;
+    albums[1].artist = Some(artist.clone());
+    library.sync_albums(&mut albums)?;
+
+    let albums: Vec<String> = library
+        .get_artist_albums(artist.id.unwrap())?
+        .into_iter()
+        .map(|album| album.title)
+        .collect();
+    assert_eq!(albums, vec!["A", "B"]);
+    Ok(())
+}
+
+/// `get_artist_top_tracks` orders by popularity and respects the limit
+pub fn artist_top_tracks(library: &dyn Library) -> Result<(), Error> {
+    let mut artist = artist("test:artist:1", "Artist");
+    library.sync_artist(&mut artist)?;
+    let popular = |uri: &str, popularity: Option<u64>| {
+        let mut track = track(uri, uri);
+        track.artist_id = artist.id;
+        if let Some(popularity) = popularity {
+            track.meta.insert(MetaKey::Popularity, MetaValue::from(popularity));
+        }
+        track
+    };
+    library.sync_tracks(&mut vec![
+        popular("test:track:1", None),
+        popular("test:track:2", Some(10)),
+        popular("test:track:3", Some(50)),
+        track("test:track:4", "Other"),
+    ])?;
+
+    let tracks: Vec<String> = library
+        .get_artist_top_tracks(artist.id.unwrap(), 2)?
+        .into_iter()
+        .map(|track| track.uri)
+        .collect();
+    assert_eq!(tracks, vec!["test:track:3", "test:track:2"]);
+    assert_eq!(library.get_artist_top_tracks(artist.id.unwrap(), 10)?.len(), 3);
+    Ok(())
+}
+
+/// `remove_*` removes the entity and unknown ids are ignored
+pub fn remove_entities(library: &dyn Library) -> Result<(), Error> {
+    let mut track = track("test:track:1", "Track");
+    let mut album = album("test:album:1", "Album");
+    let mut artist = artist("test:artist:1", "Artist");
+    let mut playlist = playlist("test:playlist:1", "Playlist");
+    library.sync_track(&mut track)?;
+    library.sync_album(&mut album)?;
+    library.sync_artist(&mut artist)?;
+    library.sync_playlist(&mut playlist)?;
+
+    library.remove_track(track.id.unwrap())?;
+    library.remove_album(album.id.unwrap())?;
+    library.remove_artist(artist.id.unwrap())?;
+    library.remove_playlist(playlist.id.unwrap())?;
+    library.remove_track(42)?;
+
+    assert_eq!(library.get_track(track.id.unwrap())?, None);
+    assert_eq!(library.get_track_by_uri(&track.uri)?, None);
+    assert_eq!(library.get_album(album.id.unwrap())?, None);
+    assert_eq!(library.get_artist(artist.id.unwrap())?, None);
+    assert_eq!(library.get_playlist(playlist.id.unwrap())?, None);
+    Ok(())
+}
+
+/// Removing an entity clears all references to it
+pub fn remove_clears_references(library: &dyn Library) -> Result<(), Error> {
+    let mut album = album("test:album:1", "Album");
+    album.artist = Some(artist("test:artist:1", "Artist"));
+    let mut track = track("test:track:1", "Track");
+    track.artist = Some(artist("test:artist:1", "Artist"));
+    track.album = Some(album);
+    let mut other = self::track("test:track:2", "Other");
+    library.sync_track(&mut track)?;
+    library.sync_track(&mut other)?;
+    let mut playlist = playlist("test:playlist:1", "Playlist");
+    playlist.tracks = vec![track.clone(), other.clone()];
+    library.sync_playlist(&mut playlist)?;
+
+    library.remove_artist(track.artist_id.unwrap())?;
+    library.remove_album(track.album_id.unwrap())?;
+    library.remove_track(other.id.unwrap())?;
+
+    let stored = library.get_track(track.id.unwrap())?.unwrap();
+    assert_eq!(stored.artist_id, None);
+    assert_eq!(stored.album_id, None);
+    let playlist = library.get_playlist(playlist.id.unwrap())?.unwrap();
+    assert_eq!(playlist.tracks, vec![track]);
+    Ok(())
+}
+
+/// `prune` removes everything of the provider which wasn't synced, including orphaned artists
+pub fn prune_missing(library: &dyn Library) -> Result<(), Error> {
+    let mut kept = track("test:track:1", "Kept");
+    kept.artist = Some(artist("test:artist:1", "Kept"));
+    let mut removed = track("test:track:2", "Removed");
+    removed.artist = Some(artist("test:artist:2", "Removed"));
+    removed.album = Some(album("test:album:1", "Removed"));
+    library.sync_tracks(&mut vec![kept.clone(), removed])?;
+    library.sync_playlist(&mut playlist("test:playlist:1", "Removed"))?;
+
+    let mut synced = SyncedUris::default();
+    synced.tracks.insert(kept.uri.clone());
+    let result = library.prune(Provider::LocalMedia, &synced)?;
+
+    assert_eq!(result.tracks, 1);
+    assert_eq!(result.albums, 1);
+    assert_eq!(result.artists, 1);
+    assert_eq!(result.playlists, 1);
+    assert_eq!(library.get_tracks()?, vec![kept]);
+    let artists: Vec<String> = library
+        .get_artists()?
+        .into_iter()
+        .map(|artist| artist.uri)
+        .collect();
+    assert_eq!(artists, vec!["test:artist:1"]);
+    assert!(library.get_albums()?.is_empty());
+    assert!(library.get_playlists()?.is_empty());
+    Ok(())
+}
+
+/// `prune` doesn't touch entities of other providers
+pub fn prune_keeps_other_providers(library: &dyn Library) -> Result<(), Error> {
+    let mut other = track("test:track:1", "Other");
+    other.provider = Provider::Soundcloud;
+    other.artist = Some(artist("test:artist:1", "Artist"));
+    let mut removed = track("test:track:2", "Removed");
+    removed.artist = Some(artist("test:artist:1", "Artist"));
+    library.sync_tracks(&mut vec![other.clone(), removed])?;
+
+    let result = library.prune(Provider::LocalMedia, &SyncedUris::default())?;
+
+    assert_eq!(result.tracks, 1);
+    assert_eq!(result.artists, 0);
+    assert_eq!(library.get_tracks()?, vec![other]);
+    assert_eq!(library.get_artists()?.len(), 1);
+    Ok(())
+}
+
+/// `observe` receives an event for every added, updated and removed entity
+pub fn observe_changes(library: &dyn Library) -> Result<(), Error> {
+    drop(library.observe());
+    let events = library.observe();
+    let mut track = track("test:track:1", "Track");
+    track.artist = Some(artist("test:artist:1", "Artist"));
+    library.sync_track(&mut track)?;
+    library.sync_track(&mut track)?;
+    library.remove_artist(track.artist_id.unwrap())?;
+    library.remove_track(track.id.unwrap())?;
+
+    let events: Vec<String> = std::iter::from_fn(|| events.try_recv())
+        .map(|event| match event {
+            LibraryEvent::TrackAdded(track) => format!("track added {}", track.uri),
+            LibraryEvent::TrackUpdated(track) => format!("track updated {}", track.uri),
+            LibraryEvent::TrackRemoved(track) => format!("track removed {}", track.uri),
+            LibraryEvent::ArtistAdded(artist) => format!("artist added {}", artist.uri),
+            LibraryEvent::ArtistUpdated(artist) => format!("artist updated {}", artist.uri),
+            LibraryEvent::ArtistRemoved(artist) => format!("artist removed {}", artist.uri),
+            event => format!("unexpected {:?}", event),
+        }).collect();
+    assert_eq!(
+        events,
+        vec![
+            "artist added test:artist:1",
+            "track added test:track:1",
+            "artist updated test:artist:1",
+            "track updated test:track:1",
+            "artist removed test:artist:1",
+            "track updated test:track:1",
+            "track removed test:track:1",
+        ]
+    );
+    Ok(())
+}
+
+/// `search` finds every kind of entity by its title or name
+pub fn search_by_title(library: &dyn Library) -> Result<(), Error> {
+    library.sync_tracks(&mut vec![
+        track("test:track:1", "Hello World"),
+        track("test:track:2", "Goodbye"),
+    ])?;
+    library.sync_album(&mut album("test:album:1", "Hello Album"))?;
+    library.sync_artist(&mut artist("test:artist:1", "Hello Artist"))?;
+    library.sync_playlist(&mut playlist("test:playlist:1", "Hello Playlist"))?;
+
+    let results = library.search("Hello".to_owned())?;
+    let tracks: Vec<&str> = results.tracks.iter().map(|track| track.uri.as_str()).collect();
+    assert_eq!(tracks, vec!["test:track:1"]);
+    assert_eq!(results.albums.len(), 1);
+    assert_eq!(results.artists.len(), 1);
+    assert_eq!(results.playlists.len(), 1);
+    Ok(())
+}
+
+/// `search` ignores the case of the query
+pub fn search_is_case_insensitive(library: &dyn Library) -> Result<(), Error> {
+    library.sync_track(&mut track("test:track:1", "Hello World"))?;
+
+    assert_eq!(library.search("hello".to_owned())?.tracks.len(), 1);
+    assert_eq!(library.search("WORLD".to_owned())?.tracks.len(), 1);
+    assert_eq!(library.search("nothing".to_owned())?.tracks.len(), 0);
+    Ok(())
+}
+
+/// `search` orders results by relevance
+///
+/// Title matches rank above artist and album matches, exact matches above partial ones.
+pub fn search_ranking(library: &dyn Library) -> Result<(), Error> {
+    let mut by_album = track("test:track:1", "Something");
+    by_album.album = Some(album("test:album:1", "Blue"));
+    let mut by_artist = track("test:track:2", "Other");
+    by_artist.artist = Some(artist("test:artist:1", "Blue"));
+    let partial = track("test:track:3", "Blue Monday");
+    let exact = track("test:track:4", "Blue");
+    library.sync_tracks(&mut vec![by_album, by_artist, partial, exact])?;
+
+    let results = library.search("blue".to_owned())?;
+    assert_eq!(
+        track_uris(results.tracks),
+        vec!["test:track:4", "test:track:2", "test:track:3", "test:track:1"]
+    );
+    Ok(())
+}
+
+/// `search` restricts terms with `title:`, `artist:` and `album:` prefixes to that field
+pub fn search_field_prefixes(library: &dyn Library) -> Result<(), Error> {
+    let mut first = track("test:track:1", "Daft");
+    first.artist = Some(artist("test:artist:1", "Other"));
+    let mut second = track("test:track:2", "Around the World");
+    second.artist = Some(artist("test:artist:2", "Daft Punk"));
+    second.album = Some(album("test:album:1", "Homework"));
+    library.sync_tracks(&mut vec![first, second])?;
+
+    let results = library.search("artist:daft".to_owned())?;
+    assert_eq!(track_uris(results.tracks), vec!["test:track:2"]);
+    let results = library.search("title:daft".to_owned())?;
+    assert_eq!(track_uris(results.tracks), vec!["test:track:1"]);
+    let results = library.search("artist:\"daft punk\" album:homework world".to_owned())?;
+    assert_eq!(track_uris(results.tracks), vec!["test:track:2"]);
+    let results = library.search("album:daft".to_owned())?;
+    assert!(results.tracks.is_empty());
+    Ok(())
+}
+
+/// `search` still matches words with small typos
+pub fn search_tolerates_typos(library: &dyn Library) -> Result<(), Error> {
+    library.sync_tracks(&mut vec![
+        track("test:track:1", "Bohemian Rhapsody"),
+        track("test:track:2", "Yesterday"),
+    ])?;
+
+    let results = library.search("bohemain rapsody".to_owned())?;
+    assert_eq!(track_uris(results.tracks), vec!["test:track:1"]);
+    let results = library.search("yesteray".to_owned())?;
+    assert_eq!(track_uris(results.tracks), vec!["test:track:2"]);
+    Ok(())
+}
+
+/// `search` ignores diacritics in the query and the library
+pub fn search_ignores_diacritics(library: &dyn Library) -> Result<(), Error> {
+    library.sync_artist(&mut artist("test:artist:1", "Beyoncé"))?;
+    library.sync_track(&mut track("test:track:1", "Deja Vu"))?;
+
+    assert_eq!(library.search("beyonce".to_owned())?.artists.len(), 1);
+    assert_eq!(library.search("BEYONCÉ".to_owned())?.artists.len(), 1);
+    assert_eq!(library.search("déjà vu".to_owned())?.tracks.len(), 1);
+    Ok(())
+}
+
+/// `search` matches terms of up to three characters only at the start of a word
+pub fn search_short_terms_match_word_starts(library: &dyn Library) -> Result<(), Error> {
+    library.sync_tracks(&mut vec![
+        track("test:track:1", "The Wall"),
+        track("test:track:2", "Heroes"),
+        track("test:track:3", "Wonderwall"),
+    ])?;
+
+    let results = library.search("he".to_owned())?;
+    assert_eq!(track_uris(results.tracks), vec!["test:track:2"]);
+    let results = library.search("wal".to_owned())?;
+    assert_eq!(track_uris(results.tracks), vec!["test:track:1"]);
+    let results = library.search("wall".to_owned())?;
+    assert_eq!(track_uris(results.tracks), vec!["test:track:1", "test:track:3"]);
+    Ok(())
+}
+
+/// `create_playlist` stores an empty internal playlist with a unique uri
+pub fn create_playlist(library: &dyn Library) -> Result<(), Error> {
+    // internal playlists stored before, e.g. restored from an archive, keep their uris
+    let mut imported: Vec<Playlist> = (3..6)
+        .map(|n| playlist(&format!("internal:playlist:{}", n), "Imported"))
+        .collect();
+    library.add_playlists(&mut imported)?;
+    let first = library.create_playlist("First".to_owned())?;
+    let second = library.create_playlist("Second".to_owned())?;
+
+    assert_eq!(first.provider, Provider::Internal);
+    assert!(first.tracks.is_empty());
+    assert_ne!(first.uri, second.uri);
+    for playlist in imported {
+        assert_ne!(first.uri, playlist.uri);
+        assert_ne!(second.uri, playlist.uri);
+    }
+    let stored = library.get_playlist(first.id.unwrap())?.unwrap();
+    assert_eq!(stored.title, "First");
+    assert_eq!(library.get_playlist_by_uri(&second.uri)?.unwrap().id, second.id);
+    Ok(())
+}
+
+fn playlist_uris(playlist: &Playlist) -> Vec<&str> {
+    playlist.tracks.iter().map(|track| track.uri.as_str()).collect()
+}
+
+/// `edit_playlist` renames, adds, removes and moves tracks and stores the result
+pub fn edit_playlist(library: &dyn Library) -> Result<(), Error> {
+    let id = library.create_playlist("Playlist".to_owned())?.id.unwrap();
+
+    let edited = library.edit_playlist(id, &PlaylistEdit::Rename("Renamed".to_owned()))?;
+    assert_eq!(edited.title, "Renamed");
+    let edit = PlaylistEdit::AddTracks {
+        tracks: vec![track("test:track:1", "One"), track("test:track:2", "Two")],
+        position: None,
+    };
+    let edited = library.edit_playlist(id, &edit)?;
+    assert_eq!(playlist_uris(&edited), vec!["test:track:1", "test:track:2"]);
+    assert!(edited.tracks.iter().all(|track| track.id.is_some()));
+    assert!(library.get_track_by_uri("test:track:1")?.is_some());
+    let edit = PlaylistEdit::AddTracks {
+        tracks: vec![track("test:track:3", "Three")],
+        position: Some(0),
+    };
+    library.edit_playlist(id, &edit)?;
+    let edited = library.edit_playlist(id, &PlaylistEdit::MoveTrack { from: 0, to: 2 })?;
+    assert_eq!(
+        playlist_uris(&edited),
+        vec!["test:track:1", "test:track:2", "test:track:3"]
+    );
+    library.edit_playlist(id, &PlaylistEdit::RemoveTracks(vec![0, 2]))?;
+
+    let stored = library.get_playlist(id)?.unwrap();
+    assert_eq!(stored.title, "Renamed");
+    assert_eq!(playlist_uris(&stored), vec!["test:track:2"]);
+    Ok(())
+}
+
+/// `edit_playlist` fails for missing playlists and invalid positions without changing anything
+pub fn edit_playlist_rejects_invalid(library: &dyn Library) -> Result<(), Error> {
+    let mut stored = playlist("test:playlist:1", "Playlist");
+    stored.tracks = vec![track("test:track:1", "One")];
+    library.sync_playlist(&mut stored)?;
+    let id = stored.id.unwrap();
+
+    assert!(library
+        .edit_playlist(id + 1, &PlaylistEdit::Rename("Other".to_owned()))
+        .is_err());
+    assert!(library
+        .edit_playlist(id, &PlaylistEdit::MoveTrack { from: 0, to: 1 })
+        .is_err());
+    assert!(library
+        .edit_playlist(id, &PlaylistEdit::RemoveTracks(vec![0, 1]))
+        .is_err());
+    let edit = PlaylistEdit::AddTracks {
+        tracks: vec![track("test:track:2", "Two")],
+        position: Some(2),
+    };
+    assert!(library.edit_playlist(id, &edit).is_err());
+
+    assert_eq!(playlist_uris(&library.get_playlist(id)?.unwrap()), vec!["test:track:1"]);
+    assert!(library.get_track_by_uri("test:track:2")?.is_none());
+    Ok(())
+}
+
+/// `evaluate_smart_playlist` selects the tracks matching all rules
+pub fn smart_playlist_rules(library: &dyn Library) -> Result<(), Error> {
+    let mut long_jazz = track("test:track:1", "Long Jazz");
+    long_jazz.duration = Some(900);
+    long_jazz.genres = vec![Genre::new("Jazz")];
+    let mut short_jazz = track("test:track:2", "Short Jazz");
+    short_jazz.duration = Some(300);
+    short_jazz.genres = vec![Genre::new("jazz")];
+    let mut long_rock = track("test:track:3", "Long Rock");
+    long_rock.duration = Some(700);
+    long_rock.genres = vec![Genre::new("Rock")];
+    let mut other_provider = track("test:track:4", "Other Jazz");
+    other_provider.duration = Some(1200);
+    other_provider.provider = Provider::Soundcloud;
+    other_provider.genres = vec![Genre::new("Jazz")];
+    let mut by_artist = track("test:track:5", "Something");
+    by_artist.artist = Some(artist("test:artist:1", "Miles Davis"));
+    library.sync_tracks(&mut vec![long_jazz, short_jazz, long_rock, other_provider, by_artist])?;
+
+    let long = Rule::Duration(Range {
+        min: Some(600.0),
+        max: None,
+    });
+    let jazz = Rule::Genre(Genre::new("jazz"));
+    let playlist = SmartPlaylist::new(
+        "Long Jazz".to_owned(),
+        vec![long.clone(), Rule::Provider(Provider::LocalMedia), jazz.clone()],
+    );
+    let tracks = library.evaluate_smart_playlist(&playlist)?;
+    assert_eq!(track_uris(tracks), vec!["test:track:1"]);
+
+    let playlist = SmartPlaylist::new(
+        "Long or Jazz".to_owned(),
+        vec![
+            Rule::Any(vec![long, jazz]),
+            Rule::Not(Box::new(Rule::Provider(Provider::Soundcloud))),
+        ],
+    );
+    let mut tracks = track_uris(library.evaluate_smart_playlist(&playlist)?);
+    tracks.sort();
+    assert_eq!(tracks, vec!["test:track:1", "test:track:2", "test:track:3"]);
+
+    let playlist = SmartPlaylist::new(
+        "Miles".to_owned(),
+        vec![Rule::Artist(TextRule::Contains("miles".to_owned()))],
+    );
+    let tracks = library.evaluate_smart_playlist(&playlist)?;
+    assert_eq!(track_uris(tracks), vec!["test:track:5"]);
+    Ok(())
+}
+
+/// `evaluate_smart_playlist` sorts and limits the selected tracks and picks up new tracks
+pub fn smart_playlist_sort_limit(library: &dyn Library) -> Result<(), Error> {
+    library.sync_tracks(&mut vec![
+        track("test:track:1", "B"),
+        track("test:track:2", "C"),
+        track("test:track:3", "A"),
+    ])?;
+    let playlist = SmartPlaylist {
+        sort: Some(SortKey::Title),
+        order: SortOrder::Descending,
+        limit: Some(2),
+        ..SmartPlaylist::new("All".to_owned(), vec![])
+    };
+
+    let tracks = library.evaluate_smart_playlist(&playlist)?;
+    assert_eq!(track_uris(tracks), vec!["test:track:2", "test:track:1"]);
+    library.sync_track(&mut track("test:track:4", "D"))?;
+    let tracks = library.evaluate_smart_playlist(&playlist)?;
+    assert_eq!(track_uris(tracks), vec!["test:track:4", "test:track:2"]);
+    Ok(())
+}
+
+/// Annotations are kept when the annotated entity is synced, removed or added again
+pub fn annotations_survive_sync(library: &dyn Library) -> Result<(), Error> {
+    let annotation = Annotation {
+        favourite: true,
+        rating: Some(4),
+    };
+    let mut stored = track("test:track:1", "Track");
+    library.sync_track(&mut stored)?;
+    assert!(library.get_annotation("test:track:1")?.is_empty());
+
+    library.set_annotation("test:track:1", annotation)?;
+    library.sync_track(&mut track("test:track:1", "Renamed"))?;
+    assert_eq!(library.get_annotation("test:track:1")?, annotation);
+    library.remove_track(stored.id.unwrap())?;
+    library.add_track(&mut track("test:track:1", "Track"))?;
+    assert_eq!(library.get_annotation("test:track:1")?, annotation);
+
+    assert_eq!(library.get_annotations()?.len(), 1);
+    library.set_annotation("test:track:1", Annotation::default())?;
+    assert!(library.get_annotation("test:track:1")?.is_empty());
+    assert!(library.get_annotations()?.is_empty());
+    Ok(())
+}
+
+/// Plays are kept in the order they were added, even when the played track is removed
+pub fn plays_survive_removal(library: &dyn Library) -> Result<(), Error> {
+    let played_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+    let mut stored = track("test:track:1", "Track");
+    library.sync_track(&mut stored)?;
+    assert!(library.get_plays()?.is_empty());
+
+    library.add_play(Play {
+        track: stored.clone(),
+        played_at,
+    })?;
+    library.add_play(Play {
+        track: track("test:track:2", "Other"),
+        played_at: played_at - Duration::from_secs(60),
+    })?;
+    library.remove_track(stored.id.unwrap())?;
+
+    let plays = library.get_plays()?;
+    let uris: Vec<&str> = plays.iter().map(|play| play.track.uri.as_str()).collect();
+    assert_eq!(uris, vec!["test:track:1", "test:track:2"]);
+    assert_eq!(plays[0].played_at, played_at);
+    Ok(())
+}
+
+/// `get_favourite_*` returns the stored entities annotated as favourite
+pub fn favourites(library: &dyn Library) -> Result<(), Error> {
+    let favourite = Annotation {
+        favourite: true,
+        rating: None,
+    };
+    library.sync_tracks(&mut vec![track("test:track:1", "One"), track("test:track:2", "Two")])?;
+    library.sync_album(&mut album("test:album:1", "Album"))?;
+    library.sync_artist(&mut artist("test:artist:1", "Artist"))?;
+    library.set_annotation("test:track:2", favourite)?;
+    library.set_annotation("test:album:1", favourite)?;
+    library.set_annotation("test:track:3", favourite)?;
+    library.set_annotation(
+        "test:artist:1",
+        Annotation {
+            favourite: false,
+            rating: Some(5),
+        },
+    )?;
+
+    assert_eq!(track_uris(library.get_favourite_tracks()?), vec!["test:track:2"]);
+    assert_eq!(library.get_favourite_albums()?.len(), 1);
+    assert!(library.get_favourite_artists()?.is_empty());
+
+    library.sync_track(&mut track("test:track:3", "Three"))?;
+    let mut favourites = track_uris(library.get_favourite_tracks()?);
+    favourites.sort();
+    assert_eq!(favourites, vec!["test:track:2", "test:track:3"]);
+    Ok(())
+}
+
+/// Duplicates from different providers are merged into one canonical track
+pub fn canonical_tracks(library: &dyn Library) -> Result<(), Error> {
+    let source = |uri: &str, title: &str, provider: Provider, duration: u64| {
+        let mut track = track(uri, title);
+        track.provider = provider;
+        track.duration = Some(duration);
+        track.artist = Some(artist("test:artist:1", "Artist"));
+        track
+    };
+    let mut local = source("test:track:1", "Song", Provider::LocalMedia, 200);
+    local.album = Some(album("test:album:1", "Album"));
+    let mut remote = source("test:track:2", "SONG", Provider::Spotify, 202);
+    remote.image_url = Some("http://example.com/cover.jpg".to_owned());
+    let other = source("test:track:3", "Other Song", Provider::LocalMedia, 200);
+    let mut renamed = source("test:track:4", "Song (Remastered)", Provider::Soundcloud, 200);
+    renamed.meta.insert(MetaKey::Isrc, MetaValue::String("USABC0000001".to_owned()));
+    let mut tagged = source("test:track:5", "Song", Provider::GooglePlayMusic, 200);
+    tagged.meta.insert(MetaKey::Isrc, MetaValue::String("usabc0000001".to_owned()));
+    let mut different = source("test:track:6", "Song (Remastered)", Provider::Spotify, 200);
+    different.meta.insert(MetaKey::Isrc, MetaValue::String("USABC0000002".to_owned()));
+    library.sync_tracks(&mut vec![local, remote, other, renamed, tagged, different])?;
+
+    let canonical = library.get_canonical_tracks()?;
+    let groups: Vec<Vec<String>> = canonical
+        .iter()
+        .map(|canonical| track_uris(canonical.sources.clone()))
+        .collect();
+    assert_eq!(groups[0], vec!["test:track:1", "test:track:2"]);
+    assert_eq!(groups[1], vec!["test:track:3"]);
+    assert_eq!(groups[2], vec!["test:track:4", "test:track:5"]);
+    assert_eq!(groups[3], vec!["test:track:6"]);
+    assert_eq!(canonical[0].track.uri, "test:track:1");
+    assert_eq!(canonical[0].track.image_url, Some("http://example.com/cover.jpg".to_owned()));
+    assert_eq!(canonical[0].providers(), vec![Provider::LocalMedia, Provider::Spotify]);
+
+    let found = library.get_canonical_track("test:track:2")?.unwrap();
+    assert_eq!(found.track.uri, "test:track:1");
+    for (group, canonical) in groups.iter().zip(&canonical) {
+        for uri in group {
+            let found = library.get_canonical_track(uri)?.unwrap();
+            assert_eq!(&track_uris(found.sources), group);
+            assert_eq!(found.track.image_url, canonical.track.image_url);
+        }
+    }
+    assert!(library.get_canonical_track("test:track:7")?.is_none());
+    Ok(())
+}
+
+/// `statistics` counts entities per provider and aggregates over the tracks
+pub fn statistics(library: &dyn Library) -> Result<(), Error> {
+    assert_eq!(library.statistics(10)?, LibraryStatistics::default());
+    let with_artist = |uri: &str, artist_uri: &str, duration: Option<u64>| {
+        let mut track = track(uri, "Track");
+        track.artist = Some(artist(artist_uri, "Artist"));
+        track.duration = duration;
+        track
+    };
+    let mut covered = album("test:album:1", "Album");
+    covered.image_url = Some("http://example.com/cover.jpg".to_owned());
+    covered.tracks = vec![with_artist("test:track:1", "test:artist:1", Some(100))];
+    library.sync_album(&mut covered)?;
+    let mut remote = with_artist("test:track:2", "test:artist:2", Some(50));
+    remote.provider = Provider::Spotify;
+    remote.image_url = Some("http://example.com/track.jpg".to_owned());
+    library.sync_tracks(&mut vec![
+        remote,
+        with_artist("test:track:3", "test:artist:2", None),
+        with_artist("test:track:4", "test:artist:2", Some(10)),
+        track("test:track:5", "Track"),
+    ])?;
+    library.sync_playlist(&mut playlist("test:playlist:1", "Playlist"))?;
+
+    let statistics = library.statistics(1)?;
+    assert_eq!(statistics.tracks, 5);
+    assert_eq!(statistics.albums, 1);
+    assert_eq!(statistics.artists, 2);
+    assert_eq!(statistics.playlists, 1);
+    assert_eq!(statistics.total_duration, 160);
+    assert_eq!(statistics.tracks_without_duration, 2);
+    assert_eq!(statistics.tracks_without_artwork, 3);
+    let local = statistics.provider(Provider::LocalMedia).unwrap();
+    assert_eq!((local.tracks, local.albums, local.playlists), (4, 1, 1));
+    assert_eq!(statistics.provider(Provider::Spotify).unwrap().tracks, 1);
+    assert!(statistics.provider(Provider::Soundcloud).is_none());
+    assert_eq!(statistics.top_artists.len(), 1);
+    assert_eq!(statistics.top_artists[0].artist.uri, "test:artist:2");
+    assert_eq!(statistics.top_artists[0].tracks, 3);
+    Ok(())
+}
+
+/// `get_genres` lists the genres of tracks and albums once, browsing matches genres normalized
+pub fn genres(library: &dyn Library) -> Result<(), Error> {
+    assert!(library.get_genres()?.is_empty());
+    let tagged = |uri: &str, title: &str, genres: &[&str]| {
+        let mut track = track(uri, title);
+        track.genres = genres.iter().map(Genre::new).collect();
+        track
+    };
+    let mut jazz = album("test:album:1", "Jazz Album");
+    jazz.genres = vec![Genre::new("jazz")];
+    jazz.tracks = vec![tagged("test:track:1", "B", &[])];
+    library.sync_album(&mut jazz)?;
+    let mut mixed = album("test:album:2", "Mixed Album");
+    mixed.tracks = vec![tagged("test:track:2", "A", &["Hip-Hop", "Jazz"])];
+    library.sync_album(&mut mixed)?;
+    library.sync_tracks(&mut vec![
+        tagged("test:track:3", "C", &["hip  hop"]),
+        tagged("test:track:4", "D", &[]),
+    ])?;
+
+    let genres = library.get_genres()?;
+    let names: Vec<&str> = genres.iter().map(|genre| genre.name()).collect();
+    assert_eq!(names, vec!["Hip-Hop", "Jazz"]);
+    let tracks = library.get_genre_tracks(&Genre::new("HIP HOP"))?;
+    let uris: Vec<&str> = tracks.iter().map(|track| track.uri.as_str()).collect();
+    assert_eq!(uris, vec!["test:track:2", "test:track:3"]);
+    let albums = library.get_genre_albums(&Genre::new("Jazz"))?;
+    let uris: Vec<&str> = albums.iter().map(|album| album.uri.as_str()).collect();
+    assert_eq!(uris, vec!["test:album:1", "test:album:2"]);
+    assert!(library.get_genre_albums(&Genre::new("Blues"))?.is_empty());
+    Ok(())
+}
+
+/// Compilations split up by the provider are grouped, artists list their appearances on them
+pub fn compilations(library: &dyn Library) -> Result<(), Error> {
+    let various = artist("test:artist:0", "Various Artists");
+    let on_album = |uri: &str, artist_uri: &str, album: &Album| {
+        let mut track = track(uri, "Track");
+        track.artist = Some(artist(artist_uri, "Artist"));
+        track.album = Some(album.clone());
+        track
+    };
+    let compilation = |uri: &str, year: u64| {
+        let mut album = album(uri, "Hits");
+        album.artist = Some(various.clone());
+        album.compilation = true;
+        album.meta.insert(MetaKey::Year, MetaValue::Int(year));
+        album
+    };
+    let mut own = album("test:album:4", "hits");
+    own.artist = Some(artist("test:artist:2", "Artist"));
+    let mut tracks = vec![
+        on_album("test:track:1", "test:artist:1", &compilation("test:album:1", 1998)),
+        on_album("test:track:2", "test:artist:2", &compilation("test:album:2", 1998)),
+        on_album("test:track:3", "test:artist:2", &compilation("test:album:3", 1999)),
+        on_album("test:track:4", "test:artist:2", &own),
+    ];
+    library.sync_tracks(&mut tracks)?;
+    let mut artist_1 = artist("test:artist:1", "Artist");
+    artist_1.albums = vec![compilation("test:album:1", 1998)];
+    library.sync_artist(&mut artist_1)?;
+
+    assert_eq!(tracks[0].album_id, tracks[1].album_id);
+    assert_ne!(tracks[0].album_id, tracks[2].album_id);
+    assert_eq!(library.get_albums()?.len(), 3);
+    let grouped = library.get_album(tracks[0].album_id.unwrap())?.unwrap();
+    assert_eq!(grouped.uri, "test:album:1");
+    assert_eq!(grouped.artist.as_ref().map(|artist| &artist.uri), Some(&various.uri));
+    assert_eq!(library.get_album_tracks(grouped.id.unwrap())?.len(), 2);
+    let part = library.get_album_by_uri("test:album:2")?.unwrap();
+    assert_eq!(part.id, grouped.id);
+    assert_eq!(tracks[1].album.as_ref().map(|album| album.uri.as_str()), Some("test:album:2"));
+    assert!(crate::library::integrity::check(library)?.is_consistent());
+
+    let mut resynced = vec![
+        on_album("test:track:2", "test:artist:2", &compilation("test:album:2", 1998)),
+        on_album("test:track:1", "test:artist:1", &compilation("test:album:1", 1998)),
+    ];
+    library.sync_tracks(&mut resynced)?;
+    assert_eq!(resynced[0].album_id, grouped.id);
+    assert_eq!(resynced[1].album_id, grouped.id);
+    assert_eq!(library.get_albums()?.len(), 3);
+    assert_eq!(library.get_album(grouped.id.unwrap())?.unwrap().uri, "test:album:1");
+
+    let artist_1 = artist_1.id.unwrap();
+    let artist_2 = tracks[1].artist_id.unwrap();
+    assert!(library.get_artist_albums(artist_1)?.is_empty());
+    let uris = |albums: Vec<Album>| -> Vec<String> {
+        albums.into_iter().map(|album| album.uri).collect()
+    };
+    assert_eq!(uris(library.get_artist_compilations(artist_1)?), vec!["test:album:1"]);
+    assert_eq!(uris(library.get_artist_albums(artist_2)?), vec!["test:album:4"]);
+    let appearances = uris(library.get_artist_compilations(artist_2)?);
+    assert_eq!(appearances, vec!["test:album:1", "test:album:3"]);
+
+    let mut synced = SyncedUris::default();
+    synced.tracks.extend(tracks.iter().map(|track| track.uri.clone()));
+    let parts = ["test:album:2", "test:album:3", "test:album:4"];
+    synced.albums.extend(parts.iter().map(|uri| uri.to_string()));
+    let pruned = library.prune(Provider::LocalMedia, &synced)?;
+    assert_eq!(pruned.albums, 0);
+    assert!(library.get_album_by_uri("test:album:1")?.is_some());
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use serde_derive::{Deserialize, Serialize};
+use crate::channel::{self, Receiver, Sender};
+use crate::library::{Album, Annotation, Artist, Playlist, Track};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, Weak};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "payload", rename_all = "camelCase")]
+pub enum LibraryEvent {
+    /// A new track has been stored
+    TrackAdded(Track),
+    /// A stored track has been changed
+    TrackUpdated(Track),
+    /// A track has been removed
+    TrackRemoved(Track),
+    AlbumAdded(Album),
+    AlbumUpdated(Album),
+    AlbumRemoved(Album),
+    ArtistAdded(Artist),
+    ArtistUpdated(Artist),
+    ArtistRemoved(Artist),
+    PlaylistAdded(Playlist),
+    PlaylistUpdated(Playlist),
+    PlaylistRemoved(Playlist),
+    /// The annotation of the entity with the given uri has been changed
+    AnnotationUpdated { uri: String, annotation: Annotation },
+}
+
+/// Senders of all live `Subscription`s of a library
+#[derive(Debug, Default)]
+pub(crate) struct Observers {
+    next_id: usize,
+    senders: Vec<(usize, Sender<LibraryEvent>)>,
+}
+
+pub(crate) type SharedObservers = Arc<Mutex<Observers>>;
+
+impl Observers {
+    pub fn subscribe(observers: &SharedObservers) -> Subscription {
+        let (sender, receiver) = channel::unbounded();
+        let mut locked = observers.lock().unwrap();
+        let id = locked.next_id;
+        locked.next_id += 1;
+        locked.senders.push((id, sender));
+        Subscription {
+            id,
+            receiver,
+            observers: Arc::downgrade(observers),
+        }
+    }
+
+    /// Sends the events to every subscription, in order
+    pub fn notify(&self, events: Vec<LibraryEvent>) {
+        for event in events {
+            for (_, sender) in &self.senders {
+                sender.send(event.clone());
+            }
+        }
+    }
+}
+
+/// Receiver of library events, returned by `Library::observe`
+///
+/// Dereferences to the underlying channel. Dropping the subscription unsubscribes,
+/// so the library stops buffering events for it.
+#[derive(Debug)]
+pub struct Subscription {
+    id: usize,
+    receiver: Receiver<LibraryEvent>,
+    observers: Weak<Mutex<Observers>>,
+}
+
+impl Deref for Subscription {
+    type Target = Receiver<LibraryEvent>;
+
+    fn deref(&self) -> &Receiver<LibraryEvent> {
+        &self.receiver
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(observers) = self.observers.upgrade() {
+            let mut observers = observers.lock().unwrap();
+            observers.senders.retain(|(id, _)| *id != self.id);
+        }
+    }
+}
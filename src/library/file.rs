@@ -0,0 +1,342 @@
+use log::{debug, trace, warn};
+use failure::Error;
+use serde_json::Value;
+use crate::history::Play;
+use crate::library::memory::{MemoryLibrary, Snapshot};
+use crate::library::migration;
+use crate::library::{
+    Album, Annotation, Artist, CanonicalTrack, Genre, Library, LibraryStatistics, Page, Playlist,
+    PlaylistEdit, PruneResult, Query, SearchResults, SmartPlaylist, Subscription, Track,
+};
+use crate::provider::{Provider, SyncedUris};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// `Library` implementation persisting its content to a single file on disk
+///
+/// All entities are held in a `MemoryLibrary` and a snapshot is written after every modification.
+/// Snapshots are written to a temporary file first and then renamed over the previous one,
+/// so a crash while writing leaves the last complete snapshot intact.
+/// When a modification fails or can't be written it is undone, observers only receive the events
+/// of modifications which have been written.
+///
+/// Every call writes the whole library, so prefer the bulk methods (`sync_tracks` etc.)
+/// and `Library::batch` over single calls in loops.
+#[derive(Debug)]
+pub struct FileLibrary {
+    path: PathBuf,
+    library: MemoryLibrary,
+    write_lock: Mutex<()>,
+}
+
+impl FileLibrary {
+    /// Opens the library stored at `path`, starting with an empty library if the file doesn't exist yet
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<FileLibrary, Error> {
+        let path = path.as_ref().to_path_buf();
+        let library = if path.exists() {
+            debug!("Loading library from {}", path.display());
+            let file = File::open(&path)?;
+            let mut snapshot: Value = serde_json::from_reader(BufReader::new(file))?;
+            migration::migrate_snapshot(&mut snapshot)?;
+            let snapshot: Snapshot = serde_json::from_value(snapshot)?;
+            MemoryLibrary::from_snapshot(snapshot)
+        } else {
+            debug!("No library at {}, starting empty", path.display());
+            MemoryLibrary::new()
+        };
+        Ok(FileLibrary {
+            path,
+            library,
+            write_lock: Mutex::new(()),
+        })
+    }
+
+    /// Applies `f` to the library and writes the result, undoing the changes of `f` on failure
+    ///
+    /// Modifications are serialized so an undo never affects a concurrent modification.
+    fn write<F, R>(&self, f: F) -> Result<R, Error>
+    where
+        F: FnOnce(&MemoryLibrary) -> Result<R, Error>,
+    {
+        let _guard = self.write_lock.lock().unwrap();
+        self.library.begin();
+        match f(&self.library).and_then(|result| self.persist().map(|_| result)) {
+            Ok(result) => {
+                self.library.commit();
+                Ok(result)
+            }
+            Err(err) => {
+                warn!("Undoing library modification: {}", err);
+                self.library.rollback();
+                Err(err)
+            }
+        }
+    }
+
+    fn persist(&self) -> Result<(), Error> {
+        trace!("Writing library to {}", self.path.display());
+        let tmp_path = self.path.with_extension("tmp");
+        {
+            let file = File::create(&tmp_path)?;
+            let mut writer = BufWriter::new(file);
+            self.library.write_snapshot(&mut writer)?;
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
+        }
+        fs::rename(&tmp_path, &self.path)?;
+        if let Some(dir) = self.path.parent().filter(|dir| dir.is_dir()) {
+            // Persists the rename itself, not supported on every platform
+            let _ = File::open(dir).and_then(|dir| dir.sync_all());
+        }
+        Ok(())
+    }
+}
+
+impl Library for FileLibrary {
+    fn get_track(&self, id: usize) -> Result<Option<Track>, Error> {
+        self.library.get_track(id)
+    }
+
+    fn get_tracks(&self) -> Result<Vec<Track>, Error> {
+        self.library.get_tracks()
+    }
+
+    fn get_track_by_uri(&self, uri: &str) -> Result<Option<Track>, Error> {
+        self.library.get_track_by_uri(uri)
+    }
+
+    fn get_album(&self, id: usize) -> Result<Option<Album>, Error> {
+        self.library.get_album(id)
+    }
+
+    fn get_albums(&self) -> Result<Vec<Album>, Error> {
+        self.library.get_albums()
+    }
+
+    fn get_album_by_uri(&self, uri: &str) -> Result<Option<Album>, Error> {
+        self.library.get_album_by_uri(uri)
+    }
+
+    fn get_artist(&self, id: usize) -> Result<Option<Artist>, Error> {
+        self.library.get_artist(id)
+    }
+
+    fn get_artists(&self) -> Result<Vec<Artist>, Error> {
+        self.library.get_artists()
+    }
+
+    fn get_artist_by_uri(&self, uri: &str) -> Result<Option<Artist>, Error> {
+        self.library.get_artist_by_uri(uri)
+    }
+
+    fn get_playlist(&self, id: usize) -> Result<Option<Playlist>, Error> {
+        self.library.get_playlist(id)
+    }
+
+    fn get_playlists(&self) -> Result<Vec<Playlist>, Error> {
+        self.library.get_playlists()
+    }
+
+    fn get_playlist_by_uri(&self, uri: &str) -> Result<Option<Playlist>, Error> {
+        self.library.get_playlist_by_uri(uri)
+    }
+
+    fn query_tracks(&self, query: &Query) -> Result<Page<Track>, Error> {
+        self.library.query_tracks(query)
+    }
+
+    fn query_albums(&self, query: &Query) -> Result<Page<Album>, Error> {
+        self.library.query_albums(query)
+    }
+
+    fn query_artists(&self, query: &Query) -> Result<Page<Artist>, Error> {
+        self.library.query_artists(query)
+    }
+
+    fn query_playlists(&self, query: &Query) -> Result<Page<Playlist>, Error> {
+        self.library.query_playlists(query)
+    }
+
+    fn get_album_tracks(&self, album_id: usize) -> Result<Vec<Track>, Error> {
+        self.library.get_album_tracks(album_id)
+    }
+
+    fn get_artist_albums(&self, artist_id: usize) -> Result<Vec<Album>, Error> {
+        self.library.get_artist_albums(artist_id)
+    }
+
+    fn get_artist_compilations(&self, artist_id: usize) -> Result<Vec<Album>, Error> {
+        self.library.get_artist_compilations(artist_id)
+    }
+
+    fn get_artist_top_tracks(&self, artist_id: usize, limit: usize) -> Result<Vec<Track>, Error> {
+        self.library.get_artist_top_tracks(artist_id, limit)
+    }
+
+    fn add_track(&self, track: &mut Track) -> Result<(), Error> {
+        self.write(|library| library.add_track(track))
+    }
+
+    fn add_album(&self, album: &mut Album) -> Result<(), Error> {
+        self.write(|library| library.add_album(album))
+    }
+
+    fn add_artist(&self, artist: &mut Artist) -> Result<(), Error> {
+        self.write(|library| library.add_artist(artist))
+    }
+
+    fn add_playlist(&self, playlist: &mut Playlist) -> Result<(), Error> {
+        self.write(|library| library.add_playlist(playlist))
+    }
+
+    fn add_tracks(&self, tracks: &mut Vec<Track>) -> Result<(), Error> {
+        self.write(|library| library.add_tracks(tracks))
+    }
+
+    fn add_albums(&self, albums: &mut Vec<Album>) -> Result<(), Error> {
+        self.write(|library| library.add_albums(albums))
+    }
+
+    fn add_artists(&self, artists: &mut Vec<Artist>) -> Result<(), Error> {
+        self.write(|library| library.add_artists(artists))
+    }
+
+    fn add_playlists(&self, playlists: &mut Vec<Playlist>) -> Result<(), Error> {
+        self.write(|library| library.add_playlists(playlists))
+    }
+
+    fn sync_track(&self, track: &mut Track) -> Result<(), Error> {
+        self.write(|library| library.sync_track(track))
+    }
+
+    fn sync_album(&self, album: &mut Album) -> Result<(), Error> {
+        self.write(|library| library.sync_album(album))
+    }
+
+    fn sync_artist(&self, artist: &mut Artist) -> Result<(), Error> {
+        self.write(|library| library.sync_artist(artist))
+    }
+
+    fn sync_playlist(&self, playlist: &mut Playlist) -> Result<(), Error> {
+        self.write(|library| library.sync_playlist(playlist))
+    }
+
+    fn sync_tracks(&self, tracks: &mut Vec<Track>) -> Result<(), Error> {
+        self.write(|library| library.sync_tracks(tracks))
+    }
+
+    fn sync_albums(&self, albums: &mut Vec<Album>) -> Result<(), Error> {
+        self.write(|library| library.sync_albums(albums))
+    }
+
+    fn sync_artists(&self, artists: &mut Vec<Artist>) -> Result<(), Error> {
+        self.write(|library| library.sync_artists(artists))
+    }
+
+    fn sync_playlists(&self, playlists: &mut Vec<Playlist>) -> Result<(), Error> {
+        self.write(|library| library.sync_playlists(playlists))
+    }
+
+    fn remove_track(&self, id: usize) -> Result<(), Error> {
+        self.write(|library| library.remove_track(id))
+    }
+
+    fn remove_album(&self, id: usize) -> Result<(), Error> {
+        self.write(|library| library.remove_album(id))
+    }
+
+    fn remove_artist(&self, id: usize) -> Result<(), Error> {
+        self.write(|library| library.remove_artist(id))
+    }
+
+    fn remove_playlist(&self, id: usize) -> Result<(), Error> {
+        self.write(|library| library.remove_playlist(id))
+    }
+
+    fn prune(&self, provider: Provider, synced: &SyncedUris) -> Result<PruneResult, Error> {
+        self.write(|library| library.prune(provider, synced))
+    }
+
+    fn create_playlist(&self, title: String) -> Result<Playlist, Error> {
+        self.write(|library| library.create_playlist(title))
+    }
+
+    fn edit_playlist(&self, id: usize, edit: &PlaylistEdit) -> Result<Playlist, Error> {
+        self.write(|library| library.edit_playlist(id, edit))
+    }
+
+    fn get_annotation(&self, uri: &str) -> Result<Annotation, Error> {
+        self.library.get_annotation(uri)
+    }
+
+    fn get_annotations(&self) -> Result<HashMap<String, Annotation>, Error> {
+        self.library.get_annotations()
+    }
+
+    fn set_annotation(&self, uri: &str, annotation: Annotation) -> Result<(), Error> {
+        self.write(|library| library.set_annotation(uri, annotation))
+    }
+
+    fn get_plays(&self) -> Result<Vec<Play>, Error> {
+        self.library.get_plays()
+    }
+
+    fn add_play(&self, play: Play) -> Result<(), Error> {
+        self.write(|library| library.add_play(play))
+    }
+
+    fn get_favourite_tracks(&self) -> Result<Vec<Track>, Error> {
+        self.library.get_favourite_tracks()
+    }
+
+    fn get_favourite_albums(&self) -> Result<Vec<Album>, Error> {
+        self.library.get_favourite_albums()
+    }
+
+    fn get_favourite_artists(&self) -> Result<Vec<Artist>, Error> {
+        self.library.get_favourite_artists()
+    }
+
+    fn get_genres(&self) -> Result<Vec<Genre>, Error> {
+        self.library.get_genres()
+    }
+
+    fn get_genre_tracks(&self, genre: &Genre) -> Result<Vec<Track>, Error> {
+        self.library.get_genre_tracks(genre)
+    }
+
+    fn get_genre_albums(&self, genre: &Genre) -> Result<Vec<Album>, Error> {
+        self.library.get_genre_albums(genre)
+    }
+
+    fn get_canonical_tracks(&self) -> Result<Vec<CanonicalTrack>, Error> {
+        self.library.get_canonical_tracks()
+    }
+
+    fn get_canonical_track(&self, uri: &str) -> Result<Option<CanonicalTrack>, Error> {
+        self.library.get_canonical_track(uri)
+    }
+
+    fn statistics(&self, top_artists: usize) -> Result<LibraryStatistics, Error> {
+        self.library.statistics(top_artists)
+    }
+
+    fn evaluate_smart_playlist(&self, playlist: &SmartPlaylist) -> Result<Vec<Track>, Error> {
+        self.library.evaluate_smart_playlist(playlist)
+    }
+
+    fn search(&self, query: String) -> Result<SearchResults, Error> {
+        self.library.search(query)
+    }
+
+    fn batch(&self, f: &mut dyn FnMut(&dyn Library) -> Result<(), Error>) -> Result<(), Error> {
+        self.write(|library| f(library))
+    }
+
+    fn observe(&self) -> Subscription {
+        self.library.observe()
+    }
+}
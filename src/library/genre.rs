@@ -0,0 +1,106 @@
+use serde::{Deserializer, Serializer};
+use crate::library::normalize;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/// Musical genre of tracks and albums
+///
+/// Names are normalized on construction: surrounding whitespace is trimmed, inner whitespace
+/// collapsed and every word starts with an uppercase letter, so "hip  hop" becomes "Hip Hop".
+/// Genres compare by `key`, ignoring case, diacritics and separators, so "Hip-Hop" and
+/// "hip hop" are the same genre. Genres are serialized as their name.
+#[derive(Clone, Debug)]
+pub struct Genre {
+    name: String,
+    key: String,
+}
+
+impl Genre {
+    pub fn new<S: AsRef<str>>(name: S) -> Genre {
+        let mut normalized = String::new();
+        for word in name.as_ref().split_whitespace() {
+            if !normalized.is_empty() {
+                normalized.push(' ');
+            }
+            let mut capitalize = true;
+            for c in word.chars() {
+                if capitalize {
+                    normalized.extend(c.to_uppercase());
+                } else {
+                    normalized.push(c);
+                }
+                capitalize = !c.is_alphanumeric() && c != '\'';
+            }
+        }
+        let key = normalize(&normalized)
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect();
+        Genre {
+            name: normalized,
+            key,
+        }
+    }
+
+    /// Splits a list of genres as provided by tags, e.g. "Rock; Pop" or "Jazz/Soul"
+    ///
+    /// Empty entries and duplicates are dropped.
+    pub fn parse_list(list: &str) -> Vec<Genre> {
+        let mut genres: Vec<Genre> = vec![];
+        for name in list.split(&[';', ',', '/'][..]) {
+            let genre = Genre::new(name);
+            if !genre.key().is_empty() && !genres.contains(&genre) {
+                genres.push(genre);
+            }
+        }
+        genres
+    }
+
+    /// Normalized name, see `Genre::new`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Identity of the genre, lowercase alphanumerics without diacritics
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl PartialEq for Genre {
+    fn eq(&self, other: &Genre) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for Genre {}
+
+impl Hash for Genre {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state)
+    }
+}
+
+impl<'a> From<&'a str> for Genre {
+    fn from(name: &'a str) -> Genre {
+        Genre::new(name)
+    }
+}
+
+impl fmt::Display for Genre {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl serde::Serialize for Genre {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.name)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Genre {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Genre, D::Error> {
+        <String as serde::Deserialize>::deserialize(deserializer).map(Genre::new)
+    }
+}
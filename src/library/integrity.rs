@@ -0,0 +1,439 @@
+//! Consistency checks for the content of a `Library`
+//!
+//! `check` scans all entities and reports broken references, duplicate uris, entities
+//! without id and embedded copies not matching the referenced entity.
+//! `repair` additionally fixes these issues through the `Library` trait:
+//!
+//! * references to duplicates are moved to the entity found by uri, the duplicates are removed
+//! * dangling references are relinked by the uri of the embedded copy, the copy is synced
+//!   when there is no such entity and the reference is cleared when there is no copy
+//! * embedded copies are replaced by the referenced entity
+//! * entities without id are synced when their uri isn't stored yet
+use log::debug;
+use failure::Error;
+use serde_derive::{Deserialize, Serialize};
+use crate::library::{Album, Artist, EntityKind, Library, Playlist, PlaylistEdit, Track};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IntegrityIssue {
+    /// The entity is stored without id
+    MissingId { kind: EntityKind, uri: String },
+    /// Several entities share the uri, `kept` is the one the library returns for the uri
+    DuplicateUri {
+        kind: EntityKind,
+        uri: String,
+        kept: usize,
+        duplicates: Vec<usize>,
+    },
+    /// The entity with `uri` references the `target` with `id`, which doesn't exist
+    DanglingReference {
+        kind: EntityKind,
+        uri: String,
+        target: EntityKind,
+        id: usize,
+    },
+    /// The embedded copy of `target` doesn't match the referenced entity
+    ///
+    /// Also reported for copies without reference and references to a duplicate.
+    /// References without copy are fine.
+    MismatchedCopy {
+        kind: EntityKind,
+        uri: String,
+        target: EntityKind,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IntegrityReport {
+    /// Issues found by the scan
+    pub issues: Vec<IntegrityIssue>,
+    /// Issues still found after repairing, None when the library wasn't repaired
+    pub remaining: Option<Vec<IntegrityIssue>>,
+}
+
+impl IntegrityReport {
+    /// Whether the library is consistent now, after repairing if it was repaired
+    pub fn is_consistent(&self) -> bool {
+        match &self.remaining {
+            Some(remaining) => remaining.is_empty(),
+            None => self.issues.is_empty(),
+        }
+    }
+}
+
+/// Scans the library without changing it
+pub fn check(library: &dyn Library) -> Result<IntegrityReport, Error> {
+    let scan = Scan::new(library)?;
+    Ok(IntegrityReport {
+        issues: scan.issues,
+        remaining: None,
+    })
+}
+
+/// Scans the library, fixes the issues it can and scans again
+///
+/// All repairs are applied in one `Library::batch`.
+pub fn repair(library: &dyn Library) -> Result<IntegrityReport, Error> {
+    let scan = Scan::new(library)?;
+    if scan.issues.is_empty() {
+        return Ok(IntegrityReport {
+            issues: vec![],
+            remaining: Some(vec![]),
+        });
+    }
+    debug!("Repairing {} library issues", scan.issues.len());
+    library.batch(&mut |library| {
+        scan.repairs.iter().try_for_each(|repair| repair.apply(library))
+    })?;
+    let remaining = Scan::new(library)?.issues;
+    Ok(IntegrityReport {
+        issues: scan.issues,
+        remaining: Some(remaining),
+    })
+}
+
+/// Change fixing one or more issues, applied in the order they were found
+enum Repair {
+    SyncTrack(Box<Track>),
+    SyncAlbum(Box<Album>),
+    SyncArtist(Artist),
+    SyncPlaylist(Playlist),
+    EditPlaylist(usize, PlaylistEdit),
+    RemoveTrack(usize),
+    RemoveAlbum(usize),
+    RemoveArtist(usize),
+    RemovePlaylist(usize),
+}
+
+impl Repair {
+    fn apply(&self, library: &dyn Library) -> Result<(), Error> {
+        match self {
+            Repair::SyncTrack(track) => library.sync_track(&mut Track::clone(track)),
+            Repair::SyncAlbum(album) => library.sync_album(&mut Album::clone(album)),
+            Repair::SyncArtist(artist) => library.sync_artist(&mut artist.clone()),
+            Repair::SyncPlaylist(playlist) => library.sync_playlist(&mut playlist.clone()),
+            Repair::EditPlaylist(id, edit) => library.edit_playlist(*id, edit).map(|_| ()),
+            Repair::RemoveTrack(id) => library.remove_track(*id),
+            Repair::RemoveAlbum(id) => library.remove_album(*id),
+            Repair::RemoveArtist(id) => library.remove_artist(*id),
+            Repair::RemovePlaylist(id) => library.remove_playlist(*id),
+        }
+    }
+}
+
+/// Entities of one kind by id with the id kept for every uri
+struct Entities<T> {
+    by_id: BTreeMap<usize, T>,
+    kept: HashMap<String, usize>,
+    /// Additional uris the library resolves to an entity, e.g. parts of a compilation
+    aliases: HashMap<String, usize>,
+}
+
+impl<T: Clone> Entities<T> {
+    /// Returns the kept entity for the uri
+    fn by_uri(&self, uri: &str) -> Option<&T> {
+        self.kept
+            .get(uri)
+            .or_else(|| self.aliases.get(uri))
+            .and_then(|id| self.by_id.get(id))
+    }
+
+    /// Records the uris of embedded copies the library resolves to another entity
+    fn add_aliases<'a, I, L>(&mut self, uris: I, lookup: L) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = &'a str>,
+        L: Fn(&str) -> Result<Option<usize>, Error>,
+    {
+        for uri in uris {
+            if self.kept.contains_key(uri) || self.aliases.contains_key(uri) {
+                continue;
+            }
+            if let Some(id) = lookup(uri)?.filter(|id| self.by_id.contains_key(id)) {
+                self.aliases.insert(uri.to_owned(), id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the embedded copy matches the entity, copies by an alias only have to match the id
+    fn is_copy(
+        &self,
+        copy: &T,
+        entity: &T,
+        identity: fn(&T) -> (Option<usize>, &str, &str),
+    ) -> bool {
+        let (copy_id, copy_uri, _) = identity(copy);
+        identity(copy) == identity(entity)
+            || (copy_id == identity(entity).0 && self.aliases.get(copy_uri) == copy_id.as_ref())
+    }
+
+    fn is_kept(&self, id: usize, uri: &str) -> bool {
+        self.kept.get(uri) == Some(&id)
+    }
+
+    /// Resolves a reference with its embedded copy
+    ///
+    /// Returns the issue of the reference, if any, and the entity it should reference.
+    fn resolve(
+        &self,
+        id: Option<usize>,
+        copy: Option<&T>,
+        identity: fn(&T) -> (Option<usize>, &str, &str),
+    ) -> (Option<Problem>, Option<T>) {
+        let stored = id.and_then(|id| self.by_id.get(&id));
+        let resolved = match (stored, copy) {
+            (Some(stored), _) => self.by_uri(identity(stored).1).cloned(),
+            (None, Some(copy)) => self
+                .by_uri(identity(copy).1)
+                .cloned()
+                .or_else(|| Some(copy.clone())),
+            (None, None) => None,
+        };
+        let resolved_id = resolved.as_ref().and_then(|resolved| identity(resolved).0);
+        let problem = match (id, stored, copy) {
+            (Some(id), None, _) => Some(Problem::Dangling(id)),
+            (Some(id), Some(_), _) if resolved_id != Some(id) => Some(Problem::Mismatched),
+            (Some(_), Some(_), Some(copy)) => resolved
+                .as_ref()
+                .filter(|resolved| !self.is_copy(copy, resolved, identity))
+                .map(|_| Problem::Mismatched),
+            (None, _, Some(_)) => Some(Problem::Mismatched),
+            (_, _, None) => None,
+        };
+        (problem, resolved)
+    }
+}
+
+enum Problem {
+    Dangling(usize),
+    Mismatched,
+}
+
+impl Problem {
+    fn issue(self, kind: EntityKind, uri: &str, target: EntityKind) -> IntegrityIssue {
+        let uri = uri.to_owned();
+        match self {
+            Problem::Dangling(id) => IntegrityIssue::DanglingReference {
+                kind,
+                uri,
+                target,
+                id,
+            },
+            Problem::Mismatched => IntegrityIssue::MismatchedCopy { kind, uri, target },
+        }
+    }
+}
+
+fn track_identity(track: &Track) -> (Option<usize>, &str, &str) {
+    (track.id, &track.uri, &track.title)
+}
+
+fn album_identity(album: &Album) -> (Option<usize>, &str, &str) {
+    (album.id, &album.uri, &album.title)
+}
+
+fn artist_identity(artist: &Artist) -> (Option<usize>, &str, &str) {
+    (artist.id, &artist.uri, &artist.name)
+}
+
+fn playlist_identity(playlist: &Playlist) -> (Option<usize>, &str, &str) {
+    (playlist.id, &playlist.uri, &playlist.title)
+}
+
+struct Scan {
+    issues: Vec<IntegrityIssue>,
+    repairs: Vec<Repair>,
+}
+
+impl Scan {
+    fn new(library: &dyn Library) -> Result<Scan, Error> {
+        let mut scan = Scan {
+            issues: vec![],
+            repairs: vec![],
+        };
+        let tracks = scan.entities(
+            EntityKind::Track,
+            library.get_tracks()?,
+            track_identity,
+            |uri| Ok(library.get_track_by_uri(uri)?.and_then(|track| track.id)),
+            |track| Repair::SyncTrack(Box::new(track)),
+        )?;
+        let album_id = |uri: &str| Ok(library.get_album_by_uri(uri)?.and_then(|album| album.id));
+        let mut albums = scan.entities(
+            EntityKind::Album,
+            library.get_albums()?,
+            album_identity,
+            album_id,
+            |album| Repair::SyncAlbum(Box::new(album)),
+        )?;
+        let copies = tracks.by_id.values().filter_map(|track| track.album.as_ref());
+        albums.add_aliases(copies.map(|album| album.uri.as_str()), album_id)?;
+        let artists = scan.entities(
+            EntityKind::Artist,
+            library.get_artists()?,
+            artist_identity,
+            |uri| Ok(library.get_artist_by_uri(uri)?.and_then(|artist| artist.id)),
+            Repair::SyncArtist,
+        )?;
+        let playlists = scan.entities(
+            EntityKind::Playlist,
+            library.get_playlists()?,
+            playlist_identity,
+            |uri| Ok(library.get_playlist_by_uri(uri)?.and_then(|playlist| playlist.id)),
+            Repair::SyncPlaylist,
+        )?;
+
+        for (id, track) in &tracks.by_id {
+            if tracks.is_kept(*id, &track.uri) {
+                scan.check_track(track, &albums, &artists);
+            }
+        }
+        for (id, album) in &albums.by_id {
+            if albums.is_kept(*id, &album.uri) {
+                scan.check_album(album, &artists);
+            }
+        }
+        for (id, playlist) in &playlists.by_id {
+            if playlists.is_kept(*id, &playlist.uri) {
+                scan.check_playlist(*id, playlist, &tracks);
+            }
+        }
+        scan.remove_duplicates(&tracks, Repair::RemoveTrack);
+        scan.remove_duplicates(&albums, Repair::RemoveAlbum);
+        scan.remove_duplicates(&artists, Repair::RemoveArtist);
+        scan.remove_duplicates(&playlists, Repair::RemovePlaylist);
+        Ok(scan)
+    }
+
+    /// Indexes the entities and reports missing ids and duplicate uris
+    fn entities<T, L>(
+        &mut self,
+        kind: EntityKind,
+        entities: Vec<T>,
+        identity: fn(&T) -> (Option<usize>, &str, &str),
+        lookup: L,
+        sync: fn(T) -> Repair,
+    ) -> Result<Entities<T>, Error>
+    where
+        T: Clone,
+        L: Fn(&str) -> Result<Option<usize>, Error>,
+    {
+        let mut by_id = BTreeMap::new();
+        let mut by_uri: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut missing = vec![];
+        for entity in entities {
+            let (id, uri, _) = identity(&entity);
+            match id {
+                Some(id) => {
+                    by_uri.entry(uri.to_owned()).or_default().push(id);
+                    by_id.insert(id, entity);
+                }
+                None => missing.push(entity),
+            }
+        }
+        let mut kept = HashMap::new();
+        let mut duplicates: Vec<(String, Vec<usize>)> = vec![];
+        for (uri, ids) in by_uri {
+            if ids.len() == 1 {
+                kept.insert(uri, ids[0]);
+            } else {
+                duplicates.push((uri, ids));
+            }
+        }
+        duplicates.sort();
+        for (uri, ids) in duplicates {
+            let found = lookup(&uri)?.filter(|id| ids.contains(id));
+            let kept_id = found.unwrap_or(ids[0]);
+            self.issues.push(IntegrityIssue::DuplicateUri {
+                kind,
+                uri: uri.clone(),
+                kept: kept_id,
+                duplicates: ids.into_iter().filter(|id| *id != kept_id).collect(),
+            });
+            kept.insert(uri, kept_id);
+        }
+        for entity in missing {
+            let uri = identity(&entity).1.to_owned();
+            let stored = kept.contains_key(&uri);
+            self.issues.push(IntegrityIssue::MissingId { kind, uri });
+            if !stored {
+                self.repairs.push(sync(entity));
+            }
+        }
+        Ok(Entities {
+            by_id,
+            kept,
+            aliases: HashMap::new(),
+        })
+    }
+
+    fn check_track(&mut self, track: &Track, albums: &Entities<Album>, artists: &Entities<Artist>) {
+        let (album_problem, album) =
+            albums.resolve(track.album_id, track.album.as_ref(), album_identity);
+        let (artist_problem, artist) =
+            artists.resolve(track.artist_id, track.artist.as_ref(), artist_identity);
+        if album_problem.is_none() && artist_problem.is_none() {
+            return;
+        }
+        if let Some(problem) = album_problem {
+            let issue = problem.issue(EntityKind::Track, &track.uri, EntityKind::Album);
+            self.issues.push(issue);
+        }
+        if let Some(problem) = artist_problem {
+            let issue = problem.issue(EntityKind::Track, &track.uri, EntityKind::Artist);
+            self.issues.push(issue);
+        }
+        let mut repaired = track.clone();
+        repaired.album_id = album.as_ref().and_then(|album| album.id);
+        repaired.album = album.filter(|_| track.album.is_some());
+        repaired.artist_id = artist.as_ref().and_then(|artist| artist.id);
+        repaired.artist = artist.filter(|_| track.artist.is_some());
+        self.repairs.push(Repair::SyncTrack(Box::new(repaired)));
+    }
+
+    fn check_album(&mut self, album: &Album, artists: &Entities<Artist>) {
+        let (problem, artist) =
+            artists.resolve(album.artist_id, album.artist.as_ref(), artist_identity);
+        if let Some(problem) = problem {
+            let issue = problem.issue(EntityKind::Album, &album.uri, EntityKind::Artist);
+            self.issues.push(issue);
+            let mut repaired = album.clone();
+            repaired.artist_id = artist.as_ref().and_then(|artist| artist.id);
+            repaired.artist = artist.filter(|_| album.artist.is_some());
+            self.repairs.push(Repair::SyncAlbum(Box::new(repaired)));
+        }
+    }
+
+    /// Replaces broken entries one by one so the order of the other entries is kept
+    fn check_playlist(&mut self, id: usize, playlist: &Playlist, tracks: &Entities<Track>) {
+        let mut removed = 0;
+        for (position, entry) in playlist.tracks.iter().enumerate() {
+            let (problem, track) = tracks.resolve(entry.id, Some(entry), track_identity);
+            if let Some(problem) = problem {
+                let issue = problem.issue(EntityKind::Playlist, &playlist.uri, EntityKind::Track);
+                self.issues.push(issue);
+                let position = position - removed;
+                let edit = PlaylistEdit::RemoveTracks(vec![position]);
+                self.repairs.push(Repair::EditPlaylist(id, edit));
+                match track {
+                    Some(track) => {
+                        let edit = PlaylistEdit::AddTracks {
+                            tracks: vec![track],
+                            position: Some(position),
+                        };
+                        self.repairs.push(Repair::EditPlaylist(id, edit));
+                    }
+                    None => removed += 1,
+                }
+            }
+        }
+    }
+
+    fn remove_duplicates<T>(&mut self, entities: &Entities<T>, remove: fn(usize) -> Repair) {
+        let kept: HashSet<&usize> = entities.kept.values().collect();
+        for id in entities.by_id.keys().filter(|id| !kept.contains(id)) {
+            self.repairs.push(remove(*id));
+        }
+    }
+}
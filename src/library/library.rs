@@ -1,9 +1,35 @@
+use serde_derive::{Deserialize, Serialize};
 use failure::Error;
-use crate::library::{Album, Artist, Playlist, Track};
+use crate::history::Play;
+use crate::library::{
+    Album, Annotation, Artist, CanonicalTrack, Genre, LibraryStatistics, Page, Playlist,
+    PlaylistEdit, Query, SmartPlaylist, Subscription, Track,
+};
+use crate::provider::{Provider, SyncedUris};
+use std::collections::HashMap;
 use std::sync::Arc;
 
 pub type SharedLibrary = Arc<Box<dyn Library>>;
 
+/// Kinds of entities stored in a `Library`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EntityKind {
+    Track,
+    Album,
+    Artist,
+    Playlist,
+}
+
+/// Number of entities removed by `Library::prune`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PruneResult {
+    pub tracks: usize,
+    pub albums: usize,
+    pub artists: usize,
+    pub playlists: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResults {
     pub tracks: Vec<Track>,
     pub albums: Vec<Album>,
@@ -11,38 +37,160 @@ pub struct SearchResults {
     pub playlists: Vec<Playlist>,
 }
 
+/// Storage for all tracks, albums, artists and playlists
+///
+/// `library::conformance` contains checks for the contract described here.
 pub trait Library: Sync + Send {
+    /// Returns the track with the given id or None when there is no such track
     fn get_track(&self, id: usize) -> Result<Option<Track>, Error>;
     fn get_tracks(&self) -> Result<Vec<Track>, Error>;
+    /// Returns the track with the given uri, backends should index uris for this lookup
+    fn get_track_by_uri(&self, uri: &str) -> Result<Option<Track>, Error>;
 
     fn get_album(&self, id: usize) -> Result<Option<Album>, Error>;
     fn get_albums(&self) -> Result<Vec<Album>, Error>;
+    fn get_album_by_uri(&self, uri: &str) -> Result<Option<Album>, Error>;
 
     fn get_artist(&self, id: usize) -> Result<Option<Artist>, Error>;
     fn get_artists(&self) -> Result<Vec<Artist>, Error>;
+    fn get_artist_by_uri(&self, uri: &str) -> Result<Option<Artist>, Error>;
 
     fn get_playlist(&self, id: usize) -> Result<Option<Playlist>, Error>;
     fn get_playlists(&self) -> Result<Vec<Playlist>, Error>;
+    fn get_playlist_by_uri(&self, uri: &str) -> Result<Option<Playlist>, Error>;
+
+    /// Returns the page of tracks selected by the query
+    fn query_tracks(&self, query: &Query) -> Result<Page<Track>, Error>;
+    fn query_albums(&self, query: &Query) -> Result<Page<Album>, Error>;
+    fn query_artists(&self, query: &Query) -> Result<Page<Artist>, Error>;
+    fn query_playlists(&self, query: &Query) -> Result<Page<Playlist>, Error>;
 
+    /// Returns the tracks of an album ordered by disc and track number
+    fn get_album_tracks(&self, album_id: usize) -> Result<Vec<Track>, Error>;
+    /// Returns the albums of an artist ordered by title
+    fn get_artist_albums(&self, artist_id: usize) -> Result<Vec<Album>, Error>;
+    /// Returns the compilations with tracks of an artist ordered by title
+    ///
+    /// The artist is the track artist, compilations of which the artist is the album artist
+    /// are part of `get_artist_albums`.
+    fn get_artist_compilations(&self, artist_id: usize) -> Result<Vec<Album>, Error>;
+    /// Returns up to `limit` tracks of an artist, most popular first
+    fn get_artist_top_tracks(&self, artist_id: usize, limit: usize) -> Result<Vec<Track>, Error>;
+
+    /// Insert a new track and assign its id
+    ///
+    /// Does not check for existing tracks with the same uri.
+    /// An embedded artist or album is synced and linked through `artist_id` and `album_id`.
+    /// The tracks of an album and the albums and top tracks of an artist are synced and linked as well.
     fn add_track(&self, track: &mut Track) -> Result<(), Error>;
     fn add_album(&self, album: &mut Album) -> Result<(), Error>;
     fn add_artist(&self, artist: &mut Artist) -> Result<(), Error>;
     fn add_playlist(&self, playlist: &mut Playlist) -> Result<(), Error>;
 
+    /// Insert multiple tracks, see `add_track`
     fn add_tracks(&self, tracks: &mut Vec<Track>) -> Result<(), Error>;
     fn add_albums(&self, albums: &mut Vec<Album>) -> Result<(), Error>;
     fn add_artists(&self, artists: &mut Vec<Artist>) -> Result<(), Error>;
     fn add_playlists(&self, playlists: &mut Vec<Playlist>) -> Result<(), Error>;
 
+    /// Insert a track or update the existing track with the same uri
+    ///
+    /// Sets the id of the given track to the one stored in the library.
+    /// An embedded artist or album is synced and linked through `artist_id` and `album_id`.
     fn sync_track(&self, track: &mut Track) -> Result<(), Error>;
     fn sync_album(&self, album: &mut Album) -> Result<(), Error>;
     fn sync_artist(&self, artist: &mut Artist) -> Result<(), Error>;
     fn sync_playlist(&self, playlist: &mut Playlist) -> Result<(), Error>;
 
+    /// Sync multiple tracks, see `sync_track`
     fn sync_tracks(&self, tracks: &mut Vec<Track>) -> Result<(), Error>;
     fn sync_albums(&self, albums: &mut Vec<Album>) -> Result<(), Error>;
     fn sync_artists(&self, artists: &mut Vec<Artist>) -> Result<(), Error>;
     fn sync_playlists(&self, playlists: &mut Vec<Playlist>) -> Result<(), Error>;
 
+    /// Remove the track with the given id, it is removed from all playlists as well
+    fn remove_track(&self, id: usize) -> Result<(), Error>;
+    /// Remove the album with the given id and clear all references to it
+    fn remove_album(&self, id: usize) -> Result<(), Error>;
+    /// Remove the artist with the given id and clear all references to it
+    fn remove_artist(&self, id: usize) -> Result<(), Error>;
+    fn remove_playlist(&self, id: usize) -> Result<(), Error>;
+
+    /// Remove all tracks, albums and playlists of `provider` which are not part of `synced`
+    ///
+    /// Artists don't belong to a provider, they are removed when they are not part of `synced`
+    /// and were only referenced by removed entities.
+    /// References to removed entities are cleared.
+    fn prune(&self, provider: Provider, synced: &SyncedUris) -> Result<PruneResult, Error>;
+
+    /// Create an empty playlist only stored in the library
+    ///
+    /// The playlist belongs to `Provider::Internal` and gets a generated uri.
+    fn create_playlist(&self, title: String) -> Result<Playlist, Error>;
+
+    /// Apply the edit to the playlist with the given id and return the edited playlist
+    ///
+    /// Added tracks are synced like the tracks of `sync_playlist`.
+    /// Fails with `PlaylistError` when the playlist doesn't exist or a position is out of range,
+    /// the playlist is left untouched in that case.
+    /// This doesn't push the edit to the provider, see `Rustic::edit_playlist`.
+    fn edit_playlist(&self, id: usize, edit: &PlaylistEdit) -> Result<Playlist, Error>;
+
+    /// Returns the tracks currently selected by the rules of the smart playlist
+    fn evaluate_smart_playlist(&self, playlist: &SmartPlaylist) -> Result<Vec<Track>, Error>;
+
+    /// Returns the annotation of the entity with the given uri, the default when there is none
+    fn get_annotation(&self, uri: &str) -> Result<Annotation, Error>;
+    /// Returns all stored annotations by uri, including those of entities not in the library
+    fn get_annotations(&self) -> Result<HashMap<String, Annotation>, Error>;
+    /// Store the annotation for the entity with the given uri
+    ///
+    /// Annotations are kept across syncs and removals, storing the default annotation clears it.
+    fn set_annotation(&self, uri: &str, annotation: Annotation) -> Result<(), Error>;
+
+    /// Returns all stored plays in the order they were added
+    fn get_plays(&self) -> Result<Vec<Play>, Error>;
+    /// Stores a play recorded by `History`, plays are kept across syncs and removals
+    fn add_play(&self, play: Play) -> Result<(), Error>;
+
+    /// Returns all stored tracks marked as favourite
+    fn get_favourite_tracks(&self) -> Result<Vec<Track>, Error>;
+    fn get_favourite_albums(&self) -> Result<Vec<Album>, Error>;
+    fn get_favourite_artists(&self) -> Result<Vec<Artist>, Error>;
+
+    /// Returns every genre of the stored tracks and albums once, ordered by name
+    fn get_genres(&self) -> Result<Vec<Genre>, Error>;
+    /// Returns all tracks tagged with the genre, ordered by title
+    fn get_genre_tracks(&self, genre: &Genre) -> Result<Vec<Track>, Error>;
+    /// Returns all albums tagged with the genre or containing a track tagged with it, ordered by title
+    fn get_genre_albums(&self, genre: &Genre) -> Result<Vec<Album>, Error>;
+
+    /// Returns all tracks with their duplicates merged, in the order of their first source
+    ///
+    /// See `CanonicalTrack` and `Track::is_duplicate`.
+    fn get_canonical_tracks(&self) -> Result<Vec<CanonicalTrack>, Error>;
+    /// Returns the canonical track the track with the given uri is a source of
+    fn get_canonical_track(&self, uri: &str) -> Result<Option<CanonicalTrack>, Error>;
+
+    /// Returns counts and aggregates over all entities with up to `top_artists` artists
+    ///
+    /// Backends should compute this without loading all entities, it backs dashboards.
+    fn statistics(&self, top_artists: usize) -> Result<LibraryStatistics, Error>;
+
+    /// Search for tracks, albums, artists and playlists, most relevant results first
+    ///
+    /// See `SearchQuery` for the query syntax and ranking.
     fn search(&self, query: String) -> Result<SearchResults, Error>;
+
+    /// Runs `f` with a handle to the library, modifications made through it are stored at once
+    ///
+    /// Backends writing every modification to disk write the modifications of `f` once it returns,
+    /// so prefer this over single calls in loops. `f` must not modify the library other than through
+    /// the handle. Whether modifications are undone when `f` fails depends on the backend.
+    fn batch(&self, f: &mut dyn FnMut(&dyn Library) -> Result<(), Error>) -> Result<(), Error>;
+
+    /// Returns a subscription receiving every change to the library
+    ///
+    /// Events are buffered until they are received, dropping the subscription unsubscribes.
+    fn observe(&self) -> Subscription;
 }
@@ -0,0 +1,1289 @@
+use failure::Error;
+use serde_derive::{Deserialize, Serialize};
+use crate::library::{
+    Album, Annotation, Artist, ArtistStatistics, CanonicalTrack, Filter, Genre, Library,
+    LibraryEvent, LibraryStatistics, Page, Playlist, PlaylistEdit, PlaylistError, PruneResult,
+    Query, SearchQuery, SearchResults, SmartPlaylist, SortKey, Subscription, Track,
+};
+use crate::history::Play;
+use crate::library::canonical::CanonicalIndex;
+use crate::library::event::{Observers, SharedObservers};
+use crate::library::migration::SCHEMA_VERSION;
+use crate::provider::{Provider, SyncedUris};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io;
+use std::mem;
+use std::sync::RwLock;
+
+/// Thread-safe in-memory implementation of `Library`
+///
+/// Every entity is indexed by id and uri, `add_*` always inserts a new entry
+/// while `sync_*` updates the entry with the same uri in place.
+/// Embedded artists and albums are synced as well and linked through `artist_id` and `album_id`.
+#[derive(Debug, Default)]
+pub struct MemoryLibrary {
+    store: RwLock<Store>,
+    observers: SharedObservers,
+}
+
+/// Plain copy of the whole content of a `MemoryLibrary`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Snapshot {
+    /// Schema version, see `library::migration`
+    #[serde(default)]
+    pub version: u32,
+    pub tracks: Vec<Track>,
+    pub albums: Vec<Album>,
+    pub artists: Vec<Artist>,
+    pub playlists: Vec<Playlist>,
+    #[serde(default)]
+    pub annotations: HashMap<String, Annotation>,
+    /// Additional uris of albums by the id of the album, see `Album::is_same_compilation`
+    #[serde(default)]
+    pub album_aliases: BTreeMap<usize, BTreeSet<String>>,
+    #[serde(default)]
+    pub plays: Vec<Play>,
+}
+
+/// Borrowed `Snapshot` of a store, serialized the same way
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    version: u32,
+    tracks: Vec<&'a Track>,
+    albums: Vec<&'a Album>,
+    artists: Vec<&'a Artist>,
+    playlists: Vec<&'a Playlist>,
+    annotations: &'a HashMap<String, Annotation>,
+    album_aliases: &'a BTreeMap<usize, BTreeSet<String>>,
+    plays: &'a [Play],
+}
+
+impl MemoryLibrary {
+    pub fn new() -> MemoryLibrary {
+        MemoryLibrary::default()
+    }
+
+    /// Restores a library from a snapshot, keeping the ids of all entities
+    pub(crate) fn from_snapshot(snapshot: Snapshot) -> MemoryLibrary {
+        MemoryLibrary {
+            store: RwLock::new(Store::restore(snapshot)),
+            ..MemoryLibrary::default()
+        }
+    }
+
+    /// Applies `f` to the store and notifies all observers about the changes
+    ///
+    /// The observers are locked before the store is released and notified afterwards,
+    /// so events of concurrent writes arrive in order without blocking readers.
+    fn write<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Store) -> R,
+    {
+        let mut store = self.store.write().unwrap();
+        let result = f(&mut store);
+        // changes which can still be undone are only sent on commit
+        let events = match store.undo {
+            Some(_) => vec![],
+            None => mem::take(&mut store.events),
+        };
+        let observers = self.observers.lock().unwrap();
+        drop(store);
+        observers.notify(events);
+        result
+    }
+
+    /// Starts recording how to undo the following changes, their events are held back meanwhile
+    ///
+    /// Every `begin` has to be followed by `commit` or `rollback` before the next one.
+    pub(crate) fn begin(&self) {
+        self.store.write().unwrap().begin();
+    }
+
+    /// Keeps the changes since `begin` and notifies the observers about them
+    pub(crate) fn commit(&self) {
+        let mut store = self.store.write().unwrap();
+        let events = store.commit();
+        let observers = self.observers.lock().unwrap();
+        drop(store);
+        observers.notify(events);
+    }
+
+    /// Undoes the changes since `begin`, the observers are never notified about them
+    pub(crate) fn rollback(&self) {
+        self.store.write().unwrap().rollback();
+    }
+
+    /// Serializes the whole content as `Snapshot` without copying it
+    pub(crate) fn write_snapshot<W: io::Write>(&self, writer: W) -> Result<(), Error> {
+        let store = self.store.read().unwrap();
+        let snapshot = SnapshotRef {
+            version: SCHEMA_VERSION,
+            tracks: store.tracks.entries.values().collect(),
+            albums: store.albums.entries.values().collect(),
+            artists: store.artists.entries.values().collect(),
+            playlists: store.playlists.entries.values().collect(),
+            annotations: &store.annotations,
+            album_aliases: &store.albums.aliases,
+            plays: &store.plays,
+        };
+        serde_json::to_writer(writer, &snapshot)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Change {
+    Added,
+    Updated,
+    Removed,
+}
+
+trait Entity: Clone {
+    fn id(&self) -> Option<usize>;
+    fn set_id(&mut self, id: usize);
+    fn uri(&self) -> &str;
+    fn event(&self, change: Change) -> LibraryEvent;
+}
+
+macro_rules! entity {
+    ($t:ty, $added:ident, $updated:ident, $removed:ident) => {
+        impl Entity for $t {
+            fn id(&self) -> Option<usize> {
+                self.id
+            }
+
+            fn set_id(&mut self, id: usize) {
+                self.id = Some(id);
+            }
+
+            fn uri(&self) -> &str {
+                &self.uri
+            }
+
+            fn event(&self, change: Change) -> LibraryEvent {
+                match change {
+                    Change::Added => LibraryEvent::$added(self.clone()),
+                    Change::Updated => LibraryEvent::$updated(self.clone()),
+                    Change::Removed => LibraryEvent::$removed(self.clone()),
+                }
+            }
+        }
+    };
+}
+
+entity!(Track, TrackAdded, TrackUpdated, TrackRemoved);
+entity!(Album, AlbumAdded, AlbumUpdated, AlbumRemoved);
+entity!(Artist, ArtistAdded, ArtistUpdated, ArtistRemoved);
+entity!(Playlist, PlaylistAdded, PlaylistUpdated, PlaylistRemoved);
+
+#[derive(Debug)]
+struct Table<T> {
+    next_id: usize,
+    entries: BTreeMap<usize, T>,
+    /// Ids by uri, including aliases
+    uris: HashMap<String, usize>,
+    /// Additional uris of entries by id, see `Table::alias`
+    aliases: BTreeMap<usize, BTreeSet<String>>,
+    /// Set while the changes to the table can be undone, see `MemoryLibrary::begin`
+    undo: Option<Undo<T>>,
+}
+
+/// State of a table before its first change since `Table::begin`
+///
+/// Only the first previous value of every changed entry and uri is kept.
+#[derive(Debug)]
+struct Undo<T> {
+    next_id: usize,
+    entries: BTreeMap<usize, (Option<T>, Option<BTreeSet<String>>)>,
+    uris: HashMap<String, Option<usize>>,
+}
+
+impl<T> Default for Table<T> {
+    fn default() -> Table<T> {
+        Table {
+            next_id: 1,
+            entries: BTreeMap::new(),
+            uris: HashMap::new(),
+            aliases: BTreeMap::new(),
+            undo: None,
+        }
+    }
+}
+
+impl<T: Entity> Table<T> {
+    fn restore(entities: Vec<T>) -> Table<T> {
+        let next_id = entities
+            .iter()
+            .filter_map(Entity::id)
+            .max()
+            .map_or(1, |id| id + 1);
+        let mut table = Table {
+            next_id,
+            ..Table::default()
+        };
+        for mut entity in entities {
+            match entity.id() {
+                Some(id) => {
+                    table.uris.insert(entity.uri().to_owned(), id);
+                    table.entries.insert(id, entity);
+                }
+                None => {
+                    table.insert(&mut entity);
+                }
+            }
+        }
+        table
+    }
+
+    fn begin(&mut self) {
+        self.undo = Some(Undo {
+            next_id: self.next_id,
+            entries: BTreeMap::new(),
+            uris: HashMap::new(),
+        });
+    }
+
+    fn commit(&mut self) {
+        self.undo = None;
+    }
+
+    /// Restores the state of `begin`, returns the ids of the changed entries with their undone state
+    fn rollback(&mut self) -> Vec<(usize, Option<T>)> {
+        let undo = match self.undo.take() {
+            Some(undo) => undo,
+            None => return vec![],
+        };
+        self.next_id = undo.next_id;
+        for (uri, id) in undo.uris {
+            match id {
+                Some(id) => self.uris.insert(uri, id),
+                None => self.uris.remove(&uri),
+            };
+        }
+        let mut undone = vec![];
+        for (id, (entity, aliases)) in undo.entries {
+            match aliases {
+                Some(aliases) => self.aliases.insert(id, aliases),
+                None => self.aliases.remove(&id),
+            };
+            let current = match entity {
+                Some(entity) => self.entries.insert(id, entity),
+                None => self.entries.remove(&id),
+            };
+            undone.push((id, current));
+        }
+        undone
+    }
+
+    /// Keeps the state of the entry before its first change for `rollback`
+    fn log_entry(&mut self, id: usize) {
+        if let Some(undo) = &mut self.undo {
+            let (entries, aliases) = (&self.entries, &self.aliases);
+            undo.entries
+                .entry(id)
+                .or_insert_with(|| (entries.get(&id).cloned(), aliases.get(&id).cloned()));
+        }
+    }
+
+    fn log_uri(&mut self, uri: &str) {
+        if let Some(undo) = &mut self.undo {
+            if !undo.uris.contains_key(uri) {
+                undo.uris.insert(uri.to_owned(), self.uris.get(uri).cloned());
+            }
+        }
+    }
+
+    fn remove(&mut self, id: usize) -> Option<T> {
+        self.log_entry(id);
+        let entity = self.entries.remove(&id)?;
+        let aliases = self.aliases.remove(&id).unwrap_or_default();
+        for uri in aliases.iter().map(String::as_str).chain(Some(entity.uri())) {
+            if self.uris.get(uri) == Some(&id) {
+                self.log_uri(uri);
+                self.uris.remove(uri);
+            }
+        }
+        Some(entity)
+    }
+
+    /// Makes the entry with the id available by another uri as well
+    ///
+    /// The entry keeps its own uri, aliases are removed together with the entry.
+    fn alias(&mut self, uri: String, id: usize) {
+        if !self.entries.contains_key(&id) {
+            return;
+        }
+        self.log_entry(id);
+        self.log_uri(&uri);
+        self.uris.insert(uri.clone(), id);
+        self.aliases.entry(id).or_default().insert(uri);
+    }
+
+    /// Whether the entry is stored by one of the uris, including its aliases
+    fn has_uri(&self, id: usize, uris: &HashSet<String>) -> bool {
+        let entity = self.entries.get(&id);
+        entity.is_some_and(|entity| uris.contains(entity.uri()))
+            || self
+                .aliases
+                .get(&id)
+                .is_some_and(|aliases| aliases.iter().any(|alias| uris.contains(alias)))
+    }
+
+    fn get(&self, id: usize) -> Option<T> {
+        self.entries.get(&id).cloned()
+    }
+
+    fn get_by_uri(&self, uri: &str) -> Option<T> {
+        self.uris.get(uri).and_then(|id| self.get(*id))
+    }
+
+    fn all(&self) -> Vec<T> {
+        self.entries.values().cloned().collect()
+    }
+
+    fn insert(&mut self, entity: &mut T) -> Change {
+        let id = self.next_id;
+        self.next_id += 1;
+        entity.set_id(id);
+        self.log_entry(id);
+        self.log_uri(entity.uri());
+        self.uris.insert(entity.uri().to_owned(), id);
+        self.entries.insert(id, entity.clone());
+        Change::Added
+    }
+
+    fn upsert(&mut self, entity: &mut T) -> Change {
+        match self.uris.get(entity.uri()).cloned() {
+            Some(id) => {
+                entity.set_id(id);
+                self.replace(id, entity.clone());
+                Change::Updated
+            }
+            None => self.insert(entity),
+        }
+    }
+
+    /// Stores the entity under the id of an existing entry
+    fn replace(&mut self, id: usize, entity: T) {
+        self.log_entry(id);
+        self.entries.insert(id, entity);
+    }
+
+    /// Applies `update` to all entries matching `affected`, returns the updated entries
+    fn update<P, U>(&mut self, affected: P, update: U) -> Vec<T>
+    where
+        P: Fn(&T) -> bool,
+        U: Fn(&mut T),
+    {
+        let ids: Vec<usize> = self
+            .entries
+            .iter()
+            .filter(|(_, entity)| affected(entity))
+            .map(|(id, _)| *id)
+            .collect();
+        let mut updated = vec![];
+        for id in ids {
+            self.log_entry(id);
+            if let Some(entity) = self.entries.get_mut(&id) {
+                update(entity);
+                updated.push(entity.clone());
+            }
+        }
+        updated
+    }
+}
+
+#[derive(Debug, Default)]
+struct Store {
+    tracks: Table<Track>,
+    albums: Table<Album>,
+    artists: Table<Artist>,
+    playlists: Table<Playlist>,
+    /// Annotations by uri, independent of the tables
+    annotations: HashMap<String, Annotation>,
+    /// Plays in recording order, independent of the tables
+    plays: Vec<Play>,
+    /// Candidates for duplicates of the stored tracks
+    canonical: CanonicalIndex,
+    /// Changes which haven't been sent to the observers yet
+    events: Vec<LibraryEvent>,
+    /// Set while the changes can be undone, see `MemoryLibrary::begin`
+    undo: Option<StoreUndo>,
+}
+
+/// Annotations and plays before the first change since `Store::begin`, see `Undo`
+#[derive(Debug)]
+struct StoreUndo {
+    annotations: HashMap<String, Option<Annotation>>,
+    plays: usize,
+}
+
+type Write<T> = fn(&mut Table<T>, &mut T) -> Change;
+
+impl Store {
+    fn restore(snapshot: Snapshot) -> Store {
+        let tracks = Table::restore(snapshot.tracks);
+        let mut albums = Table::restore(snapshot.albums);
+        for (id, aliases) in snapshot.album_aliases {
+            for alias in aliases {
+                albums.alias(alias, id);
+            }
+        }
+        Store {
+            canonical: CanonicalIndex::new(&tracks.entries),
+            tracks,
+            albums,
+            artists: Table::restore(snapshot.artists),
+            playlists: Table::restore(snapshot.playlists),
+            annotations: snapshot.annotations,
+            plays: snapshot.plays,
+            ..Store::default()
+        }
+    }
+
+    fn begin(&mut self) {
+        self.tracks.begin();
+        self.albums.begin();
+        self.artists.begin();
+        self.playlists.begin();
+        self.undo = Some(StoreUndo {
+            annotations: HashMap::new(),
+            plays: self.plays.len(),
+        });
+    }
+
+    /// Keeps all changes since `begin` and returns their events
+    fn commit(&mut self) -> Vec<LibraryEvent> {
+        self.tracks.commit();
+        self.albums.commit();
+        self.artists.commit();
+        self.playlists.commit();
+        self.undo = None;
+        mem::take(&mut self.events)
+    }
+
+    /// Undoes all changes since `begin` and drops their events
+    fn rollback(&mut self) {
+        for (id, undone) in self.tracks.rollback() {
+            if let Some(track) = undone {
+                self.canonical.remove(id, &track);
+            }
+            if let Some(track) = self.tracks.entries.get(&id) {
+                self.canonical.insert(id, track);
+            }
+        }
+        self.albums.rollback();
+        self.artists.rollback();
+        self.playlists.rollback();
+        if let Some(undo) = self.undo.take() {
+            self.plays.truncate(undo.plays);
+            for (uri, annotation) in undo.annotations {
+                match annotation {
+                    Some(annotation) => self.annotations.insert(uri, annotation),
+                    None => self.annotations.remove(&uri),
+                };
+            }
+        }
+        self.events.clear();
+    }
+
+    fn link_artist(&mut self, artist: &mut Option<Artist>, artist_id: &mut Option<usize>) {
+        if let Some(artist) = artist {
+            self.sync_artist(artist);
+            *artist_id = artist.id;
+        }
+    }
+
+    fn link_track(&mut self, track: &mut Track) {
+        self.link_artist(&mut track.artist, &mut track.artist_id);
+        if let Some(album) = &mut track.album {
+            self.sync_album(album);
+            track.album_id = album.id;
+        }
+    }
+
+    fn link_playlist(&mut self, playlist: &mut Playlist) {
+        for track in &mut playlist.tracks {
+            self.sync_track(track);
+        }
+    }
+
+    fn write_track(&mut self, track: &mut Track, write: Write<Track>) {
+        self.link_track(track);
+        let previous = self.tracks.get_by_uri(&track.uri);
+        let change = write(&mut self.tracks, track);
+        if let Some(previous) = previous.filter(|previous| previous.id == track.id) {
+            self.canonical.remove(previous.id.unwrap(), &previous);
+        }
+        self.canonical.insert(track.id.unwrap(), track);
+        self.events.push(track.event(change));
+    }
+
+    /// Stores the album without its tracks and syncs the tracks separately
+    fn write_album(&mut self, album: &mut Album, write: Write<Album>) {
+        self.link_artist(&mut album.artist, &mut album.artist_id);
+        let mut tracks = mem::take(&mut album.tracks);
+        let change = write(&mut self.albums, album);
+        self.events.push(album.event(change));
+        for track in &mut tracks {
+            track.album_id = album.id;
+            self.sync_track(track);
+        }
+        album.tracks = tracks;
+    }
+
+    /// Stores the artist without albums and top tracks and syncs those separately
+    fn write_artist(&mut self, artist: &mut Artist, write: Write<Artist>) {
+        let mut albums = mem::take(&mut artist.albums);
+        let mut top_tracks = mem::take(&mut artist.top_tracks);
+        let change = write(&mut self.artists, artist);
+        self.events.push(artist.event(change));
+        for album in &mut albums {
+            if !album.compilation {
+                album.artist_id = album.artist_id.or(artist.id);
+            }
+            self.sync_album(album);
+        }
+        for track in &mut top_tracks {
+            track.artist_id = track.artist_id.or(artist.id);
+            self.sync_track(track);
+        }
+        artist.albums = albums;
+        artist.top_tracks = top_tracks;
+    }
+
+    fn write_playlist(&mut self, playlist: &mut Playlist, write: Write<Playlist>) {
+        self.link_playlist(playlist);
+        let change = write(&mut self.playlists, playlist);
+        self.events.push(playlist.event(change));
+    }
+
+    fn add_track(&mut self, track: &mut Track) {
+        self.write_track(track, Table::insert);
+    }
+
+    fn add_album(&mut self, album: &mut Album) {
+        self.write_album(album, Table::insert);
+    }
+
+    fn add_artist(&mut self, artist: &mut Artist) {
+        self.write_artist(artist, Table::insert);
+    }
+
+    fn add_playlist(&mut self, playlist: &mut Playlist) {
+        self.write_playlist(playlist, Table::insert);
+    }
+
+    fn sync_track(&mut self, track: &mut Track) {
+        self.write_track(track, Table::upsert);
+    }
+
+    fn sync_album(&mut self, album: &mut Album) {
+        match self.compilation_id(album) {
+            Some(id) => self.write_compilation_part(album, id),
+            None => self.write_album(album, Table::upsert),
+        }
+    }
+
+    /// Id of the stored compilation the album is another part of, see `Album::is_same_compilation`
+    fn compilation_id(&self, album: &Album) -> Option<usize> {
+        if let Some(id) = self.albums.uris.get(&album.uri) {
+            let stored = self.albums.entries.get(id);
+            return Some(*id).filter(|_| stored.is_some_and(|stored| stored.uri != album.uri));
+        }
+        if !album.compilation {
+            return None;
+        }
+        self.albums
+            .entries
+            .iter()
+            .find(|(_, stored)| stored.is_same_compilation(album))
+            .map(|(id, _)| *id)
+    }
+
+    /// Links the part of a compilation to the stored compilation
+    ///
+    /// The uri of the part becomes an alias of the stored album, which is kept as it is.
+    fn write_compilation_part(&mut self, album: &mut Album, id: usize) {
+        self.albums.alias(album.uri.clone(), id);
+        album.id = Some(id);
+        self.link_artist(&mut album.artist, &mut album.artist_id);
+        let mut tracks = mem::take(&mut album.tracks);
+        for track in &mut tracks {
+            track.album_id = album.id;
+            self.sync_track(track);
+        }
+        album.tracks = tracks;
+    }
+
+    fn sync_artist(&mut self, artist: &mut Artist) {
+        self.write_artist(artist, Table::upsert);
+    }
+
+    fn sync_playlist(&mut self, playlist: &mut Playlist) {
+        self.write_playlist(playlist, Table::upsert);
+    }
+
+    /// Applies `update` to all stored tracks matching `affected`, including the copies in playlists
+    fn update_tracks<P, U>(&mut self, affected: P, update: U)
+    where
+        P: Fn(&Track) -> bool,
+        U: Fn(&mut Track),
+    {
+        for track in self.tracks.update(&affected, &update) {
+            self.events.push(track.event(Change::Updated));
+        }
+        let playlists = self.playlists.update(
+            |playlist| playlist.tracks.iter().any(&affected),
+            |playlist| {
+                for track in playlist.tracks.iter_mut().filter(|track| affected(track)) {
+                    update(track);
+                }
+            },
+        );
+        for playlist in playlists {
+            self.events.push(playlist.event(Change::Updated));
+        }
+    }
+
+    fn remove_track(&mut self, id: usize) -> bool {
+        let track = match self.tracks.remove(id) {
+            Some(track) => track,
+            None => return false,
+        };
+        self.canonical.remove(id, &track);
+        self.events.push(track.event(Change::Removed));
+        let playlists = self.playlists.update(
+            |playlist| playlist.tracks.iter().any(|track| track.id == Some(id)),
+            |playlist| playlist.tracks.retain(|track| track.id != Some(id)),
+        );
+        for playlist in playlists {
+            self.events.push(playlist.event(Change::Updated));
+        }
+        true
+    }
+
+    fn remove_album(&mut self, id: usize) -> bool {
+        let album = match self.albums.remove(id) {
+            Some(album) => album,
+            None => return false,
+        };
+        self.events.push(album.event(Change::Removed));
+        self.update_tracks(
+            |track| track.album_id == Some(id),
+            |track| {
+                track.album_id = None;
+                track.album = None;
+            },
+        );
+        true
+    }
+
+    fn remove_artist(&mut self, id: usize) -> bool {
+        let artist = match self.artists.remove(id) {
+            Some(artist) => artist,
+            None => return false,
+        };
+        self.events.push(artist.event(Change::Removed));
+        let unlink = |artist_id: &mut Option<usize>, artist: &mut Option<Artist>| {
+            if *artist_id == Some(id) {
+                *artist_id = None;
+                *artist = None;
+            }
+        };
+        self.update_tracks(
+            |track| {
+                track.artist_id == Some(id)
+                    || track
+                        .album
+                        .as_ref()
+                        .is_some_and(|album| album.artist_id == Some(id))
+            },
+            |track| {
+                if let Some(album) = &mut track.album {
+                    unlink(&mut album.artist_id, &mut album.artist);
+                }
+                unlink(&mut track.artist_id, &mut track.artist);
+            },
+        );
+        let albums = self.albums.update(
+            |album| album.artist_id == Some(id),
+            |album| unlink(&mut album.artist_id, &mut album.artist),
+        );
+        for album in albums {
+            self.events.push(album.event(Change::Updated));
+        }
+        true
+    }
+
+    fn remove_playlist(&mut self, id: usize) -> bool {
+        match self.playlists.remove(id) {
+            Some(playlist) => {
+                self.events.push(playlist.event(Change::Removed));
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn set_annotation(&mut self, uri: &str, annotation: Annotation) {
+        if let Some(undo) = &mut self.undo {
+            if !undo.annotations.contains_key(uri) {
+                let previous = self.annotations.get(uri).cloned();
+                undo.annotations.insert(uri.to_owned(), previous);
+            }
+        }
+        if annotation.is_empty() {
+            self.annotations.remove(uri);
+        } else {
+            self.annotations.insert(uri.to_owned(), annotation);
+        }
+        self.events.push(LibraryEvent::AnnotationUpdated {
+            uri: uri.to_owned(),
+            annotation,
+        });
+    }
+
+    fn statistics(&self, top_artists: usize) -> LibraryStatistics {
+        let mut statistics = LibraryStatistics {
+            tracks: self.tracks.entries.len(),
+            albums: self.albums.entries.len(),
+            artists: self.artists.entries.len(),
+            playlists: self.playlists.entries.len(),
+            ..LibraryStatistics::default()
+        };
+        let mut artist_tracks: HashMap<usize, usize> = HashMap::new();
+        for track in self.tracks.entries.values() {
+            statistics.provider_mut(track.provider).tracks += 1;
+            match track.duration {
+                Some(duration) => statistics.total_duration += duration,
+                None => statistics.tracks_without_duration += 1,
+            }
+            let album_artwork = track
+                .album_id
+                .and_then(|id| self.albums.entries.get(&id))
+                .is_some_and(|album| album.image_url.is_some());
+            if track.image_url.is_none() && !album_artwork {
+                statistics.tracks_without_artwork += 1;
+            }
+            if let Some(artist_id) = track.artist_id {
+                *artist_tracks.entry(artist_id).or_default() += 1;
+            }
+        }
+        for album in self.albums.entries.values() {
+            statistics.provider_mut(album.provider).albums += 1;
+        }
+        for playlist in self.playlists.entries.values() {
+            statistics.provider_mut(playlist.provider).playlists += 1;
+        }
+        let mut artist_ids: Vec<(usize, usize)> = artist_tracks.into_iter().collect();
+        artist_ids.sort_by_key(|&(id, tracks)| (Reverse(tracks), id));
+        statistics.top_artists = artist_ids
+            .into_iter()
+            .filter_map(|(id, tracks)| {
+                let artist = self.artists.entries.get(&id)?.clone();
+                Some(ArtistStatistics { artist, tracks })
+            })
+            .take(top_artists)
+            .collect();
+        statistics
+    }
+
+    fn is_favourite(&self, uri: &str) -> bool {
+        self.annotations
+            .get(uri)
+            .is_some_and(|annotation| annotation.favourite)
+    }
+
+    fn create_playlist(&mut self, title: String) -> Playlist {
+        let mut playlist = Playlist {
+            id: None,
+            title,
+            tracks: vec![],
+            provider: Provider::Internal,
+            uri: self.internal_playlist_uri(),
+        };
+        self.add_playlist(&mut playlist);
+        playlist
+    }
+
+    /// First `internal:playlist:<n>` from the next id on which isn't taken, e.g. by an imported playlist
+    fn internal_playlist_uri(&self) -> String {
+        (self.playlists.next_id..)
+            .map(|n| format!("internal:playlist:{}", n))
+            .find(|uri| !self.playlists.uris.contains_key(uri))
+            .unwrap()
+    }
+
+    fn edit_playlist(&mut self, id: usize, edit: &PlaylistEdit) -> Result<Playlist, PlaylistError> {
+        let mut playlist = self.playlists.get(id).ok_or(PlaylistError::NotFound(id))?;
+        let mut edit = edit.clone();
+        playlist.clone().apply(&edit)?;
+        if let PlaylistEdit::AddTracks { tracks, .. } = &mut edit {
+            for track in tracks.iter_mut() {
+                self.sync_track(track);
+            }
+        }
+        playlist.apply(&edit)?;
+        self.playlists.replace(id, playlist.clone());
+        self.events.push(playlist.event(Change::Updated));
+        Ok(playlist)
+    }
+
+    fn prune(&mut self, provider: Provider, synced: &SyncedUris) -> PruneResult {
+        let tracks: Vec<(usize, Option<usize>)> = self
+            .tracks
+            .entries
+            .iter()
+            .filter(|(_, track)| track.provider == provider && !synced.tracks.contains(&track.uri))
+            .map(|(id, track)| (*id, track.artist_id))
+            .collect();
+        let albums: Vec<(usize, Option<usize>)> = self
+            .albums
+            .entries
+            .iter()
+            .filter(|(id, album)| {
+                album.provider == provider && !self.albums.has_uri(**id, &synced.albums)
+            })
+            .map(|(id, album)| (*id, album.artist_id))
+            .collect();
+        let playlists: Vec<usize> = self
+            .playlists
+            .entries
+            .iter()
+            .filter(|(_, playlist)| {
+                playlist.provider == provider && !synced.playlists.contains(&playlist.uri)
+            }).map(|(id, _)| *id)
+            .collect();
+        let candidates: HashSet<usize> = tracks
+            .iter()
+            .chain(albums.iter())
+            .filter_map(|(_, artist_id)| *artist_id)
+            .collect();
+
+        let mut result = PruneResult {
+            tracks: tracks.len(),
+            albums: albums.len(),
+            playlists: playlists.len(),
+            ..PruneResult::default()
+        };
+        tracks.into_iter().for_each(|(id, _)| {
+            self.remove_track(id);
+        });
+        albums.into_iter().for_each(|(id, _)| {
+            self.remove_album(id);
+        });
+        playlists.into_iter().for_each(|id| {
+            self.remove_playlist(id);
+        });
+
+        let referenced: HashSet<usize> = self
+            .tracks
+            .entries
+            .values()
+            .map(|track| track.artist_id)
+            .chain(self.albums.entries.values().map(|album| album.artist_id))
+            .flatten()
+            .collect();
+        for id in candidates {
+            let orphaned = !referenced.contains(&id)
+                && self
+                    .artists
+                    .entries
+                    .get(&id)
+                    .is_some_and(|artist| !synced.artists.contains(&artist.uri));
+            if orphaned && self.remove_artist(id) {
+                result.artists += 1;
+            }
+        }
+        result
+    }
+}
+
+impl Library for MemoryLibrary {
+    fn get_track(&self, id: usize) -> Result<Option<Track>, Error> {
+        Ok(self.store.read().unwrap().tracks.get(id))
+    }
+
+    fn get_tracks(&self) -> Result<Vec<Track>, Error> {
+        Ok(self.store.read().unwrap().tracks.all())
+    }
+
+    fn get_track_by_uri(&self, uri: &str) -> Result<Option<Track>, Error> {
+        Ok(self.store.read().unwrap().tracks.get_by_uri(uri))
+    }
+
+    fn get_album(&self, id: usize) -> Result<Option<Album>, Error> {
+        Ok(self.store.read().unwrap().albums.get(id))
+    }
+
+    fn get_albums(&self) -> Result<Vec<Album>, Error> {
+        Ok(self.store.read().unwrap().albums.all())
+    }
+
+    fn get_album_by_uri(&self, uri: &str) -> Result<Option<Album>, Error> {
+        Ok(self.store.read().unwrap().albums.get_by_uri(uri))
+    }
+
+    fn get_artist(&self, id: usize) -> Result<Option<Artist>, Error> {
+        Ok(self.store.read().unwrap().artists.get(id))
+    }
+
+    fn get_artists(&self) -> Result<Vec<Artist>, Error> {
+        Ok(self.store.read().unwrap().artists.all())
+    }
+
+    fn get_artist_by_uri(&self, uri: &str) -> Result<Option<Artist>, Error> {
+        Ok(self.store.read().unwrap().artists.get_by_uri(uri))
+    }
+
+    fn get_playlist(&self, id: usize) -> Result<Option<Playlist>, Error> {
+        Ok(self.store.read().unwrap().playlists.get(id))
+    }
+
+    fn get_playlists(&self) -> Result<Vec<Playlist>, Error> {
+        Ok(self.store.read().unwrap().playlists.all())
+    }
+
+    fn get_playlist_by_uri(&self, uri: &str) -> Result<Option<Playlist>, Error> {
+        Ok(self.store.read().unwrap().playlists.get_by_uri(uri))
+    }
+
+    fn query_tracks(&self, query: &Query) -> Result<Page<Track>, Error> {
+        Ok(query.apply(self.store.read().unwrap().tracks.entries.values()))
+    }
+
+    fn query_albums(&self, query: &Query) -> Result<Page<Album>, Error> {
+        Ok(query.apply(self.store.read().unwrap().albums.entries.values()))
+    }
+
+    fn query_artists(&self, query: &Query) -> Result<Page<Artist>, Error> {
+        Ok(query.apply(self.store.read().unwrap().artists.entries.values()))
+    }
+
+    fn query_playlists(&self, query: &Query) -> Result<Page<Playlist>, Error> {
+        Ok(query.apply(self.store.read().unwrap().playlists.entries.values()))
+    }
+
+    fn get_album_tracks(&self, album_id: usize) -> Result<Vec<Track>, Error> {
+        let query = Query {
+            sort: Some(SortKey::Position),
+            filters: vec![Filter::AlbumId(album_id)],
+            ..Query::default()
+        };
+        self.query_tracks(&query).map(|page| page.items)
+    }
+
+    fn get_artist_albums(&self, artist_id: usize) -> Result<Vec<Album>, Error> {
+        let query = Query {
+            sort: Some(SortKey::Title),
+            filters: vec![Filter::ArtistId(artist_id)],
+            ..Query::default()
+        };
+        self.query_albums(&query).map(|page| page.items)
+    }
+
+    fn get_artist_compilations(&self, artist_id: usize) -> Result<Vec<Album>, Error> {
+        let store = self.store.read().unwrap();
+        let album_ids: HashSet<usize> = store
+            .tracks
+            .entries
+            .values()
+            .filter(|track| track.artist_id == Some(artist_id))
+            .filter_map(|track| track.album_id)
+            .collect();
+        let mut albums: Vec<Album> = store
+            .albums
+            .entries
+            .iter()
+            .filter(|(id, album)| album.compilation && album_ids.contains(id))
+            .filter(|(_, album)| album.artist_id != Some(artist_id))
+            .map(|(_, album)| album.clone())
+            .collect();
+        albums.sort_by(|a, b| a.title.cmp(&b.title));
+        Ok(albums)
+    }
+
+    fn get_artist_top_tracks(&self, artist_id: usize, limit: usize) -> Result<Vec<Track>, Error> {
+        let query = Query {
+            filters: vec![Filter::ArtistId(artist_id)],
+            ..Query::default()
+        };
+        let mut tracks = self.query_tracks(&query)?.items;
+        tracks.sort_by(Track::cmp_popularity);
+        tracks.truncate(limit);
+        Ok(tracks)
+    }
+
+    fn add_track(&self, track: &mut Track) -> Result<(), Error> {
+        self.write(|store| store.add_track(track));
+        Ok(())
+    }
+
+    fn add_album(&self, album: &mut Album) -> Result<(), Error> {
+        self.write(|store| store.add_album(album));
+        Ok(())
+    }
+
+    fn add_artist(&self, artist: &mut Artist) -> Result<(), Error> {
+        self.write(|store| store.add_artist(artist));
+        Ok(())
+    }
+
+    fn add_playlist(&self, playlist: &mut Playlist) -> Result<(), Error> {
+        self.write(|store| store.add_playlist(playlist));
+        Ok(())
+    }
+
+    fn add_tracks(&self, tracks: &mut Vec<Track>) -> Result<(), Error> {
+        self.write(|store| tracks.iter_mut().for_each(|track| store.add_track(track)));
+        Ok(())
+    }
+
+    fn add_albums(&self, albums: &mut Vec<Album>) -> Result<(), Error> {
+        self.write(|store| albums.iter_mut().for_each(|album| store.add_album(album)));
+        Ok(())
+    }
+
+    fn add_artists(&self, artists: &mut Vec<Artist>) -> Result<(), Error> {
+        self.write(|store| artists.iter_mut().for_each(|artist| store.add_artist(artist)));
+        Ok(())
+    }
+
+    fn add_playlists(&self, playlists: &mut Vec<Playlist>) -> Result<(), Error> {
+        self.write(|store| playlists.iter_mut().for_each(|playlist| store.add_playlist(playlist)));
+        Ok(())
+    }
+
+    fn sync_track(&self, track: &mut Track) -> Result<(), Error> {
+        self.write(|store| store.sync_track(track));
+        Ok(())
+    }
+
+    fn sync_album(&self, album: &mut Album) -> Result<(), Error> {
+        self.write(|store| store.sync_album(album));
+        Ok(())
+    }
+
+    fn sync_artist(&self, artist: &mut Artist) -> Result<(), Error> {
+        self.write(|store| store.sync_artist(artist));
+        Ok(())
+    }
+
+    fn sync_playlist(&self, playlist: &mut Playlist) -> Result<(), Error> {
+        self.write(|store| store.sync_playlist(playlist));
+        Ok(())
+    }
+
+    fn sync_tracks(&self, tracks: &mut Vec<Track>) -> Result<(), Error> {
+        self.write(|store| tracks.iter_mut().for_each(|track| store.sync_track(track)));
+        Ok(())
+    }
+
+    fn sync_albums(&self, albums: &mut Vec<Album>) -> Result<(), Error> {
+        self.write(|store| albums.iter_mut().for_each(|album| store.sync_album(album)));
+        Ok(())
+    }
+
+    fn sync_artists(&self, artists: &mut Vec<Artist>) -> Result<(), Error> {
+        self.write(|store| artists.iter_mut().for_each(|artist| store.sync_artist(artist)));
+        Ok(())
+    }
+
+    fn sync_playlists(&self, playlists: &mut Vec<Playlist>) -> Result<(), Error> {
+        self.write(|store| playlists.iter_mut().for_each(|playlist| store.sync_playlist(playlist)));
+        Ok(())
+    }
+
+    fn remove_track(&self, id: usize) -> Result<(), Error> {
+        self.write(|store| store.remove_track(id));
+        Ok(())
+    }
+
+    fn remove_album(&self, id: usize) -> Result<(), Error> {
+        self.write(|store| store.remove_album(id));
+        Ok(())
+    }
+
+    fn remove_artist(&self, id: usize) -> Result<(), Error> {
+        self.write(|store| store.remove_artist(id));
+        Ok(())
+    }
+
+    fn remove_playlist(&self, id: usize) -> Result<(), Error> {
+        self.write(|store| store.remove_playlist(id));
+        Ok(())
+    }
+
+    fn prune(&self, provider: Provider, synced: &SyncedUris) -> Result<PruneResult, Error> {
+        Ok(self.write(|store| store.prune(provider, synced)))
+    }
+
+    fn create_playlist(&self, title: String) -> Result<Playlist, Error> {
+        Ok(self.write(|store| store.create_playlist(title)))
+    }
+
+    fn edit_playlist(&self, id: usize, edit: &PlaylistEdit) -> Result<Playlist, Error> {
+        Ok(self.write(|store| store.edit_playlist(id, edit))?)
+    }
+
+    fn get_annotation(&self, uri: &str) -> Result<Annotation, Error> {
+        let store = self.store.read().unwrap();
+        Ok(store.annotations.get(uri).cloned().unwrap_or_default())
+    }
+
+    fn get_annotations(&self) -> Result<HashMap<String, Annotation>, Error> {
+        Ok(self.store.read().unwrap().annotations.clone())
+    }
+
+    fn set_annotation(&self, uri: &str, annotation: Annotation) -> Result<(), Error> {
+        self.write(|store| store.set_annotation(uri, annotation));
+        Ok(())
+    }
+
+    fn get_plays(&self) -> Result<Vec<Play>, Error> {
+        Ok(self.store.read().unwrap().plays.clone())
+    }
+
+    fn add_play(&self, play: Play) -> Result<(), Error> {
+        self.store.write().unwrap().plays.push(play);
+        Ok(())
+    }
+
+    fn get_favourite_tracks(&self) -> Result<Vec<Track>, Error> {
+        let store = self.store.read().unwrap();
+        Ok(store
+            .tracks
+            .entries
+            .values()
+            .filter(|track| store.is_favourite(&track.uri))
+            .cloned()
+            .collect())
+    }
+
+    fn get_favourite_albums(&self) -> Result<Vec<Album>, Error> {
+        let store = self.store.read().unwrap();
+        Ok(store
+            .albums
+            .entries
+            .values()
+            .filter(|album| store.is_favourite(&album.uri))
+            .cloned()
+            .collect())
+    }
+
+    fn get_favourite_artists(&self) -> Result<Vec<Artist>, Error> {
+        let store = self.store.read().unwrap();
+        Ok(store
+            .artists
+            .entries
+            .values()
+            .filter(|artist| store.is_favourite(&artist.uri))
+            .cloned()
+            .collect())
+    }
+
+    fn get_genres(&self) -> Result<Vec<Genre>, Error> {
+        let store = self.store.read().unwrap();
+        let mut seen = HashSet::new();
+        let tagged = store
+            .albums
+            .entries
+            .values()
+            .flat_map(|album| &album.genres)
+            .chain(store.tracks.entries.values().flat_map(|track| &track.genres));
+        let mut genres: Vec<Genre> = tagged.filter(|genre| seen.insert(*genre)).cloned().collect();
+        genres.sort_by(|a, b| a.name().cmp(b.name()));
+        Ok(genres)
+    }
+
+    fn get_genre_tracks(&self, genre: &Genre) -> Result<Vec<Track>, Error> {
+        let query = Query {
+            sort: Some(SortKey::Title),
+            filters: vec![Filter::Genre(genre.clone())],
+            ..Query::default()
+        };
+        self.query_tracks(&query).map(|page| page.items)
+    }
+
+    fn get_genre_albums(&self, genre: &Genre) -> Result<Vec<Album>, Error> {
+        let store = self.store.read().unwrap();
+        let album_ids: HashSet<usize> = store
+            .tracks
+            .entries
+            .values()
+            .filter(|track| track.genres.contains(genre))
+            .filter_map(|track| track.album_id)
+            .collect();
+        let mut albums: Vec<Album> = store
+            .albums
+            .entries
+            .iter()
+            .filter(|(id, album)| album.genres.contains(genre) || album_ids.contains(id))
+            .map(|(_, album)| album.clone())
+            .collect();
+        albums.sort_by(|a, b| a.title.cmp(&b.title));
+        Ok(albums)
+    }
+
+    fn get_canonical_tracks(&self) -> Result<Vec<CanonicalTrack>, Error> {
+        let store = self.store.read().unwrap();
+        let tracks = &store.tracks.entries;
+        let mut canonical: Vec<CanonicalTrack> = store
+            .canonical
+            .components(tracks)
+            .iter()
+            .flat_map(|component| {
+                CanonicalTrack::group(component.iter().filter_map(|id| tracks.get(id)))
+            })
+            .collect();
+        // groups of later components can start before groups split off an earlier component
+        canonical.sort_by_key(|canonical| canonical.track.id);
+        Ok(canonical)
+    }
+
+    fn get_canonical_track(&self, uri: &str) -> Result<Option<CanonicalTrack>, Error> {
+        let store = self.store.read().unwrap();
+        let track = match store.tracks.get_by_uri(uri) {
+            Some(track) => track,
+            None => return Ok(None),
+        };
+        let candidates = store.canonical.candidates(&track, &store.tracks.entries);
+        let canonical = CanonicalTrack::group(
+            candidates
+                .iter()
+                .filter_map(|id| store.tracks.entries.get(id)),
+        )
+        .into_iter()
+        .find(|canonical| canonical.sources.iter().any(|source| source.uri == uri));
+        Ok(canonical)
+    }
+
+    fn statistics(&self, top_artists: usize) -> Result<LibraryStatistics, Error> {
+        Ok(self.store.read().unwrap().statistics(top_artists))
+    }
+
+    fn evaluate_smart_playlist(&self, playlist: &SmartPlaylist) -> Result<Vec<Track>, Error> {
+        Ok(playlist.evaluate(self.store.read().unwrap().tracks.entries.values()))
+    }
+
+    fn batch(&self, f: &mut dyn FnMut(&dyn Library) -> Result<(), Error>) -> Result<(), Error> {
+        f(self)
+    }
+
+    fn observe(&self) -> Subscription {
+        Observers::subscribe(&self.observers)
+    }
+
+    fn search(&self, query: String) -> Result<SearchResults, Error> {
+        let query = SearchQuery::parse(&query);
+        let store = self.store.read().unwrap();
+        Ok(SearchResults {
+            tracks: query.rank(store.tracks.entries.values()),
+            albums: query.rank(store.albums.entries.values()),
+            artists: query.rank(store.artists.entries.values()),
+            playlists: query.rank(store.playlists.entries.values()),
+        })
+    }
+}
@@ -1,5 +1,118 @@
+use serde::{Deserializer, Serializer};
 use serde_derive::{Serialize, Deserialize};
-#[derive(Clone, Debug, Serialize, Deserialize)]
+use std::fmt;
+
+/// Well-known metadata keys with an escape hatch for provider specific ones
+///
+/// Keys are serialized as strings, e.g. `track_number` or `musicbrainz_artist_id`.
+/// Parsing ignores case and separators, so "Genre", "trackNumber" and "track-number"
+/// map to the well-known keys. Construct custom keys through `MetaKey::from` so well-known
+/// names never end up in `Custom`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MetaKey {
+    /// Parsed for entries written before genres moved out of the meta data
+    #[deprecated(note = "genres are stored in `Track::genres` and `Album::genres`, match them with `Rule::Genre`")]
+    Genre,
+    /// Release year
+    Year,
+    /// Position of a track on its disc, starting at 1
+    TrackNumber,
+    /// Number of the disc a track is on, starting at 1
+    DiscNumber,
+    /// Bitrate in kbit/s
+    Bitrate,
+    MusicBrainzTrackId,
+    MusicBrainzRecordingId,
+    MusicBrainzAlbumId,
+    MusicBrainzArtistId,
+    /// International Standard Recording Code identifying a recording across providers
+    Isrc,
+    Explicit,
+    /// Provider specific popularity, higher values are more popular
+    Popularity,
+    Custom(String),
+}
+
+#[allow(deprecated)]
+const WELL_KNOWN: &[MetaKey] = &[
+    MetaKey::Genre,
+    MetaKey::Year,
+    MetaKey::TrackNumber,
+    MetaKey::DiscNumber,
+    MetaKey::Bitrate,
+    MetaKey::MusicBrainzTrackId,
+    MetaKey::MusicBrainzRecordingId,
+    MetaKey::MusicBrainzAlbumId,
+    MetaKey::MusicBrainzArtistId,
+    MetaKey::Isrc,
+    MetaKey::Explicit,
+    MetaKey::Popularity,
+];
+
+impl MetaKey {
+    #[allow(deprecated)]
+    pub fn as_str(&self) -> &str {
+        match self {
+            MetaKey::Genre => "genre",
+            MetaKey::Year => "year",
+            MetaKey::TrackNumber => "track_number",
+            MetaKey::DiscNumber => "disc_number",
+            MetaKey::Bitrate => "bitrate",
+            MetaKey::MusicBrainzTrackId => "musicbrainz_track_id",
+            MetaKey::MusicBrainzRecordingId => "musicbrainz_recording_id",
+            MetaKey::MusicBrainzAlbumId => "musicbrainz_album_id",
+            MetaKey::MusicBrainzArtistId => "musicbrainz_artist_id",
+            MetaKey::Isrc => "isrc",
+            MetaKey::Explicit => "explicit",
+            MetaKey::Popularity => "popularity",
+            MetaKey::Custom(key) => key,
+        }
+    }
+}
+
+fn simplify(key: &str) -> String {
+    key.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+impl<'a> From<&'a str> for MetaKey {
+    fn from(key: &'a str) -> MetaKey {
+        let simplified = simplify(key);
+        WELL_KNOWN
+            .iter()
+            .find(|known| simplify(known.as_str()) == simplified)
+            .cloned()
+            .unwrap_or_else(|| MetaKey::Custom(key.to_owned()))
+    }
+}
+
+impl From<String> for MetaKey {
+    fn from(key: String) -> MetaKey {
+        MetaKey::from(key.as_str())
+    }
+}
+
+impl fmt::Display for MetaKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl serde::Serialize for MetaKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for MetaKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<MetaKey, D::Error> {
+        <String as serde::Deserialize>::deserialize(deserializer).map(MetaKey::from)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum MetaValue {
     Bool(bool),
     String(String),
@@ -7,6 +120,35 @@ pub enum MetaValue {
     Int(u64),
 }
 
+impl MetaValue {
+    /// Returns numeric values and strings containing a number as integer
+    pub fn as_int(&self) -> Option<u64> {
+        match self {
+            MetaValue::Int(value) => Some(*value),
+            MetaValue::Float(value) if *value >= 0.0 => Some(*value as u64),
+            MetaValue::String(value) => value.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            MetaValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns numeric values and strings containing a number as float
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            MetaValue::Int(value) => Some(*value as f64),
+            MetaValue::Float(value) => Some(*value),
+            MetaValue::String(value) => value.trim().parse().ok(),
+            _ => None,
+        }
+    }
+}
+
 impl From<bool> for MetaValue {
     fn from(value: bool) -> Self {
         MetaValue::Bool(value)
@@ -0,0 +1,203 @@
+//! Upgrades of persisted library data to the current model
+//!
+//! Persisted data carries the schema version it was written with, data written before
+//! versioning was introduced has version 0. Every change to the serialized form of the
+//! models registers a `Migration` in `MIGRATIONS` and bumps `SCHEMA_VERSION`.
+//! Migrations work on the JSON representation of a single entity, embedded entities like the
+//! album of a track are migrated separately, so each migration only handles one flat entity.
+use failure::{Error, Fail};
+use serde_json::{Map, Value};
+use crate::library::{normalize, EntityKind, Genre, MetaKey};
+
+/// Schema version of the data written by this version
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// Single upgrade step from version `from` to `from + 1`
+pub struct Migration {
+    pub from: u32,
+    pub description: &'static str,
+    pub migrate: fn(EntityKind, &mut Map<String, Value>) -> Result<(), Error>,
+}
+
+/// All migrations ordered by version, there is exactly one for every version before `SCHEMA_VERSION`
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 0,
+        description: "Store well-known meta keys by their canonical name",
+        migrate: canonical_meta_keys,
+    },
+    Migration {
+        from: 1,
+        description: "Move the genre meta entry of tracks and albums into genres",
+        migrate: genres_from_meta,
+    },
+    Migration {
+        from: 2,
+        description: "Flag albums as compilation by their compilation tag or album artist",
+        migrate: compilation_flag,
+    },
+];
+
+#[derive(Debug, Fail)]
+pub enum MigrationError {
+    /// The data was written by a newer version
+    #[fail(display = "Unsupported schema version {}", _0)]
+    UnsupportedVersion(u32),
+    /// The data doesn't have the expected structure
+    #[fail(display = "Invalid data: {}", _0)]
+    InvalidData(String),
+}
+
+/// Fails for versions newer than `SCHEMA_VERSION`
+pub fn check_version(version: u32) -> Result<(), MigrationError> {
+    if version > SCHEMA_VERSION {
+        Err(MigrationError::UnsupportedVersion(version))
+    } else {
+        Ok(())
+    }
+}
+
+/// Upgrades an entity including its embedded entities from `version` to `SCHEMA_VERSION`
+pub fn migrate_entity(kind: EntityKind, value: &mut Value, version: u32) -> Result<(), Error> {
+    check_version(version)?;
+    for migration in &MIGRATIONS[version as usize..] {
+        apply(migration, kind, value)?;
+    }
+    Ok(())
+}
+
+/// Upgrades a snapshot of a `FileLibrary` and updates its version marker
+///
+/// Snapshots without a `version` field have version 0.
+pub fn migrate_snapshot(snapshot: &mut Value) -> Result<(), Error> {
+    let object = snapshot
+        .as_object_mut()
+        .ok_or_else(|| MigrationError::InvalidData("snapshot is no object".to_owned()))?;
+    let version = match object.get("version") {
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| MigrationError::InvalidData("version is no number".to_owned()))?
+            as u32,
+        None => 0,
+    };
+    check_version(version)?;
+    let tables = [
+        ("tracks", EntityKind::Track),
+        ("albums", EntityKind::Album),
+        ("artists", EntityKind::Artist),
+        ("playlists", EntityKind::Playlist),
+    ];
+    for (table, kind) in &tables {
+        if let Some(entities) = object.get_mut(*table).and_then(Value::as_array_mut) {
+            for entity in entities {
+                migrate_entity(*kind, entity, version)?;
+            }
+        }
+    }
+    if let Some(plays) = object.get_mut("plays").and_then(Value::as_array_mut) {
+        for play in plays {
+            if let Some(track) = play.get_mut("track") {
+                migrate_entity(EntityKind::Track, track, version)?;
+            }
+        }
+    }
+    object.insert("version".to_owned(), Value::from(SCHEMA_VERSION));
+    Ok(())
+}
+
+/// Fields holding embedded entities, per kind of the embedding entity
+fn embedded(kind: EntityKind) -> &'static [(&'static str, EntityKind)] {
+    match kind {
+        EntityKind::Track => &[("artist", EntityKind::Artist), ("album", EntityKind::Album)],
+        EntityKind::Album => &[("artist", EntityKind::Artist), ("tracks", EntityKind::Track)],
+        EntityKind::Artist => &[("albums", EntityKind::Album), ("top_tracks", EntityKind::Track)],
+        EntityKind::Playlist => &[("tracks", EntityKind::Track)],
+    }
+}
+
+fn apply(migration: &Migration, kind: EntityKind, value: &mut Value) -> Result<(), Error> {
+    let object = match value {
+        Value::Object(object) => object,
+        Value::Null => return Ok(()),
+        _ => {
+            let reason = format!("{:?} is no object", kind);
+            return Err(MigrationError::InvalidData(reason).into());
+        }
+    };
+    (migration.migrate)(kind, object)?;
+    for (field, embedded_kind) in embedded(kind) {
+        match object.get_mut(*field) {
+            Some(Value::Array(entities)) => {
+                for entity in entities {
+                    apply(migration, *embedded_kind, entity)?;
+                }
+            }
+            Some(entity) => apply(migration, *embedded_kind, entity)?,
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// Version 0 stored meta keys as passed by the provider, e.g. "trackNumber" or "GENRE"
+fn canonical_meta_keys(_kind: EntityKind, entity: &mut Map<String, Value>) -> Result<(), Error> {
+    if let Some(Value::Object(meta)) = entity.get_mut("meta") {
+        let entries = std::mem::take(meta);
+        for (key, value) in entries {
+            meta.insert(MetaKey::from(key).to_string(), value);
+        }
+    }
+    Ok(())
+}
+
+/// Version 1 kept the genre of tracks and albums as meta string, e.g. "Rock; Pop"
+fn genres_from_meta(kind: EntityKind, entity: &mut Map<String, Value>) -> Result<(), Error> {
+    if kind != EntityKind::Track && kind != EntityKind::Album {
+        return Ok(());
+    }
+    let mut genres = vec![];
+    if let Some(Value::Object(meta)) = entity.get_mut("meta") {
+        let key = "genre";
+        if let Some(list) = meta.get(key).and_then(|value| value.get("String")) {
+            genres = Genre::parse_list(list.as_str().unwrap_or_default());
+            meta.remove(key);
+        }
+    }
+    entity.insert("genres".to_owned(), serde_json::to_value(genres)?);
+    Ok(())
+}
+
+/// Version 2 had no compilation flag, providers passed the compilation tag as meta entry
+fn compilation_flag(kind: EntityKind, entity: &mut Map<String, Value>) -> Result<(), Error> {
+    if kind != EntityKind::Album {
+        return Ok(());
+    }
+    let mut compilation = entity
+        .get("artist")
+        .and_then(|artist| artist.get("name"))
+        .and_then(Value::as_str)
+        .is_some_and(|name| normalize(name).split_whitespace().eq(["various", "artists"]));
+    if let Some(Value::Object(meta)) = entity.get_mut("meta") {
+        let keys: Vec<String> = meta
+            .keys()
+            .filter(|key| key.eq_ignore_ascii_case("compilation"))
+            .cloned()
+            .collect();
+        for key in keys {
+            let tagged = match meta.remove(&key) {
+                Some(Value::Object(value)) => value.values().any(|value| match value {
+                    Value::Bool(flag) => *flag,
+                    Value::Number(number) => number.as_f64() != Some(0.0),
+                    Value::String(text) => ["1", "true", "yes"]
+                        .iter()
+                        .any(|flag| text.trim().eq_ignore_ascii_case(flag)),
+                    _ => false,
+                }),
+                _ => false,
+            };
+            compilation = compilation || tagged;
+        }
+    }
+    entity.insert("compilation".to_owned(), Value::Bool(compilation));
+    Ok(())
+}
@@ -1,13 +1,37 @@
 mod album;
+mod annotation;
 mod artist;
+mod canonical;
+#[cfg(any(test, feature = "conformance"))]
+pub mod conformance;
+mod event;
+mod file;
+mod genre;
+pub mod integrity;
 mod library;
+mod memory;
 mod playlist;
+mod query;
+mod search;
+mod smart_playlist;
+mod statistics;
 mod track;
 mod meta;
+pub mod migration;
 
 pub use self::album::Album;
+pub use self::annotation::Annotation;
 pub use self::artist::Artist;
-pub use self::library::{Library, SearchResults, SharedLibrary};
-pub use self::playlist::Playlist;
+pub use self::canonical::CanonicalTrack;
+pub use self::event::{LibraryEvent, Subscription};
+pub use self::file::FileLibrary;
+pub use self::genre::Genre;
+pub use self::library::{EntityKind, Library, PruneResult, SearchResults, SharedLibrary};
+pub use self::memory::MemoryLibrary;
+pub use self::playlist::{Playlist, PlaylistEdit, PlaylistError};
+pub use self::query::{Filter, Page, Query, Queryable, SortKey, SortOrder};
+pub use self::search::{normalize, SearchField, SearchQuery, Searchable};
+pub use self::smart_playlist::{MetaRule, Range, Rule, SmartPlaylist, TextRule};
+pub use self::statistics::{ArtistStatistics, LibraryStatistics, ProviderStatistics};
 pub use self::track::Track;
-pub use self::meta::MetaValue;
\ No newline at end of file
+pub use self::meta::{MetaKey, MetaValue};
\ No newline at end of file
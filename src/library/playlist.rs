@@ -1,9 +1,10 @@
-use serde_derive::Serialize;
+use serde_derive::{Deserialize, Serialize};
+use failure::Fail;
 use crate::library::Track;
 use crate::provider::Provider;
 use std::cmp::Ordering;
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Playlist {
     pub id: Option<usize>,
     pub title: String,
@@ -12,6 +13,76 @@ pub struct Playlist {
     pub uri: String,
 }
 
+/// Modification of a playlist, see `Library::edit_playlist`
+///
+/// Positions are indices into `Playlist::tracks` before the edit is applied.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PlaylistEdit {
+    Rename(String),
+    /// Inserts the tracks at `position` or appends them when it is None
+    AddTracks {
+        tracks: Vec<Track>,
+        position: Option<usize>,
+    },
+    RemoveTracks(Vec<usize>),
+    MoveTrack {
+        from: usize,
+        to: usize,
+    },
+}
+
+#[derive(Debug, Fail)]
+pub enum PlaylistError {
+    #[fail(display = "Playlist {} not found", _0)]
+    NotFound(usize),
+    #[fail(display = "Invalid playlist position {}", _0)]
+    InvalidPosition(usize),
+    /// The provider of the playlist doesn't support editing playlists
+    #[fail(display = "Playlists of {:?} are read-only", _0)]
+    ReadOnly(Provider),
+}
+
+impl Playlist {
+    /// Applies the edit to this playlist, leaving it untouched when a position is out of range
+    ///
+    /// Added tracks are inserted as given, libraries have to sync them first.
+    pub fn apply(&mut self, edit: &PlaylistEdit) -> Result<(), PlaylistError> {
+        let len = self.tracks.len();
+        match edit {
+            PlaylistEdit::Rename(title) => self.title = title.clone(),
+            PlaylistEdit::AddTracks { tracks, position } => {
+                let position = position.unwrap_or(len);
+                if position > len {
+                    return Err(PlaylistError::InvalidPosition(position));
+                }
+                self.tracks.splice(position..position, tracks.iter().cloned());
+            }
+            PlaylistEdit::RemoveTracks(positions) => {
+                if let Some(position) = positions.iter().find(|position| **position >= len) {
+                    return Err(PlaylistError::InvalidPosition(*position));
+                }
+                let mut index = 0;
+                self.tracks.retain(|_| {
+                    index += 1;
+                    !positions.contains(&(index - 1))
+                });
+            }
+            PlaylistEdit::MoveTrack { from, to } => {
+                if *from >= len {
+                    return Err(PlaylistError::InvalidPosition(*from));
+                }
+                if *to >= len {
+                    return Err(PlaylistError::InvalidPosition(*to));
+                }
+                let track = self.tracks.remove(*from);
+                self.tracks.insert(*to, track);
+            }
+        }
+        Ok(())
+    }
+}
+
 impl PartialEq for Playlist {
     fn eq(&self, other: &Playlist) -> bool {
         self.uri == other.uri
@@ -0,0 +1,196 @@
+use serde_derive::{Deserialize, Serialize};
+use crate::library::{Album, Artist, Genre, MetaKey, MetaValue, Playlist, Track};
+use crate::provider::Provider;
+use std::cmp::Ordering;
+
+/// Paginated, filtered and sorted selection of library entities
+///
+/// All filters have to match. Filters which don't apply to an entity never match,
+/// e.g. `Filter::Provider` when querying artists.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Query {
+    pub offset: usize,
+    pub limit: Option<usize>,
+    pub sort: Option<SortKey>,
+    pub order: SortOrder,
+    pub filters: Vec<Filter>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    Id,
+    /// Title of tracks, albums and playlists, name of artists
+    Title,
+    /// Name of the embedded artist
+    Artist,
+    /// Title of the embedded album
+    Album,
+    Duration,
+    /// Disc and track number, see `Track::cmp_position`
+    Position,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Filter {
+    Provider(Provider),
+    ArtistId(usize),
+    AlbumId(usize),
+    Meta(MetaKey, MetaValue),
+    /// Tracks and albums tagged with the genre
+    Genre(Genre),
+}
+
+/// One page of a query result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Number of entities matching the filters, regardless of offset and limit
+    pub total: usize,
+}
+
+/// Entities which can be selected by a `Query`
+pub trait Queryable: Clone {
+    fn matches(&self, filter: &Filter) -> bool;
+    fn compare(&self, other: &Self, key: SortKey) -> Ordering;
+}
+
+impl Query {
+    pub fn new() -> Query {
+        Query::default()
+    }
+
+    /// Selects the page of `items` described by this query
+    ///
+    /// Backends without native query support can pass all their entities here.
+    pub fn apply<'a, T, I>(&self, items: I) -> Page<T>
+    where
+        T: Queryable + 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        let mut items: Vec<&T> = items
+            .into_iter()
+            .filter(|item| self.filters.iter().all(|filter| item.matches(filter)))
+            .collect();
+        if let Some(key) = self.sort {
+            items.sort_by(|a, b| match self.order {
+                SortOrder::Ascending => a.compare(b, key),
+                SortOrder::Descending => b.compare(a, key),
+            });
+        }
+        let total = items.len();
+        let items = items
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect();
+        Page { items, total }
+    }
+}
+
+fn compare_text(a: &str, b: &str) -> Ordering {
+    a.to_lowercase().cmp(&b.to_lowercase())
+}
+
+fn compare_names<T, F>(a: &Option<T>, b: &Option<T>, name: F) -> Ordering
+where
+    F: Fn(&T) -> &str,
+{
+    match (a, b) {
+        (Some(a), Some(b)) => compare_text(name(a), name(b)),
+        (a, b) => a.is_some().cmp(&b.is_some()),
+    }
+}
+
+impl Queryable for Track {
+    fn matches(&self, filter: &Filter) -> bool {
+        match filter {
+            Filter::Provider(provider) => self.provider == *provider,
+            Filter::ArtistId(id) => self.artist_id == Some(*id),
+            Filter::AlbumId(id) => self.album_id == Some(*id),
+            Filter::Meta(key, value) => self.meta.get(key) == Some(value),
+            Filter::Genre(genre) => self.genres.contains(genre),
+        }
+    }
+
+    fn compare(&self, other: &Track, key: SortKey) -> Ordering {
+        match key {
+            SortKey::Id => self.id.cmp(&other.id),
+            SortKey::Title => compare_text(&self.title, &other.title),
+            SortKey::Artist => compare_names(&self.artist, &other.artist, |a| &a.name),
+            SortKey::Album => compare_names(&self.album, &other.album, |a| &a.title),
+            SortKey::Duration => self.duration.cmp(&other.duration),
+            SortKey::Position => self.cmp_position(other),
+        }
+    }
+}
+
+impl Queryable for Album {
+    fn matches(&self, filter: &Filter) -> bool {
+        match filter {
+            Filter::Provider(provider) => self.provider == *provider,
+            Filter::ArtistId(id) => self.artist_id == Some(*id),
+            Filter::AlbumId(id) => self.id == Some(*id),
+            Filter::Meta(key, value) => self.meta.get(key) == Some(value),
+            Filter::Genre(genre) => self.genres.contains(genre),
+        }
+    }
+
+    fn compare(&self, other: &Album, key: SortKey) -> Ordering {
+        match key {
+            SortKey::Id => self.id.cmp(&other.id),
+            SortKey::Title | SortKey::Album => compare_text(&self.title, &other.title),
+            SortKey::Artist => compare_names(&self.artist, &other.artist, |a| &a.name),
+            SortKey::Duration | SortKey::Position => Ordering::Equal,
+        }
+    }
+}
+
+impl Queryable for Artist {
+    fn matches(&self, filter: &Filter) -> bool {
+        match filter {
+            Filter::ArtistId(id) => self.id == Some(*id),
+            Filter::Meta(key, value) => self.meta.get(key) == Some(value),
+            Filter::Provider(_) | Filter::AlbumId(_) | Filter::Genre(_) => false,
+        }
+    }
+
+    fn compare(&self, other: &Artist, key: SortKey) -> Ordering {
+        match key {
+            SortKey::Id => self.id.cmp(&other.id),
+            SortKey::Title | SortKey::Artist => compare_text(&self.name, &other.name),
+            SortKey::Album | SortKey::Duration | SortKey::Position => Ordering::Equal,
+        }
+    }
+}
+
+impl Queryable for Playlist {
+    fn matches(&self, filter: &Filter) -> bool {
+        match filter {
+            Filter::Provider(provider) => self.provider == *provider,
+            Filter::ArtistId(_) | Filter::AlbumId(_) | Filter::Meta(_, _) | Filter::Genre(_) => {
+                false
+            }
+        }
+    }
+
+    fn compare(&self, other: &Playlist, key: SortKey) -> Ordering {
+        match key {
+            SortKey::Id => self.id.cmp(&other.id),
+            SortKey::Title => compare_text(&self.title, &other.title),
+            SortKey::Artist | SortKey::Album | SortKey::Duration | SortKey::Position => {
+                Ordering::Equal
+            }
+        }
+    }
+}
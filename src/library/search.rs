@@ -0,0 +1,263 @@
+use crate::library::{Album, Artist, Playlist, Track};
+use std::cmp::Ordering;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Fields a search term can be restricted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchField {
+    Title,
+    Artist,
+    Album,
+}
+
+impl SearchField {
+    fn weight(self) -> f64 {
+        match self {
+            SearchField::Title => 1.0,
+            SearchField::Artist => 0.8,
+            SearchField::Album => 0.6,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Term {
+    field: Option<SearchField>,
+    words: Vec<String>,
+}
+
+/// Parsed search query
+///
+/// A query consists of terms separated by whitespace. Terms can be restricted to a field
+/// with the prefixes `title:`, `artist:` and `album:`, quotes group multiple words into one term,
+/// e.g. `artist:"daft punk" around`.
+///
+/// Matching ignores case and diacritics and tolerates small typos, query words of up to
+/// three characters only match at the start of a word.
+/// Every term has to match, the score of an entity is the sum of the scores of all terms
+/// weighted by the field they matched in (title over artist over album).
+/// Fields equal to the whole query get a bonus.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchQuery {
+    terms: Vec<Term>,
+    text: String,
+}
+
+/// Entities which can be ranked by a `SearchQuery`
+pub trait Searchable {
+    fn search_fields(&self) -> Vec<(SearchField, &str)>;
+}
+
+/// Lowercases the text and strips diacritics
+pub fn normalize(text: &str) -> String {
+    text.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn words(text: &str) -> Vec<String> {
+    normalize(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if a == *b {
+                previous
+            } else {
+                1 + previous.min(row[j]).min(current)
+            };
+            previous = current;
+        }
+    }
+    row[b.len()]
+}
+
+/// Shorter query words only match at the start of a word, e.g. "he" doesn't match "the"
+const MIN_INFIX_LEN: usize = 4;
+
+/// Number of typos tolerated for a word of the given length
+fn tolerance(len: usize) -> usize {
+    match len {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Scores how well a single query word matches a single word of a field
+fn score_word(query: &str, word: &str) -> f64 {
+    if query == word {
+        1.0
+    } else if word.starts_with(query) {
+        0.8
+    } else if query.chars().count() >= MIN_INFIX_LEN && word.contains(query) {
+        0.6
+    } else {
+        let distance = levenshtein(query, word);
+        if distance <= tolerance(query.chars().count()) {
+            0.5 - 0.1 * distance as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Term {
+    /// Scores the term against the words of a field, every word of the term has to match
+    fn score(&self, field: &[String]) -> f64 {
+        let mut score = 0.0;
+        for query in &self.words {
+            let best = field
+                .iter()
+                .map(|word| score_word(query, word))
+                .fold(0.0, f64::max);
+            if best == 0.0 {
+                return 0.0;
+            }
+            score += best;
+        }
+        score / self.words.len() as f64
+    }
+}
+
+impl SearchQuery {
+    pub fn parse(query: &str) -> SearchQuery {
+        let mut terms = vec![];
+        let mut chars = query.chars().peekable();
+        loop {
+            while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                chars.next();
+            }
+            if chars.peek().is_none() {
+                break;
+            }
+            let mut token = String::new();
+            let mut quoted = false;
+            for c in chars.by_ref() {
+                match c {
+                    '"' => quoted = !quoted,
+                    c if c.is_whitespace() && !quoted => break,
+                    c => token.push(c),
+                }
+            }
+            let (field, text) = match token.find(':') {
+                Some(index) => match token[..index].to_lowercase().as_str() {
+                    "title" => (Some(SearchField::Title), &token[index + 1..]),
+                    "artist" => (Some(SearchField::Artist), &token[index + 1..]),
+                    "album" => (Some(SearchField::Album), &token[index + 1..]),
+                    _ => (None, token.as_str()),
+                },
+                None => (None, token.as_str()),
+            };
+            let words = words(text);
+            if !words.is_empty() {
+                terms.push(Term { field, words });
+            }
+        }
+        SearchQuery {
+            terms,
+            text: normalize(query.trim()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Returns the relevance of the entity or None when it doesn't match
+    pub fn score<T: Searchable>(&self, entity: &T) -> Option<f64> {
+        if self.is_empty() {
+            return None;
+        }
+        let fields: Vec<(SearchField, String, Vec<String>)> = entity
+            .search_fields()
+            .into_iter()
+            .map(|(field, value)| (field, normalize(value), words(value)))
+            .collect();
+        let mut score = 0.0;
+        for term in &self.terms {
+            let best = fields
+                .iter()
+                .filter(|(field, _, _)| term.field.map_or(true, |f| f == *field))
+                .map(|(field, _, words)| field.weight() * term.score(words))
+                .fold(0.0, f64::max);
+            if best == 0.0 {
+                return None;
+            }
+            score += best;
+        }
+        let exact = fields
+            .iter()
+            .filter(|(_, value, _)| *value == self.text)
+            .map(|(field, _, _)| field.weight())
+            .fold(0.0, f64::max);
+        Some(score + 0.5 * exact)
+    }
+
+    /// Returns all matching entities, most relevant first
+    pub fn rank<'a, T, I>(&self, entities: I) -> Vec<T>
+    where
+        T: Searchable + Clone + 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        let mut matches: Vec<(f64, &T)> = entities
+            .into_iter()
+            .filter_map(|entity| self.score(entity).map(|score| (score, entity)))
+            .collect();
+        matches.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+        matches
+            .into_iter()
+            .map(|(_, entity)| entity.clone())
+            .collect()
+    }
+}
+
+impl Searchable for Track {
+    fn search_fields(&self) -> Vec<(SearchField, &str)> {
+        let mut fields = vec![(SearchField::Title, self.title.as_str())];
+        if let Some(artist) = &self.artist {
+            fields.push((SearchField::Artist, &artist.name));
+        }
+        if let Some(album) = &self.album {
+            fields.push((SearchField::Album, &album.title));
+        }
+        fields
+    }
+}
+
+impl Searchable for Album {
+    fn search_fields(&self) -> Vec<(SearchField, &str)> {
+        let mut fields = vec![
+            (SearchField::Title, self.title.as_str()),
+            (SearchField::Album, self.title.as_str()),
+        ];
+        if let Some(artist) = &self.artist {
+            fields.push((SearchField::Artist, &artist.name));
+        }
+        fields
+    }
+}
+
+impl Searchable for Artist {
+    fn search_fields(&self) -> Vec<(SearchField, &str)> {
+        vec![(SearchField::Artist, &self.name)]
+    }
+}
+
+impl Searchable for Playlist {
+    fn search_fields(&self) -> Vec<(SearchField, &str)> {
+        vec![(SearchField::Title, &self.title)]
+    }
+}
@@ -0,0 +1,156 @@
+use serde_derive::{Deserialize, Serialize};
+use crate::library::{normalize, Genre, MetaKey, MetaValue, Query, SortKey, SortOrder, Track};
+use crate::provider::Provider;
+
+/// Playlist whose tracks are selected by rules instead of being stored
+///
+/// The tracks are selected when the playlist is evaluated, see `Library::evaluate_smart_playlist`,
+/// so the playlist stays up to date with every sync.
+/// All rules have to match, `Rule::Any` and `Rule::Not` allow other combinations.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmartPlaylist {
+    pub title: String,
+    pub rules: Vec<Rule>,
+    pub sort: Option<SortKey>,
+    #[serde(default)]
+    pub order: SortOrder,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Rule {
+    Title(TextRule),
+    /// Name of the embedded artist
+    Artist(TextRule),
+    /// Title of the embedded album
+    Album(TextRule),
+    Provider(Provider),
+    ArtistId(usize),
+    AlbumId(usize),
+    /// Duration in the unit of `Track::duration`, tracks without duration never match
+    Duration(Range),
+    Meta(MetaKey, MetaRule),
+    /// Matches tracks tagged with the genre, see `Track::genres`
+    Genre(Genre),
+    /// Matches when at least one of the rules matches
+    Any(Vec<Rule>),
+    Not(Box<Rule>),
+}
+
+/// Text comparison ignoring case and diacritics
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TextRule {
+    Is(String),
+    Contains(String),
+}
+
+/// Inclusive range, missing bounds are unbounded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Range {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MetaRule {
+    Exists,
+    /// Strings are compared like `TextRule::Is`, other values have to be equal
+    Is(MetaValue),
+    /// Numeric values and strings containing a number, see `MetaValue::as_float`
+    Range(Range),
+}
+
+impl TextRule {
+    fn matches(&self, text: &str) -> bool {
+        let text = normalize(text);
+        match self {
+            TextRule::Is(value) => text == normalize(value),
+            TextRule::Contains(value) => text.contains(&normalize(value)),
+        }
+    }
+}
+
+impl Range {
+    pub fn contains(&self, value: f64) -> bool {
+        self.min.map_or(true, |min| value >= min) && self.max.map_or(true, |max| value <= max)
+    }
+}
+
+impl MetaRule {
+    fn matches(&self, value: Option<&MetaValue>) -> bool {
+        match (self, value) {
+            (_, None) => false,
+            (MetaRule::Exists, Some(_)) => true,
+            (MetaRule::Is(MetaValue::String(expected)), Some(MetaValue::String(value))) => {
+                TextRule::Is(expected.clone()).matches(value)
+            }
+            (MetaRule::Is(expected), Some(value)) => expected == value,
+            (MetaRule::Range(range), Some(value)) => {
+                value.as_float().is_some_and(|value| range.contains(value))
+            }
+        }
+    }
+}
+
+impl Rule {
+    pub fn matches(&self, track: &Track) -> bool {
+        match self {
+            Rule::Title(rule) => rule.matches(&track.title),
+            Rule::Artist(rule) => track
+                .artist
+                .as_ref()
+                .is_some_and(|artist| rule.matches(&artist.name)),
+            Rule::Album(rule) => track
+                .album
+                .as_ref()
+                .is_some_and(|album| rule.matches(&album.title)),
+            Rule::Provider(provider) => track.provider == *provider,
+            Rule::ArtistId(id) => track.artist_id == Some(*id),
+            Rule::AlbumId(id) => track.album_id == Some(*id),
+            Rule::Duration(range) => track
+                .duration
+                .is_some_and(|duration| range.contains(duration as f64)),
+            Rule::Meta(key, rule) => rule.matches(track.meta.get(key)),
+            Rule::Genre(genre) => track.genres.contains(genre),
+            Rule::Any(rules) => rules.iter().any(|rule| rule.matches(track)),
+            Rule::Not(rule) => !rule.matches(track),
+        }
+    }
+}
+
+impl SmartPlaylist {
+    pub fn new(title: String, rules: Vec<Rule>) -> SmartPlaylist {
+        SmartPlaylist {
+            title,
+            rules,
+            sort: None,
+            order: SortOrder::default(),
+            limit: None,
+        }
+    }
+
+    pub fn matches(&self, track: &Track) -> bool {
+        self.rules.iter().all(|rule| rule.matches(track))
+    }
+
+    /// Selects the sorted and limited tracks of this playlist from `tracks`
+    ///
+    /// Backends without native support can pass all their tracks here.
+    pub fn evaluate<'a, I>(&self, tracks: I) -> Vec<Track>
+    where
+        I: IntoIterator<Item = &'a Track>,
+    {
+        let query = Query {
+            sort: self.sort,
+            order: self.order,
+            limit: self.limit,
+            ..Query::default()
+        };
+        query
+            .apply(tracks.into_iter().filter(|track| self.matches(track)))
+            .items
+    }
+}
@@ -0,0 +1,64 @@
+use serde_derive::{Deserialize, Serialize};
+use crate::library::Artist;
+use crate::provider::Provider;
+
+/// Aggregates over the whole library, see `Library::statistics`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LibraryStatistics {
+    pub tracks: usize,
+    pub albums: usize,
+    pub artists: usize,
+    pub playlists: usize,
+    /// Counts of the providers with at least one entity, in order of first appearance
+    pub providers: Vec<ProviderStatistics>,
+    /// Sum of all known track durations in seconds
+    pub total_duration: u64,
+    /// Tracks without an image of their own or of their album
+    pub tracks_without_artwork: usize,
+    pub tracks_without_duration: usize,
+    /// Artists with the most tracks, most tracks first
+    pub top_artists: Vec<ArtistStatistics>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderStatistics {
+    pub provider: Provider,
+    pub tracks: usize,
+    pub albums: usize,
+    pub playlists: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArtistStatistics {
+    pub artist: Artist,
+    pub tracks: usize,
+}
+
+impl LibraryStatistics {
+    /// Returns the counts of the provider, adding empty counts when it isn't listed yet
+    pub fn provider_mut(&mut self, provider: Provider) -> &mut ProviderStatistics {
+        let index = match self
+            .providers
+            .iter()
+            .position(|statistics| statistics.provider == provider)
+        {
+            Some(index) => index,
+            None => {
+                self.providers.push(ProviderStatistics {
+                    provider,
+                    tracks: 0,
+                    albums: 0,
+                    playlists: 0,
+                });
+                self.providers.len() - 1
+            }
+        };
+        &mut self.providers[index]
+    }
+
+    pub fn provider(&self, provider: Provider) -> Option<&ProviderStatistics> {
+        self.providers
+            .iter()
+            .find(|statistics| statistics.provider == provider)
+    }
+}
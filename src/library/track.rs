@@ -1,12 +1,15 @@
-use serde_derive::Serialize;
-use crate::library::{Album, Artist, MetaValue};
+use serde_derive::{Deserialize, Serialize};
+use crate::library::{normalize, Album, Artist, Genre, MetaKey, MetaValue};
 use crate::provider::Provider;
 use std::cmp::Ordering;
 use std::sync::Arc;
 use std::collections::HashMap;
 use crate::Rustic;
 
-#[derive(Clone, Debug, Serialize)]
+/// Maximum difference in seconds between the durations of duplicate tracks
+const DURATION_TOLERANCE: u64 = 3;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Track {
     pub id: Option<usize>,
     pub title: String,
@@ -18,7 +21,9 @@ pub struct Track {
     pub uri: String,
     pub image_url: Option<String>,
     pub duration: Option<u64>,
-    pub meta: HashMap<&'static str, MetaValue>
+    #[serde(default)]
+    pub genres: Vec<Genre>,
+    pub meta: HashMap<MetaKey, MetaValue>
 }
 
 impl Track {
@@ -27,6 +32,94 @@ impl Track {
             .clone()
             .and_then(|uri| app.cache.fetch_coverart(uri).ok())
     }
+
+    /// Artist of the embedded album, which differs from the track artist on compilations
+    pub fn album_artist(&self) -> Option<&Artist> {
+        self.album.as_ref().and_then(|album| album.artist.as_ref())
+    }
+
+    pub fn disc_number(&self) -> Option<u64> {
+        self.meta.get(&MetaKey::DiscNumber).and_then(MetaValue::as_int)
+    }
+
+    pub fn track_number(&self) -> Option<u64> {
+        self.meta.get(&MetaKey::TrackNumber).and_then(MetaValue::as_int)
+    }
+
+    pub fn popularity(&self) -> Option<f64> {
+        self.meta.get(&MetaKey::Popularity).and_then(MetaValue::as_float)
+    }
+
+    pub fn isrc(&self) -> Option<&str> {
+        self.meta.get(&MetaKey::Isrc).and_then(MetaValue::as_str)
+    }
+
+    pub fn recording_id(&self) -> Option<&str> {
+        self.meta
+            .get(&MetaKey::MusicBrainzRecordingId)
+            .and_then(MetaValue::as_str)
+    }
+
+    /// Whether both tracks are the same recording, e.g. from different providers
+    ///
+    /// Identifiers are compared when both tracks have the same kind: the ISRC first,
+    /// the MusicBrainz recording id second. Otherwise title and artist have to match
+    /// after normalization, album and duration have to match when both tracks have them.
+    pub fn is_duplicate(&self, other: &Track) -> bool {
+        if self.uri == other.uri {
+            return true;
+        }
+        if let Some(same) = self.matches_identifier(other) {
+            return same;
+        }
+        let artist = |track: &Track| track.artist.as_ref().map(|artist| normalize(&artist.name));
+        let album = |track: &Track| track.album.as_ref().map(|album| normalize(&album.title));
+        let same = |a: Option<String>, b: Option<String>| match (a, b) {
+            (Some(a), Some(b)) => a == b,
+            _ => true,
+        };
+        normalize(&self.title) == normalize(&other.title)
+            && artist(self).is_some()
+            && artist(self) == artist(other)
+            && same(album(self), album(other))
+            && match (self.duration, other.duration) {
+                (Some(a), Some(b)) => a.abs_diff(b) <= DURATION_TOLERANCE,
+                _ => true,
+            }
+    }
+
+    /// Compares the ISRC or MusicBrainz recording id, None when the tracks don't share a kind
+    pub fn matches_identifier(&self, other: &Track) -> Option<bool> {
+        let same = |a: &str, b: &str| a.trim().eq_ignore_ascii_case(b.trim());
+        if let (Some(a), Some(b)) = (self.isrc(), other.isrc()) {
+            return Some(same(a, b));
+        }
+        if let (Some(a), Some(b)) = (self.recording_id(), other.recording_id()) {
+            return Some(same(a, b));
+        }
+        None
+    }
+
+    /// Orders tracks by their position on an album, disc number first and track number second
+    ///
+    /// Tracks without a disc number are on the first disc, tracks without a track number come last.
+    pub fn cmp_position(&self, other: &Track) -> Ordering {
+        let position = |track: &Track| {
+            (
+                track.disc_number().unwrap_or(1),
+                track.track_number().unwrap_or(u64::MAX),
+            )
+        };
+        position(self).cmp(&position(other))
+    }
+
+    /// Orders tracks by popularity, most popular first and tracks without popularity last
+    pub fn cmp_popularity(&self, other: &Track) -> Ordering {
+        match (self.popularity(), other.popularity()) {
+            (Some(a), Some(b)) => b.partial_cmp(&a).unwrap_or(Ordering::Equal),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        }
+    }
 }
 
 impl PartialEq for Track {
@@ -0,0 +1,64 @@
+use crate::playlist_file::{PlaylistEntry, PlaylistFile};
+use std::fmt::Write;
+
+const HEADER: &str = "#EXTM3U";
+const EXTINF: &str = "#EXTINF:";
+const PLAYLIST: &str = "#PLAYLIST:";
+
+/// Parses extended and plain M3U, unknown directives and comments are skipped
+pub fn parse(content: &str) -> PlaylistFile {
+    let mut file = PlaylistFile::default();
+    let mut info: Option<PlaylistEntry> = None;
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line == HEADER {
+            continue;
+        }
+        if let Some(title) = line.strip_prefix(PLAYLIST) {
+            file.title = Some(title.trim().to_owned());
+        } else if let Some(extinf) = line.strip_prefix(EXTINF) {
+            info = Some(parse_extinf(extinf));
+        } else if !line.starts_with('#') {
+            let entry = PlaylistEntry {
+                location: line.to_owned(),
+                ..info.take().unwrap_or_default()
+            };
+            file.entries.push(entry);
+        }
+    }
+    file
+}
+
+/// Parses `<duration>[ attributes],<artist> - <title>`
+fn parse_extinf(extinf: &str) -> PlaylistEntry {
+    let (duration, title) = match extinf.find(',') {
+        Some(index) => (&extinf[..index], extinf[index + 1..].trim()),
+        None => (extinf, ""),
+    };
+    let duration = duration
+        .split_whitespace()
+        .next()
+        .and_then(|duration| duration.parse::<i64>().ok())
+        .filter(|duration| *duration >= 0)
+        .map(|duration| duration as u64);
+    let mut entry = PlaylistEntry {
+        duration,
+        ..PlaylistEntry::default()
+    };
+    entry.set_display_title(title);
+    entry
+}
+
+pub fn write(file: &PlaylistFile) -> String {
+    let mut content = String::new();
+    writeln!(content, "{}", HEADER).unwrap();
+    if let Some(title) = &file.title {
+        writeln!(content, "{}{}", PLAYLIST, title).unwrap();
+    }
+    for entry in &file.entries {
+        let duration = entry.duration.map_or(-1, |duration| duration as i64);
+        let title = entry.display_title().unwrap_or_default();
+        writeln!(content, "{}{},{}", EXTINF, duration, title).unwrap();
+        writeln!(content, "{}", entry.location).unwrap();
+    }
+    content
+}
@@ -0,0 +1,137 @@
+//! Import and export of playlist files
+//!
+//! Durations in playlist files are converted from and to seconds, the unit of `Track::duration`.
+mod m3u;
+mod pls;
+mod xspf;
+
+use failure::Error;
+use crate::library::{Playlist, Track};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistFormat {
+    /// Extended M3U encoded as UTF-8
+    M3u8,
+    Pls,
+    Xspf,
+}
+
+/// Single entry of a playlist file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlaylistEntry {
+    /// Uri, url or path of the entry
+    pub location: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration: Option<u64>,
+}
+
+/// Content of a parsed playlist file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlaylistFile {
+    pub title: Option<String>,
+    pub entries: Vec<PlaylistEntry>,
+}
+
+/// Result of `Rustic::import_playlist`
+#[derive(Debug, Clone)]
+pub struct ImportResult {
+    /// The stored playlist containing all resolved entries
+    pub playlist: Playlist,
+    /// Entries which couldn't be resolved to a track, in file order
+    pub unmatched: Vec<PlaylistEntry>,
+}
+
+impl PlaylistFormat {
+    /// Detects the format from a file extension, e.g. `m3u8` or `xspf`
+    pub fn from_extension(extension: &str) -> Option<PlaylistFormat> {
+        match extension.to_lowercase().as_str() {
+            "m3u" | "m3u8" => Some(PlaylistFormat::M3u8),
+            "pls" => Some(PlaylistFormat::Pls),
+            "xspf" => Some(PlaylistFormat::Xspf),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            PlaylistFormat::M3u8 => "m3u8",
+            PlaylistFormat::Pls => "pls",
+            PlaylistFormat::Xspf => "xspf",
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            PlaylistFormat::M3u8 => "application/vnd.apple.mpegurl",
+            PlaylistFormat::Pls => "audio/x-scpls",
+            PlaylistFormat::Xspf => "application/xspf+xml",
+        }
+    }
+}
+
+impl<'a> From<&'a Track> for PlaylistEntry {
+    fn from(track: &'a Track) -> PlaylistEntry {
+        PlaylistEntry {
+            location: track.uri.clone(),
+            title: Some(track.title.clone()),
+            artist: track.artist.as_ref().map(|artist| artist.name.clone()),
+            album: track.album.as_ref().map(|album| album.title.clone()),
+            duration: track.duration,
+        }
+    }
+}
+
+impl PlaylistEntry {
+    /// Title as shown by most players, `Artist - Title`
+    fn display_title(&self) -> Option<String> {
+        match (&self.artist, &self.title) {
+            (Some(artist), Some(title)) => Some(format!("{} - {}", artist, title)),
+            (None, Some(title)) => Some(title.clone()),
+            (Some(artist), None) => Some(artist.clone()),
+            (None, None) => None,
+        }
+    }
+
+    /// Splits a title written by `display_title` into artist and title
+    fn set_display_title(&mut self, title: &str) {
+        let (artist, title) = match title.find(" - ") {
+            Some(index) => (Some(&title[..index]), &title[index + 3..]),
+            None => (None, title),
+        };
+        self.artist = artist.map(str::to_owned);
+        self.title = Some(title.to_owned()).filter(|title| !title.is_empty());
+    }
+}
+
+impl PlaylistFile {
+    pub fn new(title: Option<String>, tracks: &[Track]) -> PlaylistFile {
+        PlaylistFile {
+            title,
+            entries: tracks.iter().map(PlaylistEntry::from).collect(),
+        }
+    }
+
+    pub fn parse(content: &str, format: PlaylistFormat) -> Result<PlaylistFile, Error> {
+        match format {
+            PlaylistFormat::M3u8 => Ok(m3u::parse(content)),
+            PlaylistFormat::Pls => pls::parse(content),
+            PlaylistFormat::Xspf => xspf::parse(content),
+        }
+    }
+
+    pub fn write(&self, format: PlaylistFormat) -> String {
+        match format {
+            PlaylistFormat::M3u8 => m3u::write(self),
+            PlaylistFormat::Pls => pls::write(self),
+            PlaylistFormat::Xspf => xspf::write(self),
+        }
+    }
+}
+
+impl<'a> From<&'a Playlist> for PlaylistFile {
+    fn from(playlist: &'a Playlist) -> PlaylistFile {
+        PlaylistFile::new(Some(playlist.title.clone()), &playlist.tracks)
+    }
+}
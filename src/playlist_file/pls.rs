@@ -0,0 +1,72 @@
+use failure::{format_err, Error};
+use crate::playlist_file::{PlaylistEntry, PlaylistFile};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Parses a PLS file, entries are ordered by their number
+pub fn parse(content: &str) -> Result<PlaylistFile, Error> {
+    let mut lines = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with(';'));
+    match lines.next() {
+        Some(header) if header.eq_ignore_ascii_case("[playlist]") => {}
+        _ => return Err(format_err!("missing [playlist] section")),
+    }
+    let mut entries: BTreeMap<usize, PlaylistEntry> = BTreeMap::new();
+    let mut title = None;
+    for line in lines {
+        let index = line
+            .find('=')
+            .ok_or_else(|| format_err!("invalid line {}", line))?;
+        let (key, value) = (line[..index].trim(), line[index + 1..].trim());
+        let field = key.trim_end_matches(|c: char| c.is_ascii_digit());
+        let number = match key[field.len()..].parse::<usize>() {
+            Ok(number) => number,
+            Err(_) => {
+                if field.eq_ignore_ascii_case("x-title") {
+                    title = Some(value.to_owned());
+                }
+                continue;
+            }
+        };
+        let entry = entries.entry(number).or_default();
+        match field.to_lowercase().as_str() {
+            "file" => entry.location = value.to_owned(),
+            "title" => entry.set_display_title(value),
+            "length" => {
+                entry.duration = value
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|duration| *duration >= 0)
+                    .map(|duration| duration as u64)
+            }
+            _ => {}
+        }
+    }
+    let entries = entries
+        .into_values()
+        .filter(|entry| !entry.location.is_empty())
+        .collect();
+    Ok(PlaylistFile { title, entries })
+}
+
+pub fn write(file: &PlaylistFile) -> String {
+    let mut content = String::new();
+    writeln!(content, "[playlist]").unwrap();
+    if let Some(title) = &file.title {
+        writeln!(content, "X-Title={}", title).unwrap();
+    }
+    for (index, entry) in file.entries.iter().enumerate() {
+        let number = index + 1;
+        writeln!(content, "File{}={}", number, entry.location).unwrap();
+        if let Some(title) = entry.display_title() {
+            writeln!(content, "Title{}={}", number, title).unwrap();
+        }
+        let duration = entry.duration.map_or(-1, |duration| duration as i64);
+        writeln!(content, "Length{}={}", number, duration).unwrap();
+    }
+    writeln!(content, "NumberOfEntries={}", file.entries.len()).unwrap();
+    writeln!(content, "Version=2").unwrap();
+    content
+}
@@ -0,0 +1,85 @@
+use failure::Error;
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use crate::playlist_file::{PlaylistEntry, PlaylistFile};
+use std::fmt::Write;
+
+/// Parses the title and the track list of an XSPF playlist, other elements are skipped
+pub fn parse(content: &str) -> Result<PlaylistFile, Error> {
+    let mut reader = Reader::from_str(content);
+    reader.config_mut().trim_text(true);
+    let mut file = PlaylistFile::default();
+    let mut path: Vec<Vec<u8>> = vec![];
+    let mut entry: Option<PlaylistEntry> = None;
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) => {
+                let name = element.local_name().as_ref().to_vec();
+                if name == b"track" && path.last().is_some_and(|parent| parent == b"trackList") {
+                    entry = Some(PlaylistEntry::default());
+                }
+                path.push(name);
+            }
+            Event::End(_) => {
+                let name = path.pop();
+                if name.is_some_and(|name| name == b"track") {
+                    file.entries
+                        .extend(entry.take().filter(|entry| !entry.location.is_empty()));
+                }
+            }
+            Event::Text(text) => {
+                let text = text.unescape()?.into_owned();
+                let name = path.last().map(Vec::as_slice);
+                match (&mut entry, name) {
+                    (Some(entry), Some(b"location")) => entry.location = text,
+                    (Some(entry), Some(b"title")) => entry.title = Some(text),
+                    (Some(entry), Some(b"creator")) => entry.artist = Some(text),
+                    (Some(entry), Some(b"album")) => entry.album = Some(text),
+                    (Some(entry), Some(b"duration")) => {
+                        entry.duration = text.parse::<u64>().ok().map(|duration| duration / 1000)
+                    }
+                    (None, Some(b"title")) if path.len() == 2 => file.title = Some(text),
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(file)
+}
+
+fn element(content: &mut String, name: &str, value: &str) {
+    writeln!(content, "      <{}>{}</{}>", name, escape(value), name).unwrap();
+}
+
+pub fn write(file: &PlaylistFile) -> String {
+    let mut content = String::new();
+    writeln!(content, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(content, r#"<playlist version="1" xmlns="http://xspf.org/ns/0/">"#).unwrap();
+    if let Some(title) = &file.title {
+        writeln!(content, "  <title>{}</title>", escape(title.as_str())).unwrap();
+    }
+    writeln!(content, "  <trackList>").unwrap();
+    for entry in &file.entries {
+        writeln!(content, "    <track>").unwrap();
+        element(&mut content, "location", &entry.location);
+        if let Some(title) = &entry.title {
+            element(&mut content, "title", title);
+        }
+        if let Some(artist) = &entry.artist {
+            element(&mut content, "creator", artist);
+        }
+        if let Some(album) = &entry.album {
+            element(&mut content, "album", album);
+        }
+        if let Some(duration) = entry.duration {
+            element(&mut content, "duration", &(duration * 1000).to_string());
+        }
+        writeln!(content, "    </track>").unwrap();
+    }
+    writeln!(content, "  </trackList>").unwrap();
+    writeln!(content, "</playlist>").unwrap();
+    content
+}
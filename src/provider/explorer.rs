@@ -1,9 +1,14 @@
-use super::{NavigationError, ProviderFolder, SharedProviders};
+use super::{NavigationError, ProviderFolder, ProviderItem, SharedProviders};
 use failure::Error;
+use crate::library::{Genre, SharedLibrary};
+
+/// Root folder listing the genres of the library, see `Explorer::with_library`
+pub const GENRES_FOLDER: &str = "Genres";
 
 pub struct Explorer {
     pub path: Vec<String>,
     providers: SharedProviders,
+    library: Option<SharedLibrary>,
 }
 
 impl Explorer {
@@ -11,6 +16,16 @@ impl Explorer {
         Explorer {
             path: vec![],
             providers,
+            library: None,
+        }
+    }
+
+    /// Adds a `GENRES_FOLDER` to the root, browsing the albums and tracks of the library by genre
+    pub fn with_library(providers: SharedProviders, library: SharedLibrary) -> Explorer {
+        Explorer {
+            path: vec![],
+            providers,
+            library: Some(library),
         }
     }
 
@@ -51,18 +66,53 @@ impl Explorer {
     }
 
     fn get_root(&self) -> ProviderFolder {
-        let folders = self
+        let mut folders: Vec<String> = self
             .providers
             .iter()
             .map(|provider| provider.read().unwrap().title().to_owned())
             .collect();
+        if self.library.is_some() {
+            folders.push(GENRES_FOLDER.to_owned());
+        }
         ProviderFolder {
             folders,
             items: vec![],
         }
     }
 
+    /// Lists the genres at `GENRES_FOLDER`, and the albums and tracks of a genre below it
+    fn get_genre_folder(&self, library: &SharedLibrary) -> Result<ProviderFolder, Error> {
+        match &self.path[1..] {
+            [] => {
+                let genres = library.get_genres()?;
+                let folders = genres.iter().map(|genre| genre.name().to_owned()).collect();
+                let items = genres.into_iter().map(ProviderItem::from).collect();
+                Ok(ProviderFolder::new(folders, items))
+            }
+            [name] => {
+                let genre = Genre::new(name);
+                let albums = library.get_genre_albums(&genre)?;
+                let tracks = library.get_genre_tracks(&genre)?;
+                if albums.is_empty() && tracks.is_empty() {
+                    return Err(Error::from(NavigationError::PathNotFound));
+                }
+                let items = albums
+                    .into_iter()
+                    .map(ProviderItem::from)
+                    .chain(tracks.into_iter().map(ProviderItem::from))
+                    .collect();
+                Ok(ProviderFolder::new(vec![], items))
+            }
+            _ => Err(Error::from(NavigationError::PathNotFound)),
+        }
+    }
+
     pub fn items(&self) -> Result<ProviderFolder, Error> {
+        if self.path.first().map(String::as_str) == Some(GENRES_FOLDER) {
+            if let Some(library) = &self.library {
+                return self.get_genre_folder(library);
+            }
+        }
         let root = self.get_root();
         match self.path.len() {
             0 => Ok(root),
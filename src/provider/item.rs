@@ -1,18 +1,19 @@
-use serde_derive::Serialize;
-use crate::library::{Album, Artist, Playlist, Track};
+use serde_derive::{Deserialize, Serialize};
+use crate::library::{Album, Artist, Genre, Playlist, Track};
 
-#[derive(Debug, Clone, Serialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ProviderItem {
     pub label: String,
     pub data: ProviderItemType,
 }
 
-#[derive(Debug, Clone, Serialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum ProviderItemType {
     Track(Track),
     Album(Album),
     Artist(Artist),
     Playlist(Playlist),
+    Genre(Genre),
 }
 
 impl ProviderItem {
@@ -47,6 +48,10 @@ impl ProviderItem {
             false
         }
     }
+
+    pub fn is_genre(&self) -> bool {
+        matches!(self.data, ProviderItemType::Genre(_))
+    }
 }
 
 impl From<ProviderItem> for Track {
@@ -85,6 +90,15 @@ impl From<ProviderItem> for Playlist {
     }
 }
 
+impl From<ProviderItem> for Genre {
+    fn from(item: ProviderItem) -> Genre {
+        match item.data {
+            ProviderItemType::Genre(genre) => genre,
+            _ => panic!("ProviderItem is not of type Genre"),
+        }
+    }
+}
+
 impl From<Track> for ProviderItem {
     fn from(track: Track) -> ProviderItem {
         ProviderItem {
@@ -120,3 +134,12 @@ impl From<Playlist> for ProviderItem {
         }
     }
 }
+
+impl From<Genre> for ProviderItem {
+    fn from(genre: Genre) -> ProviderItem {
+        ProviderItem {
+            label: genre.name().to_owned(),
+            data: ProviderItemType::Genre(genre),
+        }
+    }
+}
@@ -6,16 +6,18 @@ mod folder;
 mod item;
 mod sync_error;
 
-pub use self::explorer::Explorer;
+pub use self::explorer::{Explorer, GENRES_FOLDER};
 pub use self::folder::ProviderFolder;
 pub use self::item::{ProviderItem, ProviderItemType};
 pub use self::sync_error::SyncError;
 
-use crate::library::{SharedLibrary, Track};
+use crate::library::{Playlist, PlaylistEdit, PlaylistError, SharedLibrary, Track};
+use std::collections::HashSet;
 use std::fmt::Debug;
 use std::sync::{Arc, RwLock};
 
-pub type SharedProviders = Vec<Arc<RwLock<Box<dyn ProviderInstance + Send + Sync>>>>;
+pub type SharedProvider = Arc<RwLock<Box<dyn ProviderInstance + Send + Sync>>>;
+pub type SharedProviders = Vec<SharedProvider>;
 
 pub struct SyncResult {
     pub tracks: usize,
@@ -24,8 +26,21 @@ pub struct SyncResult {
     pub playlists: usize,
 }
 
+/// Uris of all entities a provider returned during a sync
+#[derive(Debug, Clone, Default)]
+pub struct SyncedUris {
+    pub tracks: HashSet<String>,
+    pub albums: HashSet<String>,
+    pub artists: HashSet<String>,
+    pub playlists: HashSet<String>,
+}
+
+/// Source of tracks, albums, artists and playlists
+///
+/// New providers are added over time, so matches have to handle unknown providers.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
+#[non_exhaustive]
 pub enum Provider {
     Pocketcasts,
     Soundcloud,
@@ -33,6 +48,8 @@ pub enum Provider {
     Spotify,
     #[serde(rename = "local")]
     LocalMedia,
+    /// Entities only stored in the library, e.g. playlists created by the user
+    Internal,
 }
 
 pub trait ProviderInstance: Debug {
@@ -41,11 +58,35 @@ pub trait ProviderInstance: Debug {
     fn uri_scheme(&self) -> &'static str;
     fn provider(&self) -> Provider;
     fn sync(&mut self, library: SharedLibrary) -> Result<SyncResult, Error>;
+
+    /// Returns the uris of everything the last `sync` returned, when it returned the whole provider
+    ///
+    /// Everything of this provider missing there gets pruned from the library after the sync.
+    /// Providers syncing only parts of their content keep this default.
+    fn synced_uris(&self) -> Option<SyncedUris> {
+        None
+    }
     fn root(&self) -> ProviderFolder;
     fn navigate(&self, path: Vec<String>) -> Result<ProviderFolder, Error>;
     fn search(&self, query: String) -> Result<Vec<ProviderItem>, Error>;
     fn resolve_track(&self, uri: &str) -> Result<Option<Track>, Error>;
     fn stream_url(&self, track: &Track) -> Result<String, Error>;
+
+    /// Pushes an edit of one of this provider's playlists back to the provider
+    ///
+    /// Called before the library applies the edit, an error aborts the edit.
+    /// Providers without support for editing playlists keep this default.
+    fn edit_playlist(&self, playlist: &Playlist, _edit: &PlaylistEdit) -> Result<(), Error> {
+        Err(PlaylistError::ReadOnly(playlist.provider).into())
+    }
+
+    /// Likes or unlikes the track, album or artist with the given uri at the provider
+    ///
+    /// Called before the favourite is stored in the library, an error aborts the change.
+    /// Providers without likes keep this default, the favourite is then only stored locally.
+    fn set_favourite(&self, _uri: &str, _favourite: bool) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 #[derive(Debug, Fail)]
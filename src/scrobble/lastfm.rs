@@ -0,0 +1,125 @@
+use serde_json::Value;
+use crate::scrobble::{Listen, ScrobbleError, ScrobbleService};
+use reqwest::Client;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+const API_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+/// Maximum number of scrobbles per request accepted by Last.fm
+const MAX_SCROBBLES: usize = 50;
+/// Error codes after which a request should be retried, see https://www.last.fm/api/errorcodes
+const RETRY_ERRORS: &[u64] = &[11, 16, 29];
+
+/// Submits scrobbles to the Last.fm api or a compatible server like Libre.fm
+///
+/// Requires an api account and the session key of an authenticated user.
+#[derive(Debug)]
+pub struct LastFm {
+    url: String,
+    api_key: String,
+    api_secret: String,
+    session_key: String,
+    client: Client,
+}
+
+impl LastFm {
+    pub fn new(api_key: String, api_secret: String, session_key: String) -> LastFm {
+        LastFm::with_url(API_URL.to_owned(), api_key, api_secret, session_key)
+    }
+
+    pub fn with_url(url: String, api_key: String, api_secret: String, session_key: String) -> LastFm {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .unwrap_or_else(|_| Client::new());
+        LastFm {
+            url,
+            api_key,
+            api_secret,
+            session_key,
+            client,
+        }
+    }
+
+    /// Signs the parameters as described in https://www.last.fm/api/authspec#_8-signing-calls
+    fn sign(&self, params: &BTreeMap<String, String>) -> String {
+        let mut signature = String::new();
+        for (key, value) in params {
+            signature.push_str(key);
+            signature.push_str(value);
+        }
+        signature.push_str(&self.api_secret);
+        format!("{:x}", md5::compute(signature))
+    }
+
+    fn call(&self, method: &str, mut params: BTreeMap<String, String>) -> Result<(), ScrobbleError> {
+        params.insert("method".to_owned(), method.to_owned());
+        params.insert("api_key".to_owned(), self.api_key.clone());
+        params.insert("sk".to_owned(), self.session_key.clone());
+        let signature = self.sign(&params);
+        params.insert("api_sig".to_owned(), signature);
+        params.insert("format".to_owned(), "json".to_owned());
+        let mut response = self.client.post(&self.url).form(&params).send()?;
+        let status = response.status();
+        let body: Value = response.json().unwrap_or(Value::Null);
+        if let Some(code) = body.get("error").and_then(Value::as_u64) {
+            let message = body.get("message").and_then(Value::as_str).unwrap_or_default();
+            let reason = format!("error {}: {}", code, message);
+            return if RETRY_ERRORS.contains(&code) {
+                Err(ScrobbleError::Unavailable(reason))
+            } else {
+                Err(ScrobbleError::Rejected(reason))
+            };
+        }
+        if status.is_server_error() {
+            Err(ScrobbleError::Unavailable(status.to_string()))
+        } else if !status.is_success() {
+            Err(ScrobbleError::Rejected(status.to_string()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Adds the parameters of a listen, `index` is set for batch scrobbles
+fn listen_params(params: &mut BTreeMap<String, String>, listen: &Listen, index: Option<usize>) {
+    let key = |name: &str| match index {
+        Some(index) => format!("{}[{}]", name, index),
+        None => name.to_owned(),
+    };
+    params.insert(key("artist"), listen.artist.clone());
+    params.insert(key("track"), listen.title.clone());
+    if let Some(album) = &listen.album {
+        params.insert(key("album"), album.clone());
+    }
+    if let Some(duration) = listen.duration {
+        params.insert(key("duration"), duration.to_string());
+    }
+    if index.is_some() {
+        params.insert(key("timestamp"), listen.listened_at.to_string());
+    }
+}
+
+impl ScrobbleService for LastFm {
+    fn name(&self) -> &'static str {
+        "Last.fm"
+    }
+
+    fn max_batch(&self) -> usize {
+        MAX_SCROBBLES
+    }
+
+    fn now_playing(&self, listen: &Listen) -> Result<(), ScrobbleError> {
+        let mut params = BTreeMap::new();
+        listen_params(&mut params, listen, None);
+        self.call("track.updateNowPlaying", params)
+    }
+
+    fn scrobble(&self, listens: &[Listen]) -> Result<(), ScrobbleError> {
+        let mut params = BTreeMap::new();
+        for (index, listen) in listens.iter().enumerate() {
+            listen_params(&mut params, listen, Some(index));
+        }
+        self.call("track.scrobble", params)
+    }
+}
@@ -0,0 +1,107 @@
+use serde_json::{json, Value};
+use crate::scrobble::{Listen, ScrobbleError, ScrobbleService};
+use reqwest::{Client, StatusCode};
+use std::time::Duration;
+
+const API_URL: &str = "https://api.listenbrainz.org";
+/// Maximum number of listens per request accepted by ListenBrainz
+const MAX_LISTENS: usize = 1000;
+
+/// Submits listens to the ListenBrainz api or a compatible server
+#[derive(Debug)]
+pub struct ListenBrainz {
+    url: String,
+    token: String,
+    client: Client,
+}
+
+impl ListenBrainz {
+    /// Creates a client for the official ListenBrainz server with the user token
+    pub fn new(token: String) -> ListenBrainz {
+        ListenBrainz::with_url(API_URL.to_owned(), token)
+    }
+
+    /// Creates a client for a compatible server, e.g. a self-hosted instance
+    pub fn with_url(url: String, token: String) -> ListenBrainz {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .unwrap_or_else(|_| Client::new());
+        ListenBrainz {
+            url: url.trim_end_matches('/').to_owned(),
+            token,
+            client,
+        }
+    }
+
+    fn submit(&self, listen_type: &str, payload: Vec<Value>) -> Result<(), ScrobbleError> {
+        let body = json!({
+            "listen_type": listen_type,
+            "payload": payload,
+        });
+        let mut response = self
+            .client
+            .post(&format!("{}/1/submit-listens", self.url))
+            .header("Authorization", format!("Token {}", self.token))
+            .json(&body)
+            .send()?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let reason = format!("{} {}", status, response.text().unwrap_or_default());
+        if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+            Err(ScrobbleError::Unavailable(reason))
+        } else {
+            Err(ScrobbleError::Rejected(reason))
+        }
+    }
+}
+
+fn track_metadata(listen: &Listen) -> Value {
+    let mut additional_info = json!({
+        "origin_url": listen.uri,
+        "submission_client": "rustic",
+    });
+    if let Some(duration) = listen.duration {
+        additional_info["duration_ms"] = json!(duration * 1000);
+    }
+    let mut metadata = json!({
+        "artist_name": listen.artist,
+        "track_name": listen.title,
+        "additional_info": additional_info,
+    });
+    if let Some(album) = &listen.album {
+        metadata["release_name"] = json!(album);
+    }
+    metadata
+}
+
+impl ScrobbleService for ListenBrainz {
+    fn name(&self) -> &'static str {
+        "ListenBrainz"
+    }
+
+    fn max_batch(&self) -> usize {
+        MAX_LISTENS
+    }
+
+    fn now_playing(&self, listen: &Listen) -> Result<(), ScrobbleError> {
+        let payload = json!({ "track_metadata": track_metadata(listen) });
+        self.submit("playing_now", vec![payload])
+    }
+
+    fn scrobble(&self, listens: &[Listen]) -> Result<(), ScrobbleError> {
+        let listen_type = if listens.len() == 1 { "single" } else { "import" };
+        let payload = listens
+            .iter()
+            .map(|listen| {
+                json!({
+                    "listened_at": listen.listened_at,
+                    "track_metadata": track_metadata(listen),
+                })
+            })
+            .collect();
+        self.submit(listen_type, payload)
+    }
+}
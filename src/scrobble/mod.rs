@@ -0,0 +1,258 @@
+//! Submission of listens to ListenBrainz and Last.fm
+//!
+//! A `Scrobbler` observes players, sends a now playing notification when a track starts
+//! and submits a listen once the track has been played according to the protocol rules:
+//! the track has to be longer than 30 seconds and be played for half its duration or 4 minutes.
+//! Listens are written to an on-disk queue before they are submitted,
+//! failed submissions stay in the queue and are retried with a growing delay by `Scrobbler::retry_queued`.
+mod lastfm;
+mod listenbrainz;
+mod queue;
+
+pub use self::lastfm::LastFm;
+pub use self::listenbrainz::ListenBrainz;
+
+use log::{debug, trace, warn};
+use failure::{Error, Fail};
+use serde_derive::{Deserialize, Serialize};
+use crate::history::{PlayThreshold, PlayTracker};
+use crate::library::Track;
+use crate::player::{PlayerBackend, PlayerEvent};
+use self::queue::ScrobbleQueue;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub type SharedScrobbler = Arc<Scrobbler>;
+
+/// Tracks up to this duration are never scrobbled
+pub const MIN_TRACK_DURATION: Duration = Duration::from_secs(30);
+
+/// First delay before queued listens are submitted again, doubled after every failed retry
+pub const MIN_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Longest delay between two retries of queued listens
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+/// Single listen as submitted to a scrobble service
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Listen {
+    pub uri: String,
+    pub title: String,
+    pub artist: String,
+    pub album: Option<String>,
+    /// Duration in seconds
+    pub duration: Option<u64>,
+    /// Start of playback as unix timestamp
+    pub listened_at: u64,
+}
+
+impl Listen {
+    /// Returns None for tracks which can't be scrobbled, e.g. without artist
+    pub fn new(track: &Track, listened_at: SystemTime) -> Option<Listen> {
+        let artist = track.artist.as_ref()?;
+        Some(Listen {
+            uri: track.uri.clone(),
+            title: track.title.clone(),
+            artist: artist.name.clone(),
+            album: track.album.as_ref().map(|album| album.title.clone()),
+            duration: track.duration,
+            listened_at: listened_at
+                .duration_since(UNIX_EPOCH)
+                .map(|timestamp| timestamp.as_secs())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum ScrobbleError {
+    /// The service is unavailable, the submission should be retried later
+    #[fail(display = "Service unavailable: {}", _0)]
+    Unavailable(String),
+    /// The service couldn't be reached, the submission should be retried later
+    #[fail(display = "Request failed: {}", _0)]
+    Request(#[cause] reqwest::Error),
+    /// The service refused the submission, retrying won't help
+    #[fail(display = "Submission rejected: {}", _0)]
+    Rejected(String),
+}
+
+impl From<reqwest::Error> for ScrobbleError {
+    fn from(error: reqwest::Error) -> ScrobbleError {
+        ScrobbleError::Request(error)
+    }
+}
+
+/// Protocol of a scrobble service
+pub trait ScrobbleService: fmt::Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+    /// Maximum number of listens per `scrobble` call
+    fn max_batch(&self) -> usize;
+    fn now_playing(&self, listen: &Listen) -> Result<(), ScrobbleError>;
+    fn scrobble(&self, listens: &[Listen]) -> Result<(), ScrobbleError>;
+}
+
+#[derive(Debug)]
+pub struct Scrobbler {
+    service: Box<dyn ScrobbleService>,
+    queue: Mutex<ScrobbleQueue>,
+    /// Held while submitting, so no listen is submitted twice
+    flushing: Mutex<()>,
+}
+
+impl Scrobbler {
+    /// Creates a scrobbler keeping its queue at `queue_path`, listens queued earlier are kept
+    pub fn new<P: Into<PathBuf>>(
+        service: Box<dyn ScrobbleService>,
+        queue_path: P,
+    ) -> Result<Scrobbler, Error> {
+        Ok(Scrobbler {
+            service,
+            queue: Mutex::new(ScrobbleQueue::open(queue_path.into())?),
+            flushing: Mutex::new(()),
+        })
+    }
+
+    /// Tracks longer than `MIN_TRACK_DURATION` with an artist can be scrobbled
+    pub fn is_scrobblable(track: &Track) -> bool {
+        track.artist.is_some()
+            && track
+                .duration
+                .is_some_and(|duration| Duration::from_secs(duration) > MIN_TRACK_DURATION)
+    }
+
+    pub fn now_playing(&self, track: &Track) {
+        if !Scrobbler::is_scrobblable(track) {
+            return;
+        }
+        if let Some(listen) = Listen::new(track, SystemTime::now()) {
+            if let Err(e) = self.service.now_playing(&listen) {
+                debug!("Can't send now playing to {}: {}", self.service.name(), e);
+            }
+        }
+    }
+
+    /// Queues a listen of the track and submits all queued listens
+    ///
+    /// When another thread is submitting already, the listen is submitted by that thread.
+    pub fn scrobble(&self, track: &Track, listened_at: SystemTime) -> Result<(), Error> {
+        if !Scrobbler::is_scrobblable(track) {
+            return Ok(());
+        }
+        if let Some(listen) = Listen::new(track, listened_at) {
+            self.queue.lock().unwrap().push(listen)?;
+        }
+        if let Ok(_flushing) = self.flushing.try_lock() {
+            self.submit_queued()?;
+        }
+        Ok(())
+    }
+
+    /// Number of listens waiting for submission
+    pub fn queued(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+
+    /// Submits all queued listens in batches and returns the number of listens still queued
+    ///
+    /// Stops at the first batch the service can't take right now,
+    /// batches rejected by the service are dropped.
+    pub fn flush(&self) -> Result<usize, Error> {
+        let _flushing = self.flushing.lock().unwrap();
+        self.submit_queued()
+    }
+
+    /// Submits the queued listens without holding the queue during requests, requires `flushing`
+    fn submit_queued(&self) -> Result<usize, Error> {
+        loop {
+            let batch = {
+                let queue = self.queue.lock().unwrap();
+                if queue.is_empty() {
+                    return Ok(0);
+                }
+                queue.peek(self.service.max_batch())
+            };
+            match self.service.scrobble(&batch) {
+                Ok(()) => trace!("Scrobbled {} listens to {}", batch.len(), self.service.name()),
+                Err(ScrobbleError::Rejected(reason)) => {
+                    warn!("{} rejected {} listens: {}", self.service.name(), batch.len(), reason);
+                }
+                Err(e) => {
+                    let queued = self.queued();
+                    debug!("{} unavailable, keeping {} listens: {}", self.service.name(), queued, e);
+                    return Ok(queued);
+                }
+            }
+            // listens are only appended while flushing, so the batch is still the oldest
+            self.queue.lock().unwrap().pop(batch.len())?;
+        }
+    }
+
+    /// Spawns a thread submitting queued listens until the scrobbler is dropped
+    ///
+    /// The delay starts at `MIN_RETRY_DELAY` and doubles up to `MAX_RETRY_DELAY` while
+    /// the service stays unavailable.
+    pub fn retry_queued(scrobbler: &SharedScrobbler) {
+        let scrobbler = Arc::downgrade(scrobbler);
+        let spawned = thread::Builder::new()
+            .name("Scrobble retry".to_owned())
+            .spawn(move || {
+                let mut delay = MIN_RETRY_DELAY;
+                loop {
+                    thread::sleep(delay);
+                    let scrobbler = match scrobbler.upgrade() {
+                        Some(scrobbler) => scrobbler,
+                        None => break,
+                    };
+                    if scrobbler.queued() == 0 {
+                        delay = MIN_RETRY_DELAY;
+                        continue;
+                    }
+                    delay = match scrobbler.flush() {
+                        Ok(0) => MIN_RETRY_DELAY,
+                        Ok(_) => (delay * 2).min(MAX_RETRY_DELAY),
+                        Err(e) => {
+                            warn!("Can't submit queued listens: {}", e);
+                            (delay * 2).min(MAX_RETRY_DELAY)
+                        }
+                    };
+                }
+            });
+        if let Err(e) = spawned {
+            debug!("Can't retry queued listens: {}", e);
+        }
+    }
+
+    /// Spawns a thread scrobbling the tracks of the given player until it stops sending events
+    pub fn observe_player(scrobbler: &SharedScrobbler, id: String, player: &Arc<Box<dyn PlayerBackend>>) {
+        let scrobbler = Arc::clone(scrobbler);
+        let events = player.observe();
+        let threshold = PlayThreshold::default();
+        let mut tracker = PlayTracker::new(player.state());
+        if let Some(track) = player.current() {
+            tracker.handle(&PlayerEvent::TrackChanged(track), Instant::now(), threshold);
+        }
+        let spawned = thread::Builder::new()
+            .name(format!("Scrobbler {}", id))
+            .spawn(move || {
+                let observed = |event: &PlayerEvent| {
+                    if let PlayerEvent::TrackChanged(track) = event {
+                        scrobbler.now_playing(track);
+                    }
+                };
+                tracker.run(&events, || threshold, observed, |track, played_at| {
+                    let listened_at = played_at - threshold.for_track(&track);
+                    if let Err(e) = scrobbler.scrobble(&track, listened_at) {
+                        warn!("Can't queue listen of {}: {}", track.uri, e);
+                    }
+                });
+                trace!("Player {} stopped sending events", id);
+            });
+        if let Err(e) = spawned {
+            debug!("Can't observe player: {}", e);
+        }
+    }
+}
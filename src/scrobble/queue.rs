@@ -0,0 +1,61 @@
+use failure::Error;
+use crate::scrobble::Listen;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+
+/// Listens waiting for submission, persisted after every change
+#[derive(Debug)]
+pub struct ScrobbleQueue {
+    path: PathBuf,
+    listens: Vec<Listen>,
+}
+
+impl ScrobbleQueue {
+    pub fn open(path: PathBuf) -> Result<ScrobbleQueue, Error> {
+        let listens = if path.exists() {
+            let file = File::open(&path)?;
+            serde_json::from_reader(BufReader::new(file))?
+        } else {
+            vec![]
+        };
+        Ok(ScrobbleQueue { path, listens })
+    }
+
+    pub fn len(&self) -> usize {
+        self.listens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.listens.is_empty()
+    }
+
+    pub fn push(&mut self, listen: Listen) -> Result<(), Error> {
+        self.listens.push(listen);
+        self.persist()
+    }
+
+    /// Returns up to `count` of the oldest listens
+    pub fn peek(&self, count: usize) -> Vec<Listen> {
+        self.listens.iter().take(count).cloned().collect()
+    }
+
+    /// Removes the `count` oldest listens
+    pub fn pop(&mut self, count: usize) -> Result<(), Error> {
+        let count = count.min(self.listens.len());
+        self.listens.drain(..count);
+        self.persist()
+    }
+
+    fn persist(&self) -> Result<(), Error> {
+        let tmp_path = self.path.with_extension("tmp");
+        {
+            let mut writer = BufWriter::new(File::create(&tmp_path)?);
+            serde_json::to_writer(&mut writer, &self.listens)?;
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
+        }
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,306 @@
+use log::{debug, warn};
+use failure::{format_err, Error};
+use crate::library::{SearchResults, Track, Album, Artist, Playlist};
+use crate::provider::{Provider, ProviderItem, ProviderItemType};
+use crate::channel;
+use crate::Rustic;
+use std::collections::{HashMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Results of `Rustic::search`
+pub struct FederatedSearchResults {
+    /// Merged results of the library and all providers, deduplicated by uri
+    pub results: SearchResults,
+    /// Providers which didn't deliver results
+    pub failures: Vec<ProviderSearchFailure>,
+}
+
+#[derive(Debug)]
+pub struct ProviderSearchFailure {
+    /// Position of the provider in `Rustic::providers`
+    pub index: usize,
+    /// None when the provider was locked by a running sync the whole time
+    pub provider: Option<Provider>,
+    pub title: Option<&'static str>,
+    pub reason: SearchFailureReason,
+}
+
+#[derive(Debug)]
+pub enum SearchFailureReason {
+    /// The provider didn't respond within the timeout
+    Timeout,
+    /// The provider returned an error
+    Error(Error),
+}
+
+enum Message {
+    Library(Result<SearchResults, Error>),
+    Started(usize, Provider, &'static str),
+    Finished(usize, Result<Vec<ProviderItem>, Error>),
+}
+
+/// Merges results in the order they are added, dropping entities with an already seen uri
+struct Merger {
+    results: SearchResults,
+    tracks: HashSet<String>,
+    albums: HashSet<String>,
+    artists: HashSet<String>,
+    playlists: HashSet<String>,
+}
+
+fn push<T>(items: &mut Vec<T>, seen: &mut HashSet<String>, uri: &str, item: T) {
+    if seen.insert(uri.to_owned()) {
+        items.push(item);
+    }
+}
+
+impl Merger {
+    fn new() -> Merger {
+        Merger {
+            results: SearchResults {
+                tracks: vec![],
+                albums: vec![],
+                artists: vec![],
+                playlists: vec![],
+            },
+            tracks: HashSet::new(),
+            albums: HashSet::new(),
+            artists: HashSet::new(),
+            playlists: HashSet::new(),
+        }
+    }
+
+    fn add_results(&mut self, results: SearchResults) {
+        results.tracks.into_iter().for_each(|track| self.add_track(track));
+        results.albums.into_iter().for_each(|album| self.add_album(album));
+        results.artists.into_iter().for_each(|artist| self.add_artist(artist));
+        results
+            .playlists
+            .into_iter()
+            .for_each(|playlist| self.add_playlist(playlist));
+    }
+
+    fn add_item(&mut self, item: ProviderItem) {
+        match item.data {
+            ProviderItemType::Track(track) => self.add_track(track),
+            ProviderItemType::Album(album) => self.add_album(album),
+            ProviderItemType::Artist(artist) => self.add_artist(artist),
+            ProviderItemType::Playlist(playlist) => self.add_playlist(playlist),
+            ProviderItemType::Genre(_) => {}
+        }
+    }
+
+    fn add_track(&mut self, track: Track) {
+        let uri = track.uri.clone();
+        push(&mut self.results.tracks, &mut self.tracks, &uri, track);
+    }
+
+    fn add_album(&mut self, album: Album) {
+        let uri = album.uri.clone();
+        push(&mut self.results.albums, &mut self.albums, &uri, album);
+    }
+
+    fn add_artist(&mut self, artist: Artist) {
+        let uri = artist.uri.clone();
+        push(&mut self.results.artists, &mut self.artists, &uri, artist);
+    }
+
+    fn add_playlist(&mut self, playlist: Playlist) {
+        let uri = playlist.uri.clone();
+        push(&mut self.results.playlists, &mut self.playlists, &uri, playlist);
+    }
+}
+
+/// Threads running the searches of a single source
+///
+/// Searches are queued and taken by the next free thread. Threads are started while there are more
+/// searches than threads, up to `MAX_WORKERS`, so a hanging source occupies a bounded number of threads
+/// while concurrent searches of other callers still get their turn.
+struct Workers {
+    name: String,
+    jobs: channel::Sender<Job>,
+    queue: channel::Receiver<Job>,
+    load: Arc<Mutex<Load>>,
+}
+
+/// Most threads searching a single source at once
+const MAX_WORKERS: usize = 4;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+#[derive(Default)]
+struct Load {
+    threads: usize,
+    /// Submitted jobs which are not finished yet
+    jobs: usize,
+}
+
+impl Workers {
+    fn new(name: String) -> Workers {
+        let (jobs, queue) = channel::unbounded();
+        Workers {
+            name,
+            jobs,
+            queue,
+            load: Arc::default(),
+        }
+    }
+
+    fn submit(&self, job: Job) -> Result<(), Error> {
+        let mut load = self.load.lock().unwrap();
+        if load.jobs >= load.threads && load.threads < MAX_WORKERS {
+            self.spawn()?;
+            load.threads += 1;
+        }
+        load.jobs += 1;
+        self.jobs.send(job);
+        Ok(())
+    }
+
+    fn spawn(&self) -> Result<(), Error> {
+        let queue = self.queue.clone();
+        let load = Arc::clone(&self.load);
+        thread::Builder::new().name(self.name.clone()).spawn(move || {
+            while let Some(job) = queue.recv() {
+                // a panicking search only drops its sender, the caller reports it as timed out
+                let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                load.lock().unwrap().jobs -= 1;
+            }
+        })?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Source {
+    Library,
+    Provider(usize),
+}
+
+impl Source {
+    fn thread_name(self) -> String {
+        match self {
+            Source::Library => "Search Library".to_owned(),
+            Source::Provider(index) => format!("Search Provider {}", index),
+        }
+    }
+}
+
+/// Search workers of the library and every provider, created on first use
+#[derive(Default)]
+pub(crate) struct SearchPool {
+    workers: Mutex<HashMap<Source, Workers>>,
+}
+
+impl SearchPool {
+    fn submit(&self, source: Source, job: Job) -> Result<(), Error> {
+        let mut workers = self.workers.lock().unwrap();
+        workers
+            .entry(source)
+            .or_insert_with(|| Workers::new(source.thread_name()))
+            .submit(job)
+    }
+}
+
+pub(crate) fn search(
+    app: &Rustic,
+    query: &str,
+    timeout: Duration,
+) -> Result<FederatedSearchResults, Error> {
+    let deadline = Instant::now() + timeout;
+    let (sender, receiver) = mpsc::channel();
+    let library = Arc::clone(&app.library);
+    let library_query = query.to_owned();
+    let library_sender = sender.clone();
+    let library_job: Job = Box::new(move || {
+        // jobs queued behind other searches are skipped once nobody waits for them anymore
+        if Instant::now() < deadline {
+            let _ = library_sender.send(Message::Library(library.search(library_query)));
+        }
+    });
+    app.searches.submit(Source::Library, library_job)?;
+
+    // known right away unless a running sync locks the provider
+    let mut providers: Vec<Option<(Provider, &'static str)>> = app
+        .providers
+        .iter()
+        .map(|provider| {
+            let provider = provider.try_read().ok()?;
+            Some((provider.provider(), provider.title()))
+        })
+        .collect();
+    for (index, provider) in app.providers.iter().enumerate() {
+        let provider = Arc::clone(provider);
+        let sender = sender.clone();
+        let query = query.to_owned();
+        let job: Job = Box::new(move || {
+            if Instant::now() >= deadline {
+                return;
+            }
+            let provider = provider.read().unwrap();
+            let _ = sender.send(Message::Started(index, provider.provider(), provider.title()));
+            let result = provider.search(query);
+            let _ = sender.send(Message::Finished(index, result));
+        });
+        app.searches.submit(Source::Provider(index), job)?;
+    }
+    drop(sender);
+
+    let mut library = None;
+    let mut pending: Vec<bool> = vec![true; app.providers.len()];
+    let mut items: Vec<Vec<ProviderItem>> = app.providers.iter().map(|_| vec![]).collect();
+    let mut failures: Vec<(usize, SearchFailureReason)> = vec![];
+    while library.is_none() || pending.iter().any(|pending| *pending) {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(remaining) {
+            Ok(Message::Library(results)) => library = Some(results?),
+            Ok(Message::Started(index, provider, title)) => {
+                providers[index] = Some((provider, title));
+            }
+            Ok(Message::Finished(index, result)) => {
+                pending[index] = false;
+                match result {
+                    Ok(result) => items[index] = result,
+                    Err(error) => failures.push((index, SearchFailureReason::Error(error))),
+                }
+            }
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    let library = library.ok_or_else(|| format_err!("library search timed out"))?;
+    for (index, pending) in pending.into_iter().enumerate() {
+        if pending {
+            failures.push((index, SearchFailureReason::Timeout));
+        }
+    }
+    failures.sort_by_key(|(index, _)| *index);
+
+    let mut merger = Merger::new();
+    merger.add_results(library);
+    for items in items {
+        items.into_iter().for_each(|item| merger.add_item(item));
+    }
+    let failures: Vec<ProviderSearchFailure> = failures
+        .into_iter()
+        .map(|(index, reason)| {
+            let provider = providers[index];
+            ProviderSearchFailure {
+                index,
+                provider: provider.map(|(provider, _)| provider),
+                title: provider.map(|(_, title)| title),
+                reason,
+            }
+        }).collect();
+    for failure in &failures {
+        warn!("Search failed for provider {:?}: {:?}", failure.title, failure.reason);
+    }
+    debug!("Search for {} finished with {} failures", query, failures.len());
+    Ok(FederatedSearchResults {
+        results: merger.results,
+        failures,
+    })
+}
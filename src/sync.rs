@@ -21,14 +21,29 @@ pub fn start(
                     let mut provider = provider.write().unwrap();
                     info!("Syncing {} library", provider.title());
                     match provider.sync(Arc::clone(&app.library)) {
-                        Ok(result) => info!(
-                            "Synced {} tracks, {} albums, {} artist and {} playlists from {}",
-                            result.tracks,
-                            result.albums,
-                            result.artists,
-                            result.playlists,
-                            provider.title()
-                        ),
+                        Ok(result) => {
+                            info!(
+                                "Synced {} tracks, {} albums, {} artist and {} playlists from {}",
+                                result.tracks,
+                                result.albums,
+                                result.artists,
+                                result.playlists,
+                                provider.title()
+                            );
+                            if let Some(synced) = provider.synced_uris() {
+                                match app.library.prune(provider.provider(), &synced) {
+                                    Ok(pruned) => info!(
+                                        "Pruned {} tracks, {} albums, {} artists and {} playlists from {}",
+                                        pruned.tracks,
+                                        pruned.albums,
+                                        pruned.artists,
+                                        pruned.playlists,
+                                        provider.title()
+                                    ),
+                                    Err(err) => error!("Error pruning {}: {:?}", provider.title(), err),
+                                }
+                            }
+                        }
                         Err(err) => error!("Error syncing {}: {:?}", provider.title(), err),
                     }
                 }
@@ -0,0 +1,121 @@
+mod common;
+
+use rustic_core::archive::{ArchiveError, Manifest, ARCHIVE_VERSION};
+use rustic_core::library::MemoryLibrary;
+use rustic_core::{Annotation, Rustic};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+fn app() -> Arc<Rustic> {
+    Rustic::new(Box::new(MemoryLibrary::new()), vec![]).unwrap()
+}
+
+fn exported() -> Vec<u8> {
+    let app = app();
+    let mut album = common::album("test:album:1", "Album");
+    album.artist = Some(common::artist("test:artist:1", "Artist"));
+    album.tracks = vec![
+        common::track("test:track:1", "One"),
+        common::track("test:track:2", "Two"),
+    ];
+    app.library.sync_album(&mut album).unwrap();
+    let mut single = common::track("test:track:3", "Single");
+    single.artist = Some(common::artist("test:artist:2", "Other"));
+    app.library.sync_track(&mut single).unwrap();
+    let mut playlist = common::playlist("test:playlist:1", "Playlist");
+    playlist.tracks = vec![single.clone(), album.tracks[0].clone()];
+    app.library.sync_playlist(&mut playlist).unwrap();
+    let annotation = Annotation {
+        favourite: true,
+        rating: Some(4),
+    };
+    app.library.set_annotation("test:track:1", annotation).unwrap();
+    let played_at = UNIX_EPOCH + Duration::from_secs(1000);
+    app.history.record(single.clone(), played_at).unwrap();
+    app.history.record(single, played_at + Duration::from_secs(300)).unwrap();
+
+    let mut archive = vec![];
+    app.export_archive(&mut archive).unwrap();
+    archive
+}
+
+#[test]
+fn round_trip_remaps_ids() {
+    let archive = exported();
+    let app = app();
+    app.library
+        .sync_tracks(&mut vec![
+            common::track("test:track:10", "Existing"),
+            common::track("test:track:11", "Existing"),
+        ])
+        .unwrap();
+
+    let manifest = app.import_archive(archive.as_slice()).unwrap();
+
+    assert_eq!(manifest.version, ARCHIVE_VERSION);
+    assert_eq!((manifest.artists, manifest.albums, manifest.tracks), (2, 1, 3));
+    assert_eq!((manifest.playlists, manifest.annotations, manifest.plays), (1, 1, 2));
+    let library = &app.library;
+    assert_eq!(library.get_tracks().unwrap().len(), 5);
+    let album = library.get_album_by_uri("test:album:1").unwrap().unwrap();
+    let artist = library.get_artist_by_uri("test:artist:1").unwrap().unwrap();
+    assert_eq!(album.artist_id, artist.id);
+    let tracks = library.get_album_tracks(album.id.unwrap()).unwrap();
+    let uris: Vec<&str> = tracks.iter().map(|track| track.uri.as_str()).collect();
+    assert_eq!(uris, vec!["test:track:1", "test:track:2"]);
+    let single = library.get_track_by_uri("test:track:3").unwrap().unwrap();
+    let other = library.get_artist_by_uri("test:artist:2").unwrap().unwrap();
+    assert_eq!(single.artist_id, other.id);
+    let playlist = library.get_playlist_by_uri("test:playlist:1").unwrap().unwrap();
+    let entries: Vec<_> = playlist.tracks.iter().map(|track| track.id).collect();
+    assert_eq!(entries, vec![single.id, tracks[0].id]);
+    assert_eq!(library.get_annotation("test:track:1").unwrap().rating, Some(4));
+    assert_eq!(app.history.stats("test:track:3").unwrap().play_count, 2);
+    assert!(app.check_library(false).unwrap().is_consistent());
+}
+
+#[test]
+fn importing_again_skips_recorded_plays() {
+    let archive = exported();
+    let app = app();
+
+    app.import_archive(archive.as_slice()).unwrap();
+    app.import_archive(archive.as_slice()).unwrap();
+
+    assert_eq!(app.history.plays().len(), 2);
+    assert_eq!(app.history.stats("test:track:3").unwrap().play_count, 2);
+    assert_eq!(app.library.get_tracks().unwrap().len(), 3);
+}
+
+#[test]
+fn starts_with_manifest() {
+    let archive = String::from_utf8(exported()).unwrap();
+    let lines: Vec<&str> = archive.lines().collect();
+
+    let manifest: Manifest = serde_json::from_str(lines[0]).unwrap();
+    assert_eq!(manifest.format, "rustic-archive");
+    assert_eq!(lines.len(), 11);
+    assert!(lines[1].starts_with("{\"type\":\"artist\""));
+}
+
+#[test]
+fn rejects_invalid_archives() {
+    let archive = String::from_utf8(exported()).unwrap();
+    let app = app();
+
+    let truncated: Vec<&str> = archive.lines().take(5).collect();
+    let error = app.import_archive(truncated.join("\n").as_bytes()).unwrap_err();
+    assert!(matches!(error.downcast_ref(), Some(ArchiveError::Incomplete)));
+    assert!(app.library.get_artists().unwrap().is_empty());
+
+    let newer = archive.replacen(
+        &format!("\"version\":{}", ARCHIVE_VERSION),
+        &format!("\"version\":{}", ARCHIVE_VERSION + 1),
+        1,
+    );
+    let error = app.import_archive(newer.as_bytes()).unwrap_err();
+    assert!(matches!(error.downcast_ref(), Some(ArchiveError::UnsupportedVersion(_))));
+
+    let error = app.import_archive("".as_bytes()).unwrap_err();
+    assert!(matches!(error.downcast_ref(), Some(ArchiveError::MissingManifest)));
+}
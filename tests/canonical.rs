@@ -0,0 +1,69 @@
+mod common;
+
+use common::MockProvider;
+use rustic_core::library::{MemoryLibrary, Track};
+use rustic_core::provider::{Provider, SharedProvider};
+use rustic_core::Rustic;
+use std::sync::Arc;
+
+fn provider(provider: Provider, available: bool) -> SharedProvider {
+    let provider = MockProvider::new(provider);
+    if available {
+        provider.shared()
+    } else {
+        provider.unavailable().shared()
+    }
+}
+
+fn source(uri: &str, provider: Provider) -> Track {
+    let mut track = common::track(uri, "Song");
+    track.provider = provider;
+    track.duration = Some(200);
+    track.artist = Some(common::artist("test:artist:1", "Artist"));
+    track
+}
+
+fn app(providers: Vec<SharedProvider>) -> Arc<Rustic> {
+    let app = Rustic::new(Box::new(MemoryLibrary::new()), providers).unwrap();
+    app.library
+        .sync_tracks(&mut vec![
+            source("spotify:track:1", Provider::Spotify),
+            source("file:track:1", Provider::LocalMedia),
+        ])
+        .unwrap();
+    app
+}
+
+#[test]
+fn streams_preferred_source() {
+    let app = app(vec![
+        provider(Provider::LocalMedia, true),
+        provider(Provider::Spotify, true),
+    ]);
+    let track = source("spotify:track:1", Provider::Spotify);
+
+    assert_eq!(app.stream_url(&track).unwrap(), "http://file:track:1");
+    app.set_source_preference(vec![Provider::Spotify]);
+    assert_eq!(app.stream_url(&track).unwrap(), "http://spotify:track:1");
+}
+
+#[test]
+fn skips_unavailable_sources() {
+    let app = app(vec![
+        provider(Provider::LocalMedia, false),
+        provider(Provider::Spotify, true),
+    ]);
+    let track = source("file:track:1", Provider::LocalMedia);
+
+    assert_eq!(app.stream_url(&track).unwrap(), "http://spotify:track:1");
+}
+
+#[test]
+fn streams_tracks_outside_the_library() {
+    let app = app(vec![provider(Provider::Soundcloud, true)]);
+
+    let track = source("soundcloud:track:1", Provider::Soundcloud);
+    assert_eq!(app.stream_url(&track).unwrap(), "http://soundcloud:track:1");
+    let track = source("file:track:1", Provider::LocalMedia);
+    assert!(app.stream_url(&track).is_err());
+}
@@ -0,0 +1,218 @@
+//! Helpers shared by the integration tests
+#![allow(dead_code, unused_imports)]
+
+use failure::{bail, Error};
+use rustic_core::library::{FileLibrary, SharedLibrary};
+use rustic_core::provider::{
+    Provider, ProviderFolder, ProviderInstance, ProviderItem, ProviderItemType, SharedProvider,
+    SyncResult,
+};
+use rustic_core::{Album, Artist, Playlist, PlaylistEdit, Track};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+
+pub use rustic_core::library::conformance::{album, artist, playlist, track, track_uris};
+
+static DIR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Empty directory in the system temp dir, removed with its content when dropped
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new() -> TempDir {
+        let id = DIR_COUNTER.fetch_add(1, Ordering::SeqCst);
+        let path = env::temp_dir().join(format!("rustic-test-{}-{}", process::id(), id));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path of the file with the given name inside this directory
+    pub fn file(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// `FileLibrary` stored in its own `TempDir`, dereferences to the library
+///
+/// The library is dropped before its directory is removed.
+pub struct TempLibrary {
+    library: FileLibrary,
+    dir: TempDir,
+}
+
+impl TempLibrary {
+    pub const FILE: &'static str = "library.json";
+
+    /// Opens a new, empty library
+    pub fn new() -> TempLibrary {
+        TempLibrary::open(TempDir::new()).unwrap()
+    }
+
+    /// Opens the library file `TempLibrary::FILE` of `dir`, keeping the directory alive
+    pub fn open(dir: TempDir) -> Result<TempLibrary, Error> {
+        let library = FileLibrary::open(dir.file(TempLibrary::FILE))?;
+        Ok(TempLibrary { library, dir })
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.dir.file(TempLibrary::FILE)
+    }
+}
+
+impl Deref for TempLibrary {
+    type Target = FileLibrary;
+
+    fn deref(&self) -> &FileLibrary {
+        &self.library
+    }
+}
+
+/// Provider answering from its configuration, it can't sync
+///
+/// An unavailable provider fails every call reaching the service.
+/// Favourites and playlist edits pushed to the provider are recorded in `likes` and `edits`.
+#[derive(Debug)]
+pub struct MockProvider {
+    pub provider: Provider,
+    pub available: bool,
+    /// Tracks returned by every search
+    pub tracks: Vec<Track>,
+    /// Time a search takes
+    pub delay: Option<Duration>,
+    pub likes: Arc<Mutex<Vec<(String, bool)>>>,
+    pub edits: Arc<Mutex<Vec<(String, PlaylistEdit)>>>,
+}
+
+impl MockProvider {
+    pub fn new(provider: Provider) -> MockProvider {
+        MockProvider {
+            provider,
+            available: true,
+            tracks: vec![],
+            delay: None,
+            likes: Arc::default(),
+            edits: Arc::default(),
+        }
+    }
+
+    pub fn unavailable(self) -> MockProvider {
+        MockProvider {
+            available: false,
+            ..self
+        }
+    }
+
+    pub fn with_tracks(self, tracks: Vec<Track>) -> MockProvider {
+        MockProvider { tracks, ..self }
+    }
+
+    pub fn with_delay(self, delay: Duration) -> MockProvider {
+        MockProvider {
+            delay: Some(delay),
+            ..self
+        }
+    }
+
+    pub fn shared(self) -> SharedProvider {
+        let instance: Box<dyn ProviderInstance + Send + Sync> = Box::new(self);
+        Arc::new(RwLock::new(instance))
+    }
+
+    fn check_available(&self) -> Result<(), Error> {
+        if !self.available {
+            bail!("{:?} is unavailable", self.provider);
+        }
+        Ok(())
+    }
+}
+
+impl ProviderInstance for MockProvider {
+    fn setup(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn title(&self) -> &'static str {
+        "Mock Provider"
+    }
+
+    fn uri_scheme(&self) -> &'static str {
+        "test"
+    }
+
+    fn provider(&self) -> Provider {
+        self.provider
+    }
+
+    fn sync(&mut self, _library: SharedLibrary) -> Result<SyncResult, Error> {
+        bail!("{:?} can't sync", self.provider)
+    }
+
+    fn root(&self) -> ProviderFolder {
+        ProviderFolder::empty()
+    }
+
+    fn navigate(&self, _path: Vec<String>) -> Result<ProviderFolder, Error> {
+        Ok(ProviderFolder::empty())
+    }
+
+    fn search(&self, _query: String) -> Result<Vec<ProviderItem>, Error> {
+        if let Some(delay) = self.delay {
+            thread::sleep(delay);
+        }
+        self.check_available()?;
+        let items = self
+            .tracks
+            .iter()
+            .cloned()
+            .map(|track| ProviderItem {
+                label: track.title.clone(),
+                data: ProviderItemType::Track(track),
+            })
+            .collect();
+        Ok(items)
+    }
+
+    fn resolve_track(&self, _uri: &str) -> Result<Option<Track>, Error> {
+        Ok(None)
+    }
+
+    fn stream_url(&self, track: &Track) -> Result<String, Error> {
+        self.check_available()?;
+        Ok(format!("http://{}", track.uri))
+    }
+
+    fn edit_playlist(&self, playlist: &Playlist, edit: &PlaylistEdit) -> Result<(), Error> {
+        self.check_available()?;
+        let edit = (playlist.uri.clone(), edit.clone());
+        self.edits.lock().unwrap().push(edit);
+        Ok(())
+    }
+
+    fn set_favourite(&self, uri: &str, favourite: bool) -> Result<(), Error> {
+        self.check_available()?;
+        self.likes.lock().unwrap().push((uri.to_owned(), favourite));
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use common::MockProvider;
+use rustic_core::library::MemoryLibrary;
+use rustic_core::provider::Provider;
+use rustic_core::Rustic;
+use std::sync::{Arc, Mutex};
+
+type Likes = Arc<Mutex<Vec<(String, bool)>>>;
+
+fn app(available: bool) -> (Arc<Rustic>, Likes) {
+    let provider = MockProvider {
+        available,
+        ..MockProvider::new(Provider::Soundcloud)
+    };
+    let likes = Arc::clone(&provider.likes);
+    let app = Rustic::new(Box::new(MemoryLibrary::new()), vec![provider.shared()]).unwrap();
+    (app, likes)
+}
+
+#[test]
+fn favourites_are_pushed_to_the_provider() {
+    let (app, likes) = app(true);
+    app.library
+        .sync_track(&mut common::track("test:track:1", "Track"))
+        .unwrap();
+
+    app.set_favourite("test:track:1", true).unwrap();
+    app.set_favourite("test:track:1", true).unwrap();
+    app.set_favourite("other:track:1", true).unwrap();
+
+    assert_eq!(*likes.lock().unwrap(), vec![("test:track:1".to_owned(), true)]);
+    assert_eq!(app.library.get_favourite_tracks().unwrap().len(), 1);
+    assert!(app.library.get_annotation("other:track:1").unwrap().favourite);
+}
+
+#[test]
+fn rejected_favourites_are_not_stored() {
+    let (app, _) = app(false);
+
+    assert!(app.set_favourite("test:track:1", true).is_err());
+    assert!(!app.library.get_annotation("test:track:1").unwrap().favourite);
+}
+
+#[test]
+fn ratings_are_validated() {
+    let (app, likes) = app(true);
+
+    app.set_rating("test:track:1", Some(3)).unwrap();
+    assert!(app.set_rating("test:track:1", Some(6)).is_err());
+    assert!(app.set_rating("test:track:1", Some(0)).is_err());
+
+    assert_eq!(app.library.get_annotation("test:track:1").unwrap().rating, Some(3));
+    app.set_rating("test:track:1", None).unwrap();
+    assert!(app.library.get_annotation("test:track:1").unwrap().is_empty());
+    assert!(likes.lock().unwrap().is_empty());
+}
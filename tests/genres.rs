@@ -0,0 +1,65 @@
+mod common;
+
+use rustic_core::library::{Genre, Library, MemoryLibrary};
+use rustic_core::provider::GENRES_FOLDER;
+use rustic_core::{Explorer, SharedLibrary};
+use std::sync::Arc;
+
+fn library() -> SharedLibrary {
+    let library = MemoryLibrary::new();
+    let mut album = common::album("test:album:1", "Album");
+    album.genres = vec![Genre::new("Jazz")];
+    album.tracks = vec![common::track("test:track:1", "Album Track")];
+    library.sync_album(&mut album).unwrap();
+    let mut track = common::track("test:track:2", "Single");
+    track.genres = vec![Genre::new("jazz"), Genre::new("Soul")];
+    library.sync_track(&mut track).unwrap();
+    Arc::new(Box::new(library))
+}
+
+#[test]
+fn normalizes_names() {
+    assert_eq!(Genre::new("  hip   hop ").name(), "Hip Hop");
+    assert_eq!(Genre::new("rock'n'roll").name(), "Rock'n'roll");
+    assert_eq!(Genre::new("drum-and-bass").name(), "Drum-And-Bass");
+    assert_eq!(Genre::new("EDM").name(), "EDM");
+    assert_eq!(Genre::new("Hip-Hop"), Genre::new("hip hop"));
+    assert_eq!(Genre::new("Música Popular").key(), "musicapopular");
+}
+
+#[test]
+fn parses_tag_lists() {
+    let genres = Genre::parse_list("rock; Pop/ROCK,, Jazz ");
+    let names: Vec<&str> = genres.iter().map(|genre| genre.name()).collect();
+    assert_eq!(names, vec!["Rock", "Pop", "Jazz"]);
+    assert!(Genre::parse_list(" ; ").is_empty());
+}
+
+#[test]
+fn explorer_browses_genres() {
+    let mut explorer = Explorer::with_library(vec![], library());
+    assert_eq!(explorer.items().unwrap().folders, vec![GENRES_FOLDER.to_owned()]);
+
+    explorer.navigate(GENRES_FOLDER.to_owned());
+    let genres = explorer.items().unwrap();
+    assert_eq!(genres.folders, vec!["Jazz".to_owned(), "Soul".to_owned()]);
+    assert!(genres.items.iter().all(|item| item.is_genre()));
+
+    explorer.navigate("jazz".to_owned());
+    let items = explorer.items().unwrap().items;
+    let labels: Vec<&str> = items.iter().map(|item| item.label.as_str()).collect();
+    assert_eq!(labels, vec!["Album", "Single"]);
+    assert!(items[0].is_album());
+
+    explorer.go_up();
+    explorer.navigate("Blues".to_owned());
+    assert!(explorer.items().is_err());
+}
+
+#[test]
+fn explorer_without_library_has_no_genres() {
+    let mut explorer = Explorer::new(vec![]);
+    assert!(explorer.items().unwrap().folders.is_empty());
+    explorer.navigate(GENRES_FOLDER.to_owned());
+    assert!(explorer.items().is_err());
+}
@@ -0,0 +1,167 @@
+mod common;
+
+use rustic_core::history::{History, PlayThreshold, PlayTracker};
+use rustic_core::library::{Library, MemoryLibrary};
+use rustic_core::{PlayerEvent, PlayerState, Track};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+fn track(uri: &str, duration: Option<u64>) -> Track {
+    let mut track = common::track(uri, uri);
+    track.duration = duration;
+    track
+}
+
+fn secs(secs: u64) -> Duration {
+    Duration::from_secs(secs)
+}
+
+#[test]
+fn threshold_uses_fraction_or_duration() {
+    let threshold = PlayThreshold::default();
+
+    assert_eq!(threshold.for_track(&track("test:track:1", Some(200))), secs(100));
+    assert_eq!(threshold.for_track(&track("test:track:1", Some(600))), secs(240));
+    assert_eq!(threshold.for_track(&track("test:track:1", None)), secs(240));
+}
+
+#[test]
+fn tracker_reports_play_after_threshold() {
+    let threshold = PlayThreshold::default();
+    let start = Instant::now();
+    let mut tracker = PlayTracker::new(PlayerState::Play);
+
+    let event = PlayerEvent::TrackChanged(track("test:track:1", Some(200)));
+    assert!(tracker.handle(&event, start, threshold).is_none());
+    let event = PlayerEvent::TrackChanged(track("test:track:2", Some(200)));
+    let (played, at) = tracker.handle(&event, start + secs(150), threshold).unwrap();
+
+    assert_eq!(played.uri, "test:track:1");
+    assert_eq!(at, start + secs(100));
+    let event = PlayerEvent::StateChanged(PlayerState::Stop);
+    assert!(tracker.handle(&event, start + secs(200), threshold).is_none());
+}
+
+#[test]
+fn tracker_reports_play_without_next_event() {
+    let threshold = PlayThreshold::default();
+    let start = Instant::now();
+    let mut tracker = PlayTracker::new(PlayerState::Play);
+
+    tracker.handle(&PlayerEvent::TrackChanged(track("test:track:1", Some(200))), start, threshold);
+    assert_eq!(tracker.remaining(start + secs(40)), Some(secs(60)));
+    assert!(tracker.poll(start + secs(40)).is_none());
+    tracker.handle(&PlayerEvent::StateChanged(PlayerState::Pause), start + secs(40), threshold);
+    assert!(tracker.remaining(start + secs(500)).is_none());
+    tracker.handle(&PlayerEvent::StateChanged(PlayerState::Play), start + secs(500), threshold);
+    assert_eq!(tracker.remaining(start + secs(500)), Some(secs(60)));
+
+    let (played, at) = tracker.poll(start + secs(560)).unwrap();
+    assert_eq!(played.uri, "test:track:1");
+    assert_eq!(at, start + secs(560));
+    assert!(tracker.remaining(start + secs(560)).is_none());
+    let event = PlayerEvent::StateChanged(PlayerState::Stop);
+    assert!(tracker.handle(&event, start + secs(600), threshold).is_none());
+}
+
+#[test]
+fn tracker_runs_until_player_stops_sending_events() {
+    use crossbeam_channel as channel;
+    use std::thread;
+
+    let threshold = PlayThreshold {
+        fraction: 0.5,
+        duration: Duration::from_millis(50),
+    };
+    let (events, receiver) = channel::unbounded();
+    let (played, reported) = channel::unbounded();
+    let player = thread::spawn(move || {
+        events.send(PlayerEvent::TrackChanged(track("test:track:1", Some(200))));
+        // the play is reported by the timer, the player stays silent until then
+        reported.recv()
+    });
+    let mut tracker = PlayTracker::new(PlayerState::Play);
+    let mut observed = 0;
+
+    tracker.run(&receiver, || threshold, |_| observed += 1, |track, _| {
+        played.send(track.uri);
+    });
+
+    assert_eq!(observed, 1);
+    assert_eq!(player.join().unwrap(), Some("test:track:1".to_owned()));
+}
+
+#[test]
+fn tracker_only_counts_playing_time() {
+    let threshold = PlayThreshold::default();
+    let start = Instant::now();
+    let mut tracker = PlayTracker::new(PlayerState::Play);
+
+    tracker.handle(&PlayerEvent::TrackChanged(track("test:track:1", Some(200))), start, threshold);
+    tracker.handle(&PlayerEvent::StateChanged(PlayerState::Pause), start + secs(60), threshold);
+    let event = PlayerEvent::StateChanged(PlayerState::Play);
+    assert!(tracker.handle(&event, start + secs(600), threshold).is_none());
+    tracker.handle(&PlayerEvent::Seek(secs(190)), start + secs(610), threshold);
+    let event = PlayerEvent::StateChanged(PlayerState::Stop);
+    let (_, at) = tracker.handle(&event, start + secs(700), threshold).unwrap();
+
+    assert_eq!(at, start + secs(640));
+}
+
+#[test]
+fn tracker_ignores_skipped_tracks() {
+    let threshold = PlayThreshold::default();
+    let start = Instant::now();
+    let mut tracker = PlayTracker::new(PlayerState::Play);
+
+    tracker.handle(&PlayerEvent::TrackChanged(track("test:track:1", Some(200))), start, threshold);
+    let event = PlayerEvent::TrackChanged(track("test:track:2", Some(200)));
+    assert!(tracker.handle(&event, start + secs(30), threshold).is_none());
+}
+
+#[test]
+fn history_counts_plays() {
+    let history = History::default();
+    let now = SystemTime::now();
+
+    history.record(track("test:track:1", None), now - secs(300)).unwrap();
+    history.record(track("test:track:2", None), now - secs(200)).unwrap();
+    history.record(track("test:track:1", None), now - secs(100)).unwrap();
+    history.record(track("test:track:3", None), now).unwrap();
+
+    let recent: Vec<String> = history
+        .recently_played(3)
+        .into_iter()
+        .map(|play| play.track.uri)
+        .collect();
+    assert_eq!(recent, vec!["test:track:3", "test:track:1", "test:track:2"]);
+    let most: Vec<(String, u64)> = history
+        .most_played(2)
+        .into_iter()
+        .map(|stats| (stats.track.uri, stats.play_count))
+        .collect();
+    assert_eq!(
+        most,
+        vec![("test:track:1".to_owned(), 2), ("test:track:3".to_owned(), 1)]
+    );
+    let stats = history.stats("test:track:1").unwrap();
+    assert_eq!(stats.last_played, now - secs(100));
+    assert!(history.stats("test:track:4").is_none());
+}
+
+#[test]
+fn history_is_stored_in_library() {
+    let library: Arc<Box<dyn Library>> = Arc::new(Box::new(MemoryLibrary::new()));
+    let now = SystemTime::now();
+    {
+        let history = History::with_library(PlayThreshold::default(), Arc::clone(&library)).unwrap();
+        history.record(track("test:track:1", None), now - secs(100)).unwrap();
+        history.record(track("test:track:1", None), now).unwrap();
+    }
+
+    assert_eq!(library.get_plays().unwrap().len(), 2);
+    let history = History::with_library(PlayThreshold::default(), library).unwrap();
+    let stats = history.stats("test:track:1").unwrap();
+    assert_eq!(stats.play_count, 2);
+    assert_eq!(stats.last_played, now);
+}
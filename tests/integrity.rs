@@ -0,0 +1,119 @@
+mod common;
+
+use common::{TempDir, TempLibrary};
+use rustic_core::library::integrity::{self, IntegrityIssue};
+use rustic_core::library::{EntityKind, FileLibrary, Library};
+use serde_json::{json, Value};
+use std::fs;
+
+/// Creates a consistent library file, lets `corrupt` edit the json and opens it again
+fn corrupted_library<F: FnOnce(&mut Value)>(corrupt: F) -> TempLibrary {
+    let dir = TempDir::new();
+    let path = dir.file(TempLibrary::FILE);
+    {
+        let library = FileLibrary::open(&path).unwrap();
+        let mut album = common::album("test:album:1", "Album");
+        album.artist = Some(common::artist("test:artist:1", "Artist"));
+        album.tracks = vec![
+            common::track("test:track:1", "One"),
+            common::track("test:track:2", "Two"),
+        ];
+        library.sync_album(&mut album).unwrap();
+        let mut playlist = common::playlist("test:playlist:1", "Playlist");
+        playlist.tracks = album.tracks.clone();
+        library.sync_playlist(&mut playlist).unwrap();
+    }
+    let mut snapshot: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    corrupt(&mut snapshot);
+    fs::write(&path, serde_json::to_string(&snapshot).unwrap()).unwrap();
+    TempLibrary::open(dir).unwrap()
+}
+
+fn track_mut<'a>(snapshot: &'a mut Value, uri: &str) -> &'a mut Value {
+    snapshot["tracks"]
+        .as_array_mut()
+        .unwrap()
+        .iter_mut()
+        .find(|track| track["uri"] == uri)
+        .unwrap()
+}
+
+#[test]
+fn consistent_library_has_no_issues() {
+    let library = corrupted_library(|_| {});
+
+    let report = integrity::check(&*library).unwrap();
+
+    assert!(report.issues.is_empty());
+    assert!(report.is_consistent());
+}
+
+#[test]
+fn reports_and_repairs_broken_references() {
+    let library = corrupted_library(|snapshot| {
+        let album = snapshot["albums"][0].clone();
+        let track = track_mut(snapshot, "test:track:1");
+        track["album_id"] = json!(99);
+        track["album"] = album;
+        track_mut(snapshot, "test:track:2")["artist_id"] = json!(77);
+        snapshot["albums"][0]["artist"]["name"] = json!("Old Name");
+    });
+
+    let report = integrity::check(&*library).unwrap();
+    assert_eq!(report.issues.len(), 3);
+    assert!(report.issues.contains(&IntegrityIssue::DanglingReference {
+        kind: EntityKind::Track,
+        uri: "test:track:1".to_owned(),
+        target: EntityKind::Album,
+        id: 99,
+    }));
+    assert!(report.issues.contains(&IntegrityIssue::MismatchedCopy {
+        kind: EntityKind::Album,
+        uri: "test:album:1".to_owned(),
+        target: EntityKind::Artist,
+    }));
+    assert!(report.remaining.is_none());
+
+    let report = integrity::repair(&*library).unwrap();
+    assert_eq!(report.issues.len(), 3);
+    assert_eq!(report.remaining, Some(vec![]));
+    let album = library.get_album_by_uri("test:album:1").unwrap().unwrap();
+    let track = library.get_track_by_uri("test:track:1").unwrap().unwrap();
+    assert_eq!(track.album_id, album.id);
+    assert_eq!(album.artist.unwrap().name, "Artist");
+    let track = library.get_track_by_uri("test:track:2").unwrap().unwrap();
+    assert!(track.artist_id.is_none());
+    assert_eq!(library.get_albums().unwrap().len(), 1);
+}
+
+#[test]
+fn repairs_duplicates_and_missing_ids() {
+    let library = corrupted_library(|snapshot| {
+        let mut duplicate = track_mut(snapshot, "test:track:1").clone();
+        duplicate["id"] = json!(50);
+        let mut unindexed = track_mut(snapshot, "test:track:2").clone();
+        unindexed["id"] = Value::Null;
+        unindexed["uri"] = json!("test:track:3");
+        let tracks = snapshot["tracks"].as_array_mut().unwrap();
+        tracks.insert(0, duplicate);
+        tracks.push(unindexed);
+    });
+    let kept = library.get_track_by_uri("test:track:1").unwrap().unwrap().id.unwrap();
+
+    let report = integrity::repair(&*library).unwrap();
+
+    assert!(report.issues.contains(&IntegrityIssue::DuplicateUri {
+        kind: EntityKind::Track,
+        uri: "test:track:1".to_owned(),
+        kept,
+        duplicates: vec![if kept == 50 { 1 } else { 50 }],
+    }));
+    assert!(report.is_consistent());
+    let tracks = library.get_tracks().unwrap();
+    assert_eq!(tracks.len(), 3);
+    assert!(tracks.iter().all(|track| track.id.is_some()));
+    let playlist = library.get_playlist_by_uri("test:playlist:1").unwrap().unwrap();
+    let entries: Vec<_> = playlist.tracks.iter().map(|track| (track.uri.as_str(), track.id)).collect();
+    assert_eq!(entries[0], ("test:track:1", Some(kept)));
+    assert_eq!(entries[1].0, "test:track:2");
+}
@@ -0,0 +1,205 @@
+mod common;
+
+use common::{TempDir, TempLibrary};
+use rustic_core::library::FileLibrary;
+
+mod memory {
+    use rustic_core::library::MemoryLibrary;
+
+    rustic_core::library_conformance_tests!(Box::new(MemoryLibrary::new()));
+}
+
+mod file {
+    rustic_core::library_conformance_tests!(super::TempLibrary::new());
+}
+
+#[test]
+fn file_library_persists_content() {
+    use rustic_core::library::Library;
+
+    let dir = TempDir::new();
+    let path = dir.file("library.json");
+    let mut track = common::track("test:track:1", "Track");
+    track.artist = Some(common::artist("test:artist:1", "Artist"));
+    {
+        let library = FileLibrary::open(&path).unwrap();
+        library.sync_track(&mut track).unwrap();
+    }
+
+    let library = FileLibrary::open(&path).unwrap();
+    let stored = library.get_track(track.id.unwrap()).unwrap().unwrap();
+    assert_eq!(stored.artist_id, track.artist_id);
+    let mut next = common::track("test:track:2", "Track");
+    library.add_track(&mut next).unwrap();
+    assert_ne!(next.id, track.id);
+}
+
+#[test]
+fn file_library_persists_album_aliases() {
+    use rustic_core::library::Library;
+
+    let dir = TempDir::new();
+    let path = dir.file("library.json");
+    let compilation = |uri: &str| {
+        let mut album = common::album(uri, "Hits");
+        album.compilation = true;
+        album
+    };
+    let grouped = {
+        let library = FileLibrary::open(&path).unwrap();
+        let mut first = compilation("test:album:1");
+        library.sync_album(&mut first).unwrap();
+        library.sync_album(&mut compilation("test:album:2")).unwrap();
+        first.id
+    };
+
+    let library = FileLibrary::open(&path).unwrap();
+    let part = library.get_album_by_uri("test:album:2").unwrap().unwrap();
+    assert_eq!(part.id, grouped);
+    assert_eq!(part.uri, "test:album:1");
+    library.remove_album(grouped.unwrap()).unwrap();
+    assert!(library.get_album_by_uri("test:album:2").unwrap().is_none());
+}
+
+#[test]
+fn file_library_persists_plays() {
+    use rustic_core::history::Play;
+    use rustic_core::library::Library;
+    use std::time::SystemTime;
+
+    let dir = TempDir::new();
+    let path = dir.file("library.json");
+    let played_at = SystemTime::now();
+    {
+        let library = FileLibrary::open(&path).unwrap();
+        let play = Play {
+            track: common::track("test:track:1", "Track"),
+            played_at,
+        };
+        library.add_play(play).unwrap();
+    }
+
+    let plays = FileLibrary::open(&path).unwrap().get_plays().unwrap();
+    assert_eq!(plays.len(), 1);
+    assert_eq!(plays[0].track.uri, "test:track:1");
+    assert_eq!(plays[0].played_at, played_at);
+}
+
+#[test]
+fn file_library_undoes_modifications_when_write_fails() {
+    use rustic_core::library::{Annotation, Library, LibraryEvent};
+    use std::fs;
+
+    let library = TempLibrary::new();
+    let mut album = common::album("test:album:1", "Album");
+    album.artist = Some(common::artist("test:artist:1", "Artist"));
+    album.tracks = vec![common::track("test:track:1", "One")];
+    library.sync_album(&mut album).unwrap();
+    let events = library.observe();
+    // the temporary file can't be created while a directory is in its place
+    let blocked = library.path().with_extension("tmp");
+    fs::create_dir(&blocked).unwrap();
+
+    assert!(library.sync_track(&mut common::track("test:track:2", "Two")).is_err());
+    assert!(library.remove_artist(album.artist_id.unwrap()).is_err());
+    let annotation = Annotation {
+        favourite: true,
+        rating: None,
+    };
+    assert!(library.set_annotation("test:track:1", annotation).is_err());
+
+    assert!(events.try_recv().is_none());
+    let tracks = library.get_tracks().unwrap();
+    assert_eq!(common::track_uris(tracks.clone()), vec!["test:track:1"]);
+    assert_eq!(tracks[0].album_id, album.id);
+    let stored = library.get_album(album.id.unwrap()).unwrap().unwrap();
+    assert_eq!(stored.artist_id, album.artist_id);
+    assert!(library.get_annotations().unwrap().is_empty());
+    assert!(library.get_track_by_uri("test:track:2").unwrap().is_none());
+
+    fs::remove_dir(&blocked).unwrap();
+    let mut three = common::track("test:track:3", "Three");
+    library.sync_track(&mut three).unwrap();
+    assert_eq!(three.id, Some(tracks[0].id.unwrap() + 1));
+    assert!(matches!(events.try_recv(), Some(LibraryEvent::TrackAdded(_))));
+    let reopened = FileLibrary::open(library.path()).unwrap();
+    assert_eq!(
+        common::track_uris(reopened.get_tracks().unwrap()),
+        vec!["test:track:1", "test:track:3"]
+    );
+}
+
+#[test]
+fn file_library_batch_is_undone_on_failure() {
+    use failure::bail;
+    use rustic_core::library::Library;
+
+    let library = TempLibrary::new();
+    library
+        .batch(&mut |library| {
+            library.sync_track(&mut common::track("test:track:1", "One"))?;
+            library.sync_track(&mut common::track("test:track:2", "Two"))
+        })
+        .unwrap();
+    let failed = library.batch(&mut |library| {
+        library.sync_track(&mut common::track("test:track:3", "Three"))?;
+        bail!("failed")
+    });
+
+    assert!(failed.is_err());
+    assert_eq!(library.get_tracks().unwrap().len(), 2);
+    let reopened = FileLibrary::open(library.path()).unwrap();
+    assert_eq!(
+        common::track_uris(reopened.get_tracks().unwrap()),
+        vec!["test:track:1", "test:track:2"]
+    );
+}
+
+#[test]
+fn smart_playlist_round_trips() {
+    use rustic_core::library::{Genre, MetaKey, MetaRule, Range, Rule, SmartPlaylist, SortKey};
+
+    let playlist = SmartPlaylist {
+        sort: Some(SortKey::Duration),
+        limit: Some(10),
+        ..SmartPlaylist::new(
+            "Long Jazz".to_owned(),
+            vec![
+                Rule::Duration(Range {
+                    min: Some(600.0),
+                    max: None,
+                }),
+                Rule::Genre(Genre::new("Jazz")),
+                Rule::Meta(MetaKey::Year, MetaRule::Range(Range {
+                    min: Some(1950.0),
+                    max: Some(1959.0),
+                })),
+            ],
+        )
+    };
+
+    let json = serde_json::to_string(&playlist).unwrap();
+    let restored: SmartPlaylist = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, playlist);
+}
+
+#[test]
+fn meta_keys_round_trip() {
+    use rustic_core::library::{MetaKey, MetaValue, Track};
+
+    assert_eq!(MetaKey::from("Year"), MetaKey::Year);
+    assert_eq!(MetaKey::from("trackNumber"), MetaKey::TrackNumber);
+    assert_eq!(MetaKey::from("MusicBrainz-Artist-Id"), MetaKey::MusicBrainzArtistId);
+    assert_eq!(MetaKey::from("mood"), MetaKey::Custom("mood".to_owned()));
+
+    let mut track = common::track("test:track:1", "Track");
+    track.meta.insert(MetaKey::Year, MetaValue::Int(1959));
+    track.meta.insert(MetaKey::Explicit, MetaValue::Bool(true));
+    track.meta.insert(MetaKey::from("mood"), MetaValue::String("calm".to_owned()));
+
+    let json = serde_json::to_value(&track).unwrap();
+    assert_eq!(json["meta"]["year"], serde_json::json!({ "Int": 1959 }));
+    assert!(json["meta"].get("mood").is_some());
+    let restored: Track = serde_json::from_value(json).unwrap();
+    assert_eq!(restored.meta, track.meta);
+}
@@ -0,0 +1,166 @@
+mod common;
+
+use common::TempDir;
+use rustic_core::library::migration::{self, MigrationError, MIGRATIONS, SCHEMA_VERSION};
+use rustic_core::library::{EntityKind, FileLibrary, Genre, Library, MemoryLibrary, MetaKey};
+use rustic_core::Rustic;
+use serde_json::{json, Value};
+use std::fs;
+
+/// Track as written before schema versioning
+fn track_v0() -> Value {
+    json!({
+        "id": 1,
+        "title": "Track",
+        "artist_id": null,
+        "artist": null,
+        "album_id": 1,
+        "album": {
+            "id": 1,
+            "title": "Album",
+            "artist_id": null,
+            "artist": null,
+            "provider": "local",
+            "image_url": null,
+            "uri": "test:album:1",
+            "meta": { "GENRE": { "String": "Jazz" } }
+        },
+        "provider": "local",
+        "uri": "test:track:1",
+        "image_url": null,
+        "duration": 200,
+        "meta": { "trackNumber": { "Int": 3 }, "Disc-Number": { "Int": 2 } }
+    })
+}
+
+#[test]
+fn migrations_cover_every_version() {
+    assert_eq!(MIGRATIONS.len(), SCHEMA_VERSION as usize);
+    for (version, migration) in MIGRATIONS.iter().enumerate() {
+        assert_eq!(migration.from as usize, version);
+        assert!(!migration.description.is_empty());
+    }
+}
+
+#[test]
+fn migrates_embedded_entities() {
+    let mut playlist = json!({
+        "id": 1,
+        "title": "Playlist",
+        "tracks": [track_v0()],
+        "provider": "local",
+        "uri": "test:playlist:1"
+    });
+
+    migration::migrate_entity(EntityKind::Playlist, &mut playlist, 0).unwrap();
+
+    let track = &playlist["tracks"][0];
+    assert_eq!(track["meta"]["track_number"], json!({ "Int": 3 }));
+    assert_eq!(track["meta"]["disc_number"], json!({ "Int": 2 }));
+    assert!(track["album"]["meta"].get("genre").is_none());
+    assert_eq!(track["album"]["genres"], json!(["Jazz"]));
+    assert_eq!(track["genres"], json!([]));
+    assert_eq!(track["album"]["compilation"], json!(false));
+    assert!(track.get("compilation").is_none());
+}
+
+#[test]
+fn flags_compilations() {
+    let mut tagged = track_v0()["album"].clone();
+    tagged["meta"] = json!({ "COMPILATION": { "String": "1" }, "year": { "Int": 1997 } });
+    let mut various = track_v0()["album"].clone();
+    various["artist"] = json!({ "name": "Various  Artists", "uri": "test:artist:0" });
+
+    migration::migrate_entity(EntityKind::Album, &mut tagged, 2).unwrap();
+    migration::migrate_entity(EntityKind::Album, &mut various, 2).unwrap();
+
+    assert_eq!(tagged["compilation"], json!(true));
+    assert_eq!(tagged["meta"], json!({ "year": { "Int": 1997 } }));
+    assert_eq!(various["compilation"], json!(true));
+    let mut album = track_v0()["album"].clone();
+    migration::migrate_entity(EntityKind::Album, &mut album, 2).unwrap();
+    assert_eq!(album["compilation"], json!(false));
+}
+
+#[test]
+fn splits_genre_lists() {
+    let mut album = track_v0()["album"].clone();
+    album["meta"] = json!({
+        "genre": { "String": "rock; Hip-Hop / jazz, Rock" },
+        "year": { "Int": 1997 }
+    });
+
+    migration::migrate_entity(EntityKind::Album, &mut album, 1).unwrap();
+
+    assert_eq!(album["genres"], json!(["Rock", "Hip-Hop", "Jazz"]));
+    assert_eq!(album["meta"], json!({ "year": { "Int": 1997 } }));
+}
+
+#[test]
+fn opens_unversioned_snapshots() {
+    let dir = TempDir::new();
+    let path = dir.file("library.json");
+    let snapshot = json!({
+        "tracks": [track_v0()],
+        "albums": [track_v0()["album"]],
+        "artists": [],
+        "playlists": []
+    });
+    fs::write(&path, snapshot.to_string()).unwrap();
+
+    let library = FileLibrary::open(&path).unwrap();
+    let track = library.get_track(1).unwrap().unwrap();
+    assert_eq!(track.track_number(), Some(3));
+    assert_eq!(track.disc_number(), Some(2));
+    let album = library.get_album(1).unwrap().unwrap();
+    assert!(!album.meta.contains_key(&MetaKey::from("genre")));
+    assert_eq!(album.genres, vec![Genre::new("Jazz")]);
+
+    library.set_annotation("test:track:1", Default::default()).unwrap();
+    let written: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(written["version"], SCHEMA_VERSION);
+    assert!(written["tracks"][0]["meta"].get("track_number").is_some());
+}
+
+#[test]
+fn rejects_newer_snapshots() {
+    let dir = TempDir::new();
+    let path = dir.file("library.json");
+    let snapshot = json!({
+        "version": SCHEMA_VERSION + 1,
+        "tracks": [],
+        "albums": [],
+        "artists": [],
+        "playlists": []
+    });
+    fs::write(&path, snapshot.to_string()).unwrap();
+
+    let error = FileLibrary::open(&path).err().unwrap();
+    assert!(matches!(
+        error.downcast_ref(),
+        Some(MigrationError::UnsupportedVersion(_))
+    ));
+}
+
+#[test]
+fn imports_unversioned_archives() {
+    let manifest = json!({
+        "format": "rustic-archive",
+        "version": 1,
+        "created_at": 0,
+        "artists": 0,
+        "albums": 0,
+        "tracks": 1,
+        "playlists": 0,
+        "annotations": 0,
+        "plays": 0
+    });
+    let record = json!({ "type": "track", "data": track_v0() });
+    let archive = format!("{}\n{}\n", manifest, record);
+    let app = Rustic::new(Box::new(MemoryLibrary::new()), vec![]).unwrap();
+
+    app.import_archive(archive.as_bytes()).unwrap();
+
+    let track = app.library.get_track_by_uri("test:track:1").unwrap().unwrap();
+    assert_eq!(track.track_number(), Some(3));
+}
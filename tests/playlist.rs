@@ -0,0 +1,79 @@
+mod common;
+
+use common::MockProvider;
+use rustic_core::library::MemoryLibrary;
+use rustic_core::provider::Provider;
+use rustic_core::{PlaylistEdit, Rustic};
+use std::sync::{Arc, Mutex};
+
+type Edits = Arc<Mutex<Vec<(String, PlaylistEdit)>>>;
+
+fn app(writable: bool) -> (Arc<Rustic>, Edits) {
+    let provider = MockProvider {
+        available: writable,
+        ..MockProvider::new(Provider::Soundcloud)
+    };
+    let edits = Arc::clone(&provider.edits);
+    let app = Rustic::new(Box::new(MemoryLibrary::new()), vec![provider.shared()]).unwrap();
+    (app, edits)
+}
+
+fn provider_playlist(app: &Rustic) -> usize {
+    let mut playlist = common::playlist("test:playlist:1", "Playlist");
+    playlist.provider = Provider::Soundcloud;
+    playlist.tracks = vec![common::track("test:track:1", "One")];
+    app.library.sync_playlist(&mut playlist).unwrap();
+    playlist.id.unwrap()
+}
+
+#[test]
+fn edits_are_pushed_to_the_provider() {
+    let (app, edits) = app(true);
+    let id = provider_playlist(&app);
+
+    let edit = PlaylistEdit::Rename("Renamed".to_owned());
+    let playlist = app.edit_playlist(id, &edit).unwrap();
+
+    assert_eq!(playlist.title, "Renamed");
+    let edits = edits.lock().unwrap();
+    assert_eq!(edits.len(), 1);
+    assert_eq!(edits[0].0, "test:playlist:1");
+}
+
+#[test]
+fn rejected_edits_leave_the_library_untouched() {
+    let (app, _) = app(false);
+    let id = provider_playlist(&app);
+
+    let result = app.edit_playlist(id, &PlaylistEdit::RemoveTracks(vec![0]));
+
+    assert!(result.is_err());
+    assert_eq!(app.library.get_playlist(id).unwrap().unwrap().tracks.len(), 1);
+}
+
+#[test]
+fn invalid_edits_are_not_pushed() {
+    let (app, edits) = app(true);
+    let id = provider_playlist(&app);
+
+    let result = app.edit_playlist(id, &PlaylistEdit::MoveTrack { from: 0, to: 3 });
+
+    assert!(result.is_err());
+    assert!(edits.lock().unwrap().is_empty());
+}
+
+#[test]
+fn internal_playlists_are_edited_locally() {
+    let (app, edits) = app(true);
+    let playlist = app.create_playlist("Local".to_owned()).unwrap();
+
+    let edit = PlaylistEdit::AddTracks {
+        tracks: vec![common::track("test:track:2", "Two")],
+        position: None,
+    };
+    let playlist = app.edit_playlist(playlist.id.unwrap(), &edit).unwrap();
+
+    assert_eq!(playlist.provider, Provider::Internal);
+    assert_eq!(playlist.tracks.len(), 1);
+    assert!(edits.lock().unwrap().is_empty());
+}
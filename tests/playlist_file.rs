@@ -0,0 +1,96 @@
+mod common;
+
+use rustic_core::library::{Library, MemoryLibrary};
+use rustic_core::{PlaylistFile, PlaylistFormat, Rustic};
+
+const FORMATS: &[PlaylistFormat] = &[PlaylistFormat::M3u8, PlaylistFormat::Pls, PlaylistFormat::Xspf];
+
+fn playlist_file() -> PlaylistFile {
+    let mut first = common::track("test:track:1", "Sweet & Sour");
+    first.artist = Some(common::artist("test:artist:1", "Artist"));
+    first.duration = Some(215);
+    let second = common::track("test:track:2", "Second <Track>");
+    PlaylistFile::new(Some("Playlist".to_owned()), &[first, second])
+}
+
+#[test]
+fn formats_round_trip() {
+    for format in FORMATS {
+        let mut file = playlist_file();
+        if *format != PlaylistFormat::Xspf {
+            file.entries[0].album = None;
+        }
+
+        let content = file.write(*format);
+        let parsed = PlaylistFile::parse(&content, *format).unwrap();
+
+        assert_eq!(parsed, file, "{:?}", format);
+    }
+}
+
+#[test]
+fn parses_m3u_from_other_players() {
+    let content = "#EXTM3U\n\
+                   #EXTINF:123,Some Artist - Some Title\n\
+                   /music/some.mp3\n\
+                   # comment\n\
+                   \n\
+                   http://example.com/stream\n";
+
+    let file = PlaylistFile::parse(content, PlaylistFormat::M3u8).unwrap();
+
+    assert_eq!(file.entries.len(), 2);
+    assert_eq!(file.entries[0].location, "/music/some.mp3");
+    assert_eq!(file.entries[0].artist.as_deref(), Some("Some Artist"));
+    assert_eq!(file.entries[0].title.as_deref(), Some("Some Title"));
+    assert_eq!(file.entries[0].duration, Some(123));
+    assert_eq!(file.entries[1].location, "http://example.com/stream");
+    assert_eq!(file.entries[1].title, None);
+}
+
+#[test]
+fn parses_pls_in_number_order() {
+    let content = "[playlist]\n\
+                   File2=second.mp3\n\
+                   File1=first.mp3\n\
+                   Title1=First\n\
+                   Length1=-1\n\
+                   NumberOfEntries=2\n\
+                   Version=2\n";
+
+    let file = PlaylistFile::parse(content, PlaylistFormat::Pls).unwrap();
+
+    let locations: Vec<&str> = file.entries.iter().map(|e| e.location.as_str()).collect();
+    assert_eq!(locations, vec!["first.mp3", "second.mp3"]);
+    assert_eq!(file.entries[0].title.as_deref(), Some("First"));
+    assert_eq!(file.entries[0].duration, None);
+    assert!(PlaylistFile::parse("File1=first.mp3", PlaylistFormat::Pls).is_err());
+}
+
+#[test]
+fn import_reports_unmatched_entries() {
+    let library = MemoryLibrary::new();
+    library
+        .sync_track(&mut common::track("test:track:1", "Known"))
+        .unwrap();
+    let app = Rustic::new(Box::new(library), vec![]).unwrap();
+    let content = "#EXTM3U\n\
+                   test:track:1\n\
+                   /music/unknown.mp3\n\
+                   test:track:2\n";
+
+    let result = app
+        .import_playlist("Imported".to_owned(), content, PlaylistFormat::M3u8)
+        .unwrap();
+
+    assert_eq!(result.playlist.title, "Imported");
+    assert_eq!(result.playlist.tracks.len(), 1);
+    assert_eq!(result.playlist.tracks[0].uri, "test:track:1");
+    let unmatched: Vec<&str> = result.unmatched.iter().map(|e| e.location.as_str()).collect();
+    assert_eq!(unmatched, vec!["/music/unknown.mp3", "test:track:2"]);
+
+    let exported = app
+        .export_playlist(result.playlist.id.unwrap(), PlaylistFormat::Xspf)
+        .unwrap();
+    assert!(exported.contains("<location>test:track:1</location>"));
+}
@@ -0,0 +1,236 @@
+mod common;
+
+use common::TempDir;
+use rustic_core::scrobble::{LastFm, ListenBrainz, Scrobbler};
+use rustic_core::Track;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone)]
+struct Request {
+    path: String,
+    headers: HashMap<String, String>,
+    body: String,
+}
+
+/// Minimal http server answering with the queued responses, then with 200
+#[derive(Clone)]
+struct MockServer {
+    url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+    responses: Arc<Mutex<Vec<(u16, String)>>>,
+}
+
+impl MockServer {
+    fn start() -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = MockServer {
+            url: format!("http://{}", listener.local_addr().unwrap()),
+            requests: Arc::default(),
+            responses: Arc::default(),
+        };
+        let state = server.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let path = line.split_whitespace().nth(1).unwrap_or_default().to_owned();
+                let mut headers = HashMap::new();
+                loop {
+                    line.clear();
+                    reader.read_line(&mut line).unwrap();
+                    let header = line.trim_end();
+                    if header.is_empty() {
+                        break;
+                    }
+                    let (name, value) = header.split_at(header.find(':').unwrap());
+                    headers.insert(name.to_lowercase(), value[1..].trim().to_owned());
+                }
+                let length = headers
+                    .get("content-length")
+                    .map(|length| length.parse().unwrap())
+                    .unwrap_or(0);
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                state.requests.lock().unwrap().push(Request {
+                    path,
+                    headers,
+                    body: String::from_utf8(body).unwrap(),
+                });
+                let (status, body) = {
+                    let mut responses = state.responses.lock().unwrap();
+                    if responses.is_empty() {
+                        (200, "{}".to_owned())
+                    } else {
+                        responses.remove(0)
+                    }
+                };
+                let response = format!(
+                    "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        server
+    }
+
+    fn respond(&self, status: u16, body: &str) {
+        self.responses.lock().unwrap().push((status, body.to_owned()));
+    }
+
+    fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn track(uri: &str, duration: Option<u64>) -> Track {
+    let mut track = common::track(uri, "Title");
+    track.artist = Some(common::artist("test:artist:1", "Artist"));
+    track.album = Some(common::album("test:album:1", "Album"));
+    track.duration = duration;
+    track
+}
+
+fn timestamp(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+fn listenbrainz(server: &MockServer, dir: &TempDir) -> Scrobbler {
+    let service = ListenBrainz::with_url(server.url.clone(), "token".to_owned());
+    Scrobbler::new(Box::new(service), dir.file("queue.json")).unwrap()
+}
+
+#[test]
+fn listenbrainz_submits_listens() {
+    let server = MockServer::start();
+    let dir = TempDir::new();
+    let scrobbler = listenbrainz(&server, &dir);
+
+    scrobbler.scrobble(&track("test:track:1", Some(200)), timestamp(1000)).unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].path, "/1/submit-listens");
+    assert_eq!(requests[0].headers["authorization"], "Token token");
+    let body: Value = serde_json::from_str(&requests[0].body).unwrap();
+    assert_eq!(body["listen_type"], "single");
+    let listen = &body["payload"][0];
+    assert_eq!(listen["listened_at"], 1000);
+    assert_eq!(listen["track_metadata"]["artist_name"], "Artist");
+    assert_eq!(listen["track_metadata"]["track_name"], "Title");
+    assert_eq!(listen["track_metadata"]["release_name"], "Album");
+    assert_eq!(listen["track_metadata"]["additional_info"]["duration_ms"], 200_000);
+    assert_eq!(scrobbler.queued(), 0);
+}
+
+#[test]
+fn lastfm_signs_requests() {
+    let server = MockServer::start();
+    let service = LastFm::with_url(
+        format!("{}/2.0/", server.url),
+        "key".to_owned(),
+        "secret".to_owned(),
+        "session".to_owned(),
+    );
+    let dir = TempDir::new();
+    let scrobbler = Scrobbler::new(Box::new(service), dir.file("queue.json")).unwrap();
+
+    scrobbler.scrobble(&track("test:track:1", Some(200)), timestamp(1000)).unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].path, "/2.0/");
+    let params: HashMap<String, String> = url::form_urlencoded::parse(requests[0].body.as_bytes())
+        .into_owned()
+        .collect();
+    assert_eq!(params["method"], "track.scrobble");
+    assert_eq!(params["artist[0]"], "Artist");
+    assert_eq!(params["track[0]"], "Title");
+    assert_eq!(params["album[0]"], "Album");
+    assert_eq!(params["timestamp[0]"], "1000");
+    assert_eq!(params["format"], "json");
+    let mut signed: Vec<_> = params
+        .iter()
+        .filter(|(key, _)| *key != "format" && *key != "api_sig")
+        .collect();
+    signed.sort();
+    let mut signature: String = signed.into_iter().map(|(key, value)| format!("{}{}", key, value)).collect();
+    signature.push_str("secret");
+    assert_eq!(params["api_sig"], format!("{:x}", md5::compute(signature)));
+}
+
+#[test]
+fn unavailable_service_keeps_listens_queued() {
+    let server = MockServer::start();
+    let dir = TempDir::new();
+    let scrobbler = listenbrainz(&server, &dir);
+    server.respond(503, "");
+    server.respond(503, "");
+
+    scrobbler.scrobble(&track("test:track:1", Some(200)), timestamp(1000)).unwrap();
+    scrobbler.scrobble(&track("test:track:2", Some(200)), timestamp(2000)).unwrap();
+    assert_eq!(scrobbler.queued(), 2);
+    assert_eq!(scrobbler.flush().unwrap(), 0);
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    let body: Value = serde_json::from_str(&requests[2].body).unwrap();
+    assert_eq!(body["listen_type"], "import");
+    assert_eq!(body["payload"].as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn rejected_listens_are_dropped() {
+    let server = MockServer::start();
+    let dir = TempDir::new();
+    let scrobbler = listenbrainz(&server, &dir);
+    server.respond(400, "{\"error\": \"invalid\"}");
+
+    scrobbler.scrobble(&track("test:track:1", Some(200)), timestamp(1000)).unwrap();
+
+    assert_eq!(scrobbler.queued(), 0);
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[test]
+fn skips_short_and_anonymous_tracks() {
+    let server = MockServer::start();
+    let dir = TempDir::new();
+    let scrobbler = listenbrainz(&server, &dir);
+    let mut anonymous = track("test:track:3", Some(200));
+    anonymous.artist = None;
+
+    scrobbler.scrobble(&track("test:track:1", Some(30)), timestamp(1000)).unwrap();
+    scrobbler.scrobble(&track("test:track:2", None), timestamp(1000)).unwrap();
+    scrobbler.scrobble(&anonymous, timestamp(1000)).unwrap();
+
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn queue_survives_restart() {
+    let server = MockServer::start();
+    let dir = TempDir::new();
+    let path = dir.file("queue.json");
+    server.respond(503, "");
+    let service = ListenBrainz::with_url(server.url.clone(), "token".to_owned());
+    let scrobbler = Scrobbler::new(Box::new(service), path.clone()).unwrap();
+    scrobbler.scrobble(&track("test:track:1", Some(200)), timestamp(1000)).unwrap();
+    drop(scrobbler);
+
+    let service = ListenBrainz::with_url(server.url.clone(), "token".to_owned());
+    let scrobbler = Scrobbler::new(Box::new(service), path).unwrap();
+    assert_eq!(scrobbler.queued(), 1);
+    assert_eq!(scrobbler.flush().unwrap(), 0);
+    assert_eq!(server.requests().len(), 2);
+}
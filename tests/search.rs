@@ -0,0 +1,100 @@
+mod common;
+
+use common::MockProvider;
+use rustic_core::library::{Library, MemoryLibrary};
+use rustic_core::provider::Provider;
+use rustic_core::{Rustic, SearchFailureReason};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn app(providers: Vec<MockProvider>) -> Arc<Rustic> {
+    let library = MemoryLibrary::new();
+    library
+        .sync_track(&mut common::track("test:track:1", "Song"))
+        .unwrap();
+    let providers = providers.into_iter().map(MockProvider::shared).collect();
+    Rustic::new(Box::new(library), providers).unwrap()
+}
+
+#[test]
+fn search_merges_and_dedups_by_uri() {
+    let app = app(vec![
+        MockProvider::new(Provider::Soundcloud).with_tracks(vec![
+            common::track("test:track:1", "Song"),
+            common::track("test:track:2", "Song 2"),
+        ]),
+        MockProvider::new(Provider::Spotify)
+            .with_tracks(vec![common::track("test:track:2", "Song 2")]),
+    ]);
+
+    let search = app.search("song", Duration::from_secs(1)).unwrap();
+
+    let uris: Vec<&str> = search.results.tracks.iter().map(|t| t.uri.as_str()).collect();
+    assert_eq!(uris, vec!["test:track:1", "test:track:2"]);
+    assert!(search.results.tracks[0].id.is_some(), "library results come first");
+    assert!(search.failures.is_empty());
+}
+
+#[test]
+fn search_reports_failed_providers() {
+    let app = app(vec![
+        MockProvider::new(Provider::Soundcloud).unavailable(),
+        MockProvider::new(Provider::Spotify)
+            .with_tracks(vec![common::track("test:track:2", "Song 2")]),
+    ]);
+
+    let search = app.search("song", Duration::from_secs(1)).unwrap();
+
+    assert_eq!(search.results.tracks.len(), 2);
+    assert_eq!(search.failures.len(), 1);
+    assert_eq!(search.failures[0].index, 0);
+    assert_eq!(search.failures[0].provider, Some(Provider::Soundcloud));
+    match search.failures[0].reason {
+        SearchFailureReason::Error(_) => {}
+        ref reason => panic!("unexpected reason {:?}", reason),
+    }
+}
+
+#[test]
+fn search_times_out_slow_providers() {
+    let app = app(vec![
+        MockProvider::new(Provider::Soundcloud).with_delay(Duration::from_secs(5)),
+        MockProvider::new(Provider::Spotify)
+            .with_tracks(vec![common::track("test:track:2", "Song 2")]),
+    ]);
+
+    let started = Instant::now();
+    let search = app.search("song", Duration::from_millis(200)).unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert_eq!(search.results.tracks.len(), 2);
+    assert_eq!(search.failures.len(), 1);
+    assert_eq!(search.failures[0].index, 0);
+    match search.failures[0].reason {
+        SearchFailureReason::Timeout => {}
+        ref reason => panic!("unexpected reason {:?}", reason),
+    }
+}
+
+#[test]
+fn search_runs_concurrently() {
+    let app = app(vec![MockProvider::new(Provider::Soundcloud)
+        .with_delay(Duration::from_millis(300))
+        .with_tracks(vec![common::track("test:track:2", "Song 2")])]);
+    // leaves a search hanging in the provider
+    app.search("song", Duration::from_millis(50)).unwrap();
+
+    let searches: Vec<_> = (0..3)
+        .map(|_| {
+            let app = Arc::clone(&app);
+            thread::spawn(move || app.search("song", Duration::from_secs(2)).unwrap())
+        })
+        .collect();
+
+    for search in searches {
+        let search = search.join().unwrap();
+        assert_eq!(search.results.tracks.len(), 2);
+        assert!(search.failures.is_empty());
+    }
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use rustic_core::library::{Genre, LibraryEvent, MetaKey, MetaValue, Page, SearchResults};
+use rustic_core::provider::{ProviderFolder, ProviderItem, ProviderItemType};
+use rustic_core::{Album, Artist, PlayerState, Playlist, Provider, Track};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Asserts that deserializing the json of `value` and serializing it again yields the same json
+fn assert_round_trip<T: Serialize + DeserializeOwned>(value: &T) {
+    let json = serde_json::to_value(value).unwrap();
+    let restored: T = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(serde_json::to_value(&restored).unwrap(), json);
+}
+
+fn artist() -> Artist {
+    let mut artist = common::artist("test:artist:1", "Artist");
+    artist.id = Some(1);
+    artist.image_url = Some("http://example.com/artist.png".to_owned());
+    artist
+        .meta
+        .insert(MetaKey::MusicBrainzArtistId, MetaValue::String("mbid".to_owned()));
+    artist
+}
+
+fn album() -> Album {
+    let mut album = common::album("test:album:1", "Album");
+    album.artist = Some(artist());
+    album.artist_id = Some(1);
+    album.meta.insert(MetaKey::Year, MetaValue::Int(1997));
+    album
+}
+
+fn track() -> Track {
+    let mut track = common::track("test:track:1", "Track");
+    track.id = Some(3);
+    track.artist = Some(artist());
+    track.album = Some(album());
+    track.duration = Some(215);
+    track.provider = Provider::Spotify;
+    track.meta.insert(MetaKey::Explicit, MetaValue::Bool(true));
+    track.genres = vec![Genre::new("Jazz"), Genre::new("Soul")];
+    track.meta.insert(MetaKey::Popularity, MetaValue::Float(0.75));
+    track.meta.insert(MetaKey::TrackNumber, MetaValue::Int(4));
+    track.meta.insert(MetaKey::from("mood"), MetaValue::String("calm".to_owned()));
+    track
+}
+
+fn playlist() -> Playlist {
+    let mut playlist = common::playlist("test:playlist:1", "Playlist");
+    playlist.tracks = vec![track(), common::track("test:track:2", "Other")];
+    playlist
+}
+
+#[test]
+fn library_models_round_trip() {
+    assert_round_trip(&track());
+    let mut album = album();
+    album.tracks = vec![track()];
+    assert_round_trip(&album);
+    let mut artist = artist();
+    artist.albums = vec![album];
+    artist.top_tracks = vec![track()];
+    assert_round_trip(&artist);
+    assert_round_trip(&playlist());
+}
+
+#[test]
+fn embedded_relations_survive_round_trip() {
+    let json = serde_json::to_string(&track()).unwrap();
+    let restored: Track = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(restored.artist.unwrap().name, "Artist");
+    let album = restored.album.unwrap();
+    assert_eq!(album.artist.unwrap().uri, "test:artist:1");
+    assert_eq!(album.meta.get(&MetaKey::Year), Some(&MetaValue::Int(1997)));
+    assert_eq!(restored.meta, track().meta);
+}
+
+#[test]
+fn providers_round_trip() {
+    for provider in &[
+        Provider::Pocketcasts,
+        Provider::Soundcloud,
+        Provider::GooglePlayMusic,
+        Provider::Spotify,
+        Provider::LocalMedia,
+        Provider::Internal,
+    ] {
+        let json = serde_json::to_string(provider).unwrap();
+        let restored: Provider = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, *provider);
+    }
+    assert_eq!(serde_json::to_string(&Provider::LocalMedia).unwrap(), "\"local\"");
+}
+
+#[test]
+fn provider_models_round_trip() {
+    let items = vec![
+        ProviderItem {
+            label: "Track".to_owned(),
+            data: ProviderItemType::Track(track()),
+        },
+        ProviderItem {
+            label: "Album".to_owned(),
+            data: ProviderItemType::Album(album()),
+        },
+        ProviderItem {
+            label: "Artist".to_owned(),
+            data: ProviderItemType::Artist(artist()),
+        },
+        ProviderItem {
+            label: "Playlist".to_owned(),
+            data: ProviderItemType::Playlist(playlist()),
+        },
+        ProviderItem {
+            label: "Jazz".to_owned(),
+            data: ProviderItemType::Genre(Genre::new("Jazz")),
+        },
+    ];
+    for item in &items {
+        assert_round_trip(item);
+    }
+    assert_round_trip(&ProviderFolder::new(vec!["Folder".to_owned()], items));
+}
+
+#[test]
+fn results_and_events_round_trip() {
+    assert_round_trip(&SearchResults {
+        tracks: vec![track()],
+        albums: vec![album()],
+        artists: vec![artist()],
+        playlists: vec![playlist()],
+    });
+    assert_round_trip(&Page {
+        items: vec![track()],
+        total: 10,
+    });
+    assert_round_trip(&LibraryEvent::TrackAdded(track()));
+    assert_round_trip(&LibraryEvent::PlaylistRemoved(playlist()));
+    assert_round_trip(&PlayerState::Pause);
+}
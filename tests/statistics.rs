@@ -0,0 +1,30 @@
+mod common;
+
+use rustic_core::cache::CacheStatistics;
+use rustic_core::library::MemoryLibrary;
+use rustic_core::Rustic;
+
+#[test]
+fn combines_library_and_cache() {
+    let app = Rustic::new(Box::new(MemoryLibrary::new()), vec![]).unwrap();
+    app.library
+        .sync_track(&mut common::track("test:track:1", "Track"))
+        .unwrap();
+    app.cache
+        .coverart
+        .write()
+        .unwrap()
+        .insert("http://example.com/missing.jpg".to_owned(), "missing.png".to_owned());
+
+    let statistics = app.statistics(10).unwrap();
+
+    assert_eq!(statistics.library.tracks, 1);
+    assert_eq!(statistics.library.tracks_without_artwork, 1);
+    assert_eq!(
+        statistics.cache,
+        CacheStatistics {
+            coverart: 1,
+            coverart_bytes: 0,
+        }
+    );
+}